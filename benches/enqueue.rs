@@ -0,0 +1,65 @@
+//! Times the two hot `enqueue_waiter` paths and reports the waker clones each makes:
+//!
+//! - queuing an elem for the first time, which must clone the context's waker once,
+//! - enqueuing an elem already queued by the same task, which only reads the elem to find its
+//!   waker is current.
+//!
+//! Run with:
+//!
+//!     cargo bench --bench enqueue --features metrics
+//!
+//! See `harness` for why criterion isn't used.
+
+mod harness;
+
+use harness::{arc_waker, report_clones, time};
+
+use broadcast_rs::waiter::{Elem, List};
+
+use std::hint::black_box;
+use std::pin::pin;
+use std::task::Context;
+
+const ITERS: u32 = 1_000_000;
+
+fn main() {
+    let waker = arc_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    {
+        let mut list: List = List::new();
+        let elapsed = time(1, |timer| {
+            timer.start();
+            for _ in 0..ITERS {
+                // A fresh elem each time: one removed and queued again would still hold its
+                // waker.
+                // Safety: the elem is removed from the list before it is dropped.
+                let elem = pin!(unsafe { Elem::new() });
+                black_box(list.enqueue_waiter(&elem, &mut cx));
+                // Safety: the elem is only queued in this list.
+                unsafe { list.remove_waiter(&elem) };
+            }
+            timer.stop();
+        });
+        report_clones("first registration", elapsed, ITERS as u64, list.waker_clones());
+    }
+
+    {
+        let mut list: List = List::new();
+        // Safety: the elem is removed from the list before it is dropped.
+        let elem = pin!(unsafe { Elem::new() });
+        list.enqueue_waiter(&elem, &mut cx);
+        let elapsed = time(1, |timer| {
+            timer.start();
+            for _ in 0..ITERS {
+                black_box(list.enqueue_waiter(&elem, &mut cx));
+            }
+            timer.stop();
+        });
+        // Leave out the clone made by the initial registration.
+        let clones = list.waker_clones() - 1;
+        report_clones("refresh same task", elapsed, ITERS as u64, clones);
+        // Safety: the elem is only queued in this list.
+        unsafe { list.remove_waiter(&elem) };
+    }
+}
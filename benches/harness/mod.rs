@@ -0,0 +1,77 @@
+//! The timing and reporting shared by the benches, included by each with `mod harness;`.
+//!
+//! The benches don't use criterion, or any other benchmarking crate. The crate has no required
+//! dependencies and is meant to be copied into a project as much as depended on, and the benches
+//! keep to that: they build offline from the crate alone. What they are for is a baseline to
+//! compare a change to the waiter list against, for which the mean time per operation over a
+//! fixed number of rounds is enough. They make no attempt at criterion's warm up, outlier
+//! detection or comparison against a saved run, so differences of a few percent are noise.
+
+// Each bench uses only part of the harness.
+#![allow(dead_code)]
+
+use std::sync::Arc;
+use std::task::{Context, Wake, Waker};
+use std::time::{Duration, Instant};
+
+/// A context whose waker does nothing and costs nothing to clone.
+pub fn noop_context() -> Context<'static> {
+    Context::from_waker(Waker::noop())
+}
+
+/// A waker backed by an `Arc`, so a clone costs a reference count increment as it does for a
+/// runtime's waker.
+pub fn arc_waker() -> Waker {
+    struct ArcWaker;
+
+    impl Wake for ArcWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    Waker::from(Arc::new(ArcWaker))
+}
+
+/// Runs `f` `rounds` times, returning the time spent in all of them.
+///
+/// `f` is handed a `Timer` to leave the setup and teardown of each round out of the time.
+pub fn time(rounds: u32, mut f: impl FnMut(&mut Timer)) -> Duration {
+    let mut timer = Timer { elapsed: Duration::ZERO, start: None };
+    for _ in 0..rounds {
+        f(&mut timer);
+    }
+    timer.elapsed
+}
+
+/// Accumulates the time of the timed part of each round.
+pub struct Timer {
+    elapsed: Duration,
+    start: Option<Instant>,
+}
+
+impl Timer {
+    /// Starts timing.
+    pub fn start(&mut self) {
+        self.start = Some(Instant::now());
+    }
+
+    /// Stops timing, adding the time since `start` to the total.
+    pub fn stop(&mut self) {
+        let start = self.start.take().expect("timer stopped without being started");
+        self.elapsed += start.elapsed();
+    }
+}
+
+/// Prints the mean time per operation of a benchmark that made `ops` operations in `elapsed`.
+pub fn report(name: &str, elapsed: Duration, ops: u64) {
+    println!("{:<28} {:>10.2} ns/op", name, per_op(elapsed, ops));
+}
+
+/// As `report`, also printing the mean number of waker clones per operation.
+pub fn report_clones(name: &str, elapsed: Duration, ops: u64, clones: u64) {
+    let clones = clones as f64 / ops as f64;
+    println!("{:<28} {:>10.2} ns/op {:>6.2} waker clones/op", name, per_op(elapsed, ops), clones);
+}
+
+fn per_op(elapsed: Duration, ops: u64) -> f64 {
+    elapsed.as_nanos() as f64 / ops as f64
+}
@@ -0,0 +1,144 @@
+//! Baseline timings for the waiter list's hot operations, with pinned elems and a noop waker.
+//!
+//! Run with:
+//!
+//!     cargo bench --bench waiter
+//!
+//! No runtime is involved. Each benchmark reports the mean time per operation over a fixed
+//! number of rounds; see `harness` for why criterion isn't used.
+
+mod harness;
+
+use harness::{noop_context, report, time};
+
+use broadcast_rs::waiter::{CachePadded, Elem, List};
+
+use std::hint::black_box;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::thread;
+
+const ROUNDS: u32 = 10_000;
+
+/// Returns `n` elems, boxed so they stay pinned wherever the vector goes.
+fn elems(n: usize) -> Vec<Pin<Box<Elem>>> {
+    // Safety: every benchmark empties the list before its elems are dropped.
+    (0..n).map(|_| Box::pin(unsafe { Elem::new() })).collect()
+}
+
+/// Queues 64 fresh waiters, then takes them all out again with `clear`.
+fn enqueue_waiter() {
+    let mut cx = noop_context();
+    let mut list: List = List::new();
+    let elems = elems(64);
+
+    let elapsed = time(ROUNDS, |timer| {
+        timer.start();
+        for elem in &elems {
+            black_box(list.enqueue_waiter(elem, &mut cx));
+        }
+        timer.stop();
+        list.clear();
+    });
+    report("enqueue_waiter", elapsed, ROUNDS as u64 * elems.len() as u64);
+}
+
+/// Wakes a whole list at once, for a range of list lengths.
+fn awake_waiters() {
+    let mut cx = noop_context();
+    for n in [1, 16, 256] {
+        let mut list: List = List::new();
+        let elems = elems(n);
+
+        let elapsed = time(ROUNDS, |timer| {
+            for elem in &elems {
+                list.enqueue_waiter(elem, &mut cx);
+            }
+            timer.start();
+            black_box(list.awake_waiters());
+            timer.stop();
+        });
+        report(&format!("awake_waiters/{}", n), elapsed, ROUNDS as u64 * n as u64);
+    }
+}
+
+/// Wakes the waiters of a 256 long list one at a time.
+fn wake_one() {
+    let mut cx = noop_context();
+    let mut list: List = List::new();
+    let elems = elems(256);
+
+    let elapsed = time(ROUNDS, |timer| {
+        for elem in &elems {
+            list.enqueue_waiter(elem, &mut cx);
+        }
+        timer.start();
+        while black_box(list.wake_one()) {}
+        timer.stop();
+    });
+    report("wake_one", elapsed, ROUNDS as u64 * elems.len() as u64);
+}
+
+/// Two threads each queue and wake waiters on their own list, where the two lists sit next to
+/// each other in one struct, first unpadded and then each on its own cache line.
+fn contention() {
+    struct Adjacent {
+        a: Mutex<List>,
+        b: Mutex<List>,
+    }
+
+    struct Padded {
+        a: Mutex<CachePadded>,
+        b: Mutex<CachePadded>,
+    }
+
+    fn churn<L>(list: &Mutex<L>, get: fn(&mut L) -> &mut List) {
+        let mut cx = noop_context();
+        let elems = elems(8);
+        for _ in 0..ROUNDS {
+            let mut guard = list.lock().unwrap();
+            let list = get(&mut guard);
+            for elem in &elems {
+                list.enqueue_waiter(elem, &mut cx);
+            }
+            black_box(list.awake_waiters());
+        }
+    }
+
+    let ops = 2 * ROUNDS as u64 * 8;
+
+    let adjacent = Adjacent {
+        a: Mutex::new(List::new()),
+        b: Mutex::new(List::new()),
+    };
+    let elapsed = time(1, |timer| {
+        timer.start();
+        thread::scope(|scope| {
+            scope.spawn(|| churn(&adjacent.a, |list| list));
+            scope.spawn(|| churn(&adjacent.b, |list| list));
+        });
+        timer.stop();
+    });
+    report("contention/adjacent", elapsed, ops);
+
+    let padded = Padded {
+        a: Mutex::new(CachePadded::default()),
+        b: Mutex::new(CachePadded::default()),
+    };
+    let elapsed = time(1, |timer| {
+        timer.start();
+        thread::scope(|scope| {
+            scope.spawn(|| churn(&padded.a, |list| &mut **list));
+            scope.spawn(|| churn(&padded.b, |list| &mut **list));
+        });
+        timer.stop();
+    });
+    report("contention/cache_padded", elapsed, ops);
+}
+
+fn main() {
+    enqueue_waiter();
+    awake_waiters();
+    wake_one();
+    contention();
+}
@@ -0,0 +1,237 @@
+//! barrier - let a number of tasks wait for each other
+//!
+//! A `Barrier` built on the `waiter` module. The first `n - 1` tasks to call `wait` are queued;
+//! the `n`th releases them all with `awake_waiters` and is told it is the leader. The barrier is
+//! then ready for the next generation, reusing the same waiter list.
+//!
+//! A task whose `wait` future is dropped before the barrier trips no longer counts as arrived.
+//!
+//! # Examples
+//!
+//! ```
+//! use broadcast_rs::barrier::Barrier;
+//! use std::sync::Arc;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let barrier = Arc::new(Barrier::new(4));
+//!     let handles: Vec<_> = (0..4)
+//!         .map(|_| {
+//!             let barrier = barrier.clone();
+//!             tokio::spawn(async move { barrier.wait().await.is_leader() })
+//!         })
+//!         .collect();
+//!
+//!     let mut leaders = 0;
+//!     for handle in handles {
+//!         if handle.await.unwrap() {
+//!             leaders += 1;
+//!         }
+//!     }
+//!     assert_eq!(leaders, 1);
+//! }
+//! ```
+
+use crate::waiter::{List, Registration};
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+
+/// A barrier letting a number of tasks wait for each other.
+///
+/// See the module documentation.
+pub struct Barrier {
+    /// Number of tasks needed to trip the barrier.
+    n: usize,
+
+    state: Mutex<State>,
+}
+
+struct State {
+    /// Tasks waiting in the current generation.
+    arrived: usize,
+
+    /// Number of times the barrier has tripped.
+    generation: u64,
+
+    waiters: List<()>,
+}
+
+/// Returned by [`Barrier::wait`] once the barrier trips.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BarrierWaitResult(bool);
+
+impl BarrierWaitResult {
+    /// Returns true for exactly one of the tasks released by each trip of the barrier, the one
+    /// whose arrival tripped it.
+    pub fn is_leader(&self) -> bool {
+        self.0
+    }
+}
+
+/// Future returned by [`Barrier::wait`].
+pub struct Wait<'a> {
+    barrier: &'a Barrier,
+
+    /// Entry in the barrier's waiter list, registered once the elem has been queued, until the
+    /// barrier trips.
+    registration: Registration<()>,
+}
+
+unsafe impl<'a> Send for Wait<'a> {}
+unsafe impl<'a> Sync for Wait<'a> {}
+
+impl Barrier {
+    /// Creates a barrier that trips once `n` tasks are waiting. A barrier for zero tasks trips
+    /// on every call, as one for a single task does.
+    pub fn new(n: usize) -> Barrier {
+        Barrier {
+            n: n.max(1),
+            state: Mutex::new(State {
+                arrived: 0,
+                generation: 0,
+                waiters: List::new(),
+            }),
+        }
+    }
+
+    /// Waits until `n` tasks, counting this one, are waiting.
+    pub fn wait(&self) -> Wait<'_> {
+        Wait {
+            barrier: self,
+            // Safety: the elem is removed from the barrier's list by Wait's drop.
+            registration: unsafe { Registration::new() },
+        }
+    }
+
+    /// Returns the number of times the barrier has tripped.
+    pub fn generation(&self) -> u64 {
+        self.state.lock().unwrap().generation
+    }
+}
+
+impl<'a> Future for Wait<'a> {
+    type Output = BarrierWaitResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<BarrierWaitResult> {
+        let barrier = self.barrier;
+        let registration = &self.registration;
+        let mut state = barrier.state.lock().unwrap();
+
+        if !registration.is_registered() {
+            if state.arrived + 1 == barrier.n {
+                state.arrived = 0;
+                state.generation += 1;
+                state.waiters.awake_waiters();
+                return Poll::Ready(BarrierWaitResult(true));
+            }
+            state.arrived += 1;
+            registration.register(&mut state.waiters, cx);
+            return Poll::Pending;
+        }
+
+        if state.waiters.is_queued(registration.elem()) {
+            registration.register(&mut state.waiters, cx);
+            return Poll::Pending;
+        }
+
+        registration.unregister();
+        Poll::Ready(BarrierWaitResult(false))
+    }
+}
+
+impl<'a> Drop for Wait<'a> {
+    fn drop(&mut self) {
+        if !self.registration.is_registered() {
+            return;
+        }
+        let mut state = self.barrier.state.lock().unwrap();
+
+        // Once woken, the barrier has tripped and the arrival belongs to a past generation.
+        if state.waiters.is_queued(self.registration.elem()) {
+            // Safety: the elem is only ever queued in the barrier's list.
+            unsafe {
+                state.waiters.remove_waiter(self.registration.elem());
+            }
+            state.arrived -= 1;
+        }
+    }
+}
+
+impl fmt::Debug for Barrier {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Barrier({})", self.n)
+    }
+}
+
+impl<'a> fmt::Debug for Wait<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Wait")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_test::task;
+
+    fn result(poll: Poll<BarrierWaitResult>) -> BarrierWaitResult {
+        match poll {
+            Poll::Ready(result) => result,
+            Poll::Pending => panic!("barrier not tripped"),
+        }
+    }
+
+    #[test]
+    fn trips_with_one_leader() {
+        let barrier = Barrier::new(3);
+        let mut a = task::spawn(barrier.wait());
+        let mut b = task::spawn(barrier.wait());
+        assert!(a.poll().is_pending());
+        assert!(b.poll().is_pending());
+
+        let mut c = task::spawn(barrier.wait());
+        assert!(result(c.poll()).is_leader());
+        assert!(a.is_woken() && b.is_woken());
+        assert!(!result(a.poll()).is_leader());
+        assert!(!result(b.poll()).is_leader());
+    }
+
+    #[test]
+    fn reused_across_generations() {
+        let barrier = Barrier::new(2);
+        for generation in 1..=3 {
+            let mut a = task::spawn(barrier.wait());
+            assert!(a.poll().is_pending());
+            let mut b = task::spawn(barrier.wait());
+            assert!(result(b.poll()).is_leader());
+            assert!(!result(a.poll()).is_leader());
+            assert_eq!(barrier.generation(), generation);
+        }
+    }
+
+    #[test]
+    fn cancelled_wait_no_longer_counts() {
+        let barrier = Barrier::new(2);
+        let mut a = task::spawn(barrier.wait());
+        assert!(a.poll().is_pending());
+        drop(a);
+
+        let mut b = task::spawn(barrier.wait());
+        assert!(b.poll().is_pending());
+        let mut c = task::spawn(barrier.wait());
+        assert!(result(c.poll()).is_leader());
+        assert!(b.is_woken());
+        assert!(!result(b.poll()).is_leader());
+    }
+
+    #[test]
+    fn zero_trips_every_time() {
+        let barrier = Barrier::new(0);
+        assert!(result(task::spawn(barrier.wait()).poll()).is_leader());
+        assert!(result(task::spawn(barrier.wait()).poll()).is_leader());
+    }
+}
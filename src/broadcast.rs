@@ -8,8 +8,9 @@
 //!
 //! When a value is sent, **all** [`Receiver`] handles are notified and will
 //! receive the value. The value is stored once inside the channel and cloned on
-//! demand for each receiver. Once all receivers have received a clone of the
-//! value, the value is released from the channel.
+//! demand for each receiver, except the last to receive it, which takes the
+//! stored value itself. Once all receivers have received the value, it is
+//! released from the channel. A channel with a single receiver never clones.
 //!
 //! A channel is created by calling [`channel`], specifying the maximum number
 //! of messages the channel can retain at any given time.
@@ -44,6 +45,16 @@
 //! either by aborting its task or by tolerating lost messages and resuming
 //! consumption of the channel.
 //!
+//! ## Overflow policy
+//!
+//! Overwriting the oldest value is the default [`OverflowPolicy`]. A channel
+//! created with [`channel_with_policy`] and [`OverflowPolicy::BlockSender`]
+//! instead never overwrites a value a receiver has yet to see: a full channel
+//! makes [`Sender::send`] fail with `TrySendError::Full`,
+//! [`Sender::send_async`] wait for the slowest receiver and
+//! [`Sender::blocking_send`] park the thread until it moves on, so receivers
+//! never lag and slow receivers push back on senders.
+//!
 //! ## Closing
 //!
 //! When **all** [`Sender`] handles have been dropped, no new values may be
@@ -55,6 +66,12 @@
 //! [`Sender::subscribe`]: crate::sync::broadcast::Sender::subscribe
 //! [`Receiver`]: crate::sync::broadcast::Receiver
 //! [`channel`]: crate::sync::broadcast::channel
+//! [`channel_with_policy`]: crate::sync::broadcast::channel_with_policy
+//! [`OverflowPolicy`]: crate::sync::broadcast::OverflowPolicy
+//! [`OverflowPolicy::BlockSender`]: crate::sync::broadcast::OverflowPolicy::BlockSender
+//! [`Sender::send`]: crate::sync::broadcast::Sender::send
+//! [`Sender::send_async`]: crate::sync::broadcast::Sender::send_async
+//! [`Sender::blocking_send`]: crate::sync::broadcast::Sender::blocking_send
 //! [`RecvError::Lagged`]: crate::sync::broadcast::error::RecvError::Lagged
 //! [`RecvError::Closed`]: crate::sync::broadcast::error::RecvError::Closed
 //! [`recv`]: crate::sync::broadcast::Receiver::recv
@@ -111,17 +128,17 @@
 
 use crate::util::unsafe_cell::UnsafeCell;
 use crate::util::atomic_usize::AtomicUsize;
-use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
-use crate::util::linked_list::{self, LinkedList};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard};
+use crate::waiter::{Elem, List, Registration};
 
+use std::cell::Cell;
 use std::fmt;
-use std::future::Future;
-use std::marker::PhantomPinned;
+use std::future::{poll_fn, Future};
+use std::mem::ManuallyDrop;
 use std::pin::Pin;
-use std::ptr::NonNull;
 use std::sync::atomic::Ordering::SeqCst;
-use std::task::{Context, Poll, Waker};
-use std::usize;
+use std::task::{Context, Poll};
+use std::time::Instant;
 
 /// Sending-half of the [`broadcast`] channel.
 ///
@@ -163,10 +180,8 @@ pub struct Sender<T> {
 /// Must not be used concurrently. Messages may be retrieved using
 /// [`recv`][Receiver::recv].
 ///
-/// To turn this receiver into a `Stream`, you can use the [`BroadcastStream`]
-/// wrapper.
-///
-/// [`BroadcastStream`]: https://docs.rs/tokio-stream/0.1/tokio_stream/wrappers/struct.BroadcastStream.html
+/// With the `stream` feature, the receiver is a `Stream` of the values
+/// received, ending once the channel is closed.
 ///
 /// # Examples
 ///
@@ -200,6 +215,19 @@ pub struct Receiver<T> {
 
     /// Next position to read from
     next: u64,
+
+    /// Entry in the channel's receiver waiter list for `poll_recv`,
+    /// allocated by its first call. Boxed so the receiver can move while it
+    /// is queued.
+    waiter: Option<Box<Registration>>,
+}
+
+/// A sender that does not keep the channel open. Upgrade it to a [`Sender`]
+/// to send.
+///
+/// [`Sender`]: crate::sync::broadcast::Sender
+pub struct WeakSender<T> {
+    shared: Arc<Shared<T>>,
 }
 
 pub mod error {
@@ -207,13 +235,15 @@ pub mod error {
 
     use std::fmt;
 
-    /// Error returned by from the [`send`] function on a [`Sender`].
+    /// Error returned by the [`send_async`] and [`blocking_send`] functions on
+    /// a [`Sender`].
     ///
-    /// A **send** operation can only fail if there are no active receivers,
-    /// implying that the message could never be received. The error contains the
-    /// message being sent as a payload so it can be recovered.
+    /// A waiting **send** operation can only fail if there are no active
+    /// receivers, implying that the message could never be received. The error
+    /// contains the message being sent as a payload so it can be recovered.
     ///
-    /// [`send`]: crate::sync::broadcast::Sender::send
+    /// [`send_async`]: crate::sync::broadcast::Sender::send_async
+    /// [`blocking_send`]: crate::sync::broadcast::Sender::blocking_send
     /// [`Sender`]: crate::sync::broadcast::Sender
     #[derive(Debug)]
     pub struct SendError<T>(pub T);
@@ -226,6 +256,37 @@ pub mod error {
 
     impl<T: fmt::Debug> std::error::Error for SendError<T> {}
 
+    /// Error returned by the [`send`] and [`try_send`] functions on a
+    /// [`Sender`].
+    ///
+    /// Either variant contains the message being sent as a payload so it can
+    /// be recovered.
+    ///
+    /// [`send`]: crate::sync::broadcast::Sender::send
+    /// [`try_send`]: crate::sync::broadcast::Sender::try_send
+    /// [`Sender`]: crate::sync::broadcast::Sender
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum TrySendError<T> {
+        /// The channel is full and its overflow policy is `BlockSender`. A
+        /// later send may succeed once the slowest receiver moves on.
+        Full(T),
+
+        /// There are no active receivers, or the channel was closed, so the
+        /// message could never be received.
+        Closed(T),
+    }
+
+    impl<T> fmt::Display for TrySendError<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                TrySendError::Full(_) => write!(f, "channel full"),
+                TrySendError::Closed(_) => write!(f, "channel closed"),
+            }
+        }
+    }
+
+    impl<T: fmt::Debug> std::error::Error for TrySendError<T> {}
+
     /// An error returned from the [`recv`] function on a [`Receiver`].
     ///
     /// [`recv`]: crate::sync::broadcast::Receiver::recv
@@ -289,6 +350,47 @@ pub mod error {
     }
 
     impl std::error::Error for TryRecvError {}
+
+    /// An error returned from the [`recv_deadline`] function on a [`Receiver`].
+    ///
+    /// [`recv_deadline`]: crate::sync::broadcast::Receiver::recv_deadline
+    /// [`Receiver`]: crate::sync::broadcast::Receiver
+    #[derive(Debug, PartialEq, Eq, Clone)]
+    pub enum RecvTimeoutError {
+        /// The deadline passed before a message was sent.
+        Timeout,
+
+        /// There are no more active senders implying no further messages will ever
+        /// be sent.
+        Closed,
+
+        /// The receiver lagged too far behind. Attempting to receive again will
+        /// return the oldest message still retained by the channel.
+        ///
+        /// Includes the number of skipped messages.
+        Lagged(u64),
+    }
+
+    impl From<RecvError> for RecvTimeoutError {
+        fn from(err: RecvError) -> RecvTimeoutError {
+            match err {
+                RecvError::Closed => RecvTimeoutError::Closed,
+                RecvError::Lagged(amt) => RecvTimeoutError::Lagged(amt),
+            }
+        }
+    }
+
+    impl fmt::Display for RecvTimeoutError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                RecvTimeoutError::Timeout => write!(f, "channel receive timed out"),
+                RecvTimeoutError::Closed => write!(f, "channel closed"),
+                RecvTimeoutError::Lagged(amt) => write!(f, "channel lagged by {}", amt),
+            }
+        }
+    }
+
+    impl std::error::Error for RecvTimeoutError {}
 }
 
 use self::error::*;
@@ -306,6 +408,24 @@ struct Shared<T> {
 
     /// Number of outstanding Sender handles.
     num_tx: AtomicUsize,
+
+    /// What a send to a full channel does.
+    policy: OverflowPolicy,
+}
+
+/// What a send does when the channel is full, every slot holding a value
+/// some receiver has yet to see.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// The oldest value is overwritten. Receivers that had yet to see it
+    /// get `Lagged` from their next receive.
+    #[default]
+    OverwriteOldest,
+
+    /// No value is overwritten until every receiver has seen it. `send`
+    /// fails, `send_async` waits and `blocking_send` parks the thread until the
+    /// slowest receiver moves on.
+    BlockSender,
 }
 
 /// Next position to write a value.
@@ -320,7 +440,11 @@ struct Tail {
     closed: bool,
 
     /// Receivers waiting for a value.
-    waiters: LinkedList<Waiter, <Waiter as linked_list::Link>::Target>,
+    waiters: List,
+
+    /// Senders waiting for a full channel to free a slot, with the
+    /// `BlockSender` policy.
+    send_waiters: List,
 }
 
 /// Slot in the buffer.
@@ -343,31 +467,11 @@ struct Slot<T> {
     val: UnsafeCell<Option<T>>,
 }
 
-/// An entry in the wait queue.
-struct Waiter {
-    /// True if queued.
-    queued: bool,
-
-    /// Task waiting on the broadcast channel.
-    waker: Option<Waker>,
-
-    /// Intrusive linked-list pointers.
-    pointers: linked_list::Pointers<Waiter>,
-
-    /// Should not be `Unpin`.
-    _p: PhantomPinned,
-}
-
-generate_addr_of_methods! {
-    impl<> Waiter {
-        unsafe fn addr_of_pointers(self: NonNull<Self>) -> NonNull<linked_list::Pointers<Waiter>> {
-            &self.pointers
-        }
-    }
-}
-
 struct RecvGuard<'a, T> {
-    slot: RwLockReadGuard<'a, Slot<T>>,
+    shared: &'a Shared<T>,
+
+    /// Released by the guard's drop, ahead of the tail lock.
+    slot: ManuallyDrop<RwLockReadGuard<'a, Slot<T>>>,
 }
 
 /// Receive a value future.
@@ -375,13 +479,39 @@ struct Recv<'a, T> {
     /// Receiver being waited on.
     receiver: &'a mut Receiver<T>,
 
-    /// Entry in the waiter `LinkedList`.
-    waiter: UnsafeCell<Waiter>,
+    /// Entry in the channel's receiver waiter list, registered once the
+    /// elem has been queued.
+    registration: Registration,
 }
 
 unsafe impl<'a, T: Send> Send for Recv<'a, T> {}
 unsafe impl<'a, T: Send> Sync for Recv<'a, T> {}
 
+/// Receive a value future that gives up at a deadline.
+struct RecvDeadline<'a, T> {
+    recv: Recv<'a, T>,
+
+    deadline: Instant,
+
+    /// Entry in the timer's deadline list.
+    elem: Elem<Instant>,
+}
+
+/// Future returned by [`Sender::send_async`].
+pub struct SendAsync<'a, T> {
+    sender: &'a Sender<T>,
+
+    /// The value to send, until it is sent or handed back.
+    value: Cell<Option<T>>,
+
+    /// Entry in the channel's sender waiter list, registered once the elem has been queued,
+    /// until the value is sent or handed back.
+    registration: Registration,
+}
+
+unsafe impl<'a, T: Send> Send for SendAsync<'a, T> {}
+unsafe impl<'a, T: Send> Sync for SendAsync<'a, T> {}
+
 /// Max number of receivers. Reserve space to lock.
 const MAX_RECEIVERS: usize = usize::MAX >> 2;
 
@@ -396,14 +526,14 @@ const MAX_RECEIVERS: usize = usize::MAX >> 2;
 /// `Receiver` handles are created by calling [`Sender::subscribe`].
 ///
 /// If all [`Receiver`] handles are dropped, the `send` method will return a
-/// [`SendError`]. Similarly, if all [`Sender`] handles are dropped, the [`recv`]
-/// method will return a [`RecvError`].
+/// [`TrySendError::Closed`]. Similarly, if all [`Sender`] handles are dropped,
+/// the [`recv`] method will return a [`RecvError`].
 ///
 /// [`Sender`]: crate::sync::broadcast::Sender
 /// [`Sender::subscribe`]: crate::sync::broadcast::Sender::subscribe
 /// [`Receiver`]: crate::sync::broadcast::Receiver
 /// [`recv`]: crate::sync::broadcast::Receiver::recv
-/// [`SendError`]: crate::sync::broadcast::error::SendError
+/// [`TrySendError::Closed`]: crate::sync::broadcast::error::TrySendError::Closed
 /// [`RecvError`]: crate::sync::broadcast::error::RecvError
 ///
 /// # Examples
@@ -436,7 +566,45 @@ const MAX_RECEIVERS: usize = usize::MAX >> 2;
 /// This will panic if `capacity` is equal to `0` or larger
 /// than `usize::MAX / 2`.
 #[track_caller]
-pub fn channel<T: Clone>(mut capacity: usize) -> (Sender<T>, Receiver<T>) {
+pub fn channel<T: Clone>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    channel_with_policy(capacity, OverflowPolicy::default())
+}
+
+/// Creates a broadcast channel as [`channel`] does, with the given policy
+/// for a send to a full channel.
+///
+/// [`channel`]: crate::sync::broadcast::channel
+///
+/// # Examples
+///
+/// ```
+/// use broadcast_rs::broadcast::{self, error::TrySendError, OverflowPolicy};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let (tx, mut rx) = broadcast::channel_with_policy(1, OverflowPolicy::BlockSender);
+///
+///     tx.send(10).unwrap();
+///     // Full until rx sees 10.
+///     assert_eq!(tx.try_send(20), Err(TrySendError::Full(20)));
+///
+///     let sender = tokio::spawn(async move { tx.send_async(20).await.unwrap() });
+///
+///     assert_eq!(rx.recv().await.unwrap(), 10);
+///     assert_eq!(rx.recv().await.unwrap(), 20);
+///     sender.await.unwrap();
+/// }
+/// ```
+///
+/// # Panics
+///
+/// This will panic if `capacity` is equal to `0` or larger
+/// than `usize::MAX / 2`.
+#[track_caller]
+pub fn channel_with_policy<T: Clone>(
+    mut capacity: usize,
+    policy: OverflowPolicy,
+) -> (Sender<T>, Receiver<T>) {
     assert!(capacity > 0, "capacity is empty");
     assert!(capacity <= usize::MAX >> 1, "requested capacity too large");
 
@@ -460,14 +628,17 @@ pub fn channel<T: Clone>(mut capacity: usize) -> (Sender<T>, Receiver<T>) {
             pos: 0,
             rx_cnt: 1,
             closed: false,
-            waiters: LinkedList::new(),
+            waiters: List::new(),
+            send_waiters: List::new(),
         }),
         num_tx: AtomicUsize::new(1),
+        policy,
     });
 
     let rx = Receiver {
         shared: shared.clone(),
         next: 0,
+        waiter: None,
     };
 
     let tx = Sender { shared };
@@ -487,7 +658,8 @@ impl<T> Sender<T> {
     ///
     /// A successful send occurs when there is at least one active [`Receiver`]
     /// handle. An unsuccessful send would be one where all associated
-    /// [`Receiver`] handles have already been dropped.
+    /// [`Receiver`] handles have already been dropped, returning
+    /// `Err(TrySendError::Closed)`.
     ///
     /// # Return
     ///
@@ -505,8 +677,17 @@ impl<T> Sender<T> {
     /// will fail. New [`Receiver`] handles may be created by calling
     /// [`subscribe`].
     ///
+    /// `send` never waits, whatever the channel's policy. With the
+    /// [`OverflowPolicy::BlockSender`] policy, a full channel makes it return
+    /// `Err(TrySendError::Full)`, handing the value back. To wait for room
+    /// instead, use [`send_async`] from async code or [`blocking_send`] from
+    /// synchronous code.
+    ///
     /// [`Receiver`]: crate::sync::broadcast::Receiver
     /// [`subscribe`]: crate::sync::broadcast::Sender::subscribe
+    /// [`OverflowPolicy::BlockSender`]: crate::sync::broadcast::OverflowPolicy::BlockSender
+    /// [`send_async`]: crate::sync::broadcast::Sender::send_async
+    /// [`blocking_send`]: crate::sync::broadcast::Sender::blocking_send
     ///
     /// # Examples
     ///
@@ -532,44 +713,117 @@ impl<T> Sender<T> {
     ///     tx.send(20).unwrap();
     /// }
     /// ```
-    pub fn send(&self, value: T) -> Result<usize, SendError<T>> {
-        let mut tail = self.shared.tail.lock().unwrap();
-
-        if tail.rx_cnt == 0 {
-            return Err(SendError(value));
-        }
-
-        // Position to write into
-        let pos = tail.pos;
-        let rem = tail.rx_cnt;
-        let idx = (pos & self.shared.mask as u64) as usize;
-
-        // Update the tail position
-        tail.pos = tail.pos.wrapping_add(1);
-
-        // Get the slot
-        let mut slot = self.shared.buffer[idx].write().unwrap();
-
-        // Track the position
-        slot.pos = pos;
-
-        // Set remaining receivers
-        slot.rem.with_mut(|v| *v = rem);
+    pub fn send(&self, value: T) -> Result<usize, TrySendError<T>> {
+        self.try_send(value)
+    }
 
-        // Write the value
-        slot.val = UnsafeCell::new(Some(value));
+    /// Attempts to send a value to all active [`Receiver`] handles, without
+    /// waiting.
+    ///
+    /// The same as [`send`], which never waits either, under the name that
+    /// pairs it with [`Receiver::try_recv`]: a full channel, with the
+    /// [`OverflowPolicy::BlockSender`] policy, returns
+    /// `Err(TrySendError::Full)`.
+    ///
+    /// [`Receiver`]: crate::sync::broadcast::Receiver
+    /// [`Receiver::try_recv`]: crate::sync::broadcast::Receiver::try_recv
+    /// [`send`]: crate::sync::broadcast::Sender::send
+    /// [`OverflowPolicy::BlockSender`]: crate::sync::broadcast::OverflowPolicy::BlockSender
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use broadcast_rs::broadcast::{self, error::TrySendError, OverflowPolicy};
+    ///
+    /// let (tx, mut rx) = broadcast::channel_with_policy(1, OverflowPolicy::BlockSender);
+    ///
+    /// assert_eq!(tx.try_send(10), Ok(1));
+    /// assert_eq!(tx.try_send(20), Err(TrySendError::Full(20)));
+    ///
+    /// assert_eq!(rx.try_recv(), Ok(10));
+    /// assert_eq!(tx.try_send(20), Ok(1));
+    ///
+    /// drop(rx);
+    /// assert_eq!(tx.try_send(30), Err(TrySendError::Closed(30)));
+    /// ```
+    pub fn try_send(&self, value: T) -> Result<usize, TrySendError<T>> {
+        let tail = self.shared.tail.lock().unwrap();
 
-        // Release the slot lock before notifying the receivers.
-        drop(slot);
+        if tail.rx_cnt == 0 || tail.closed {
+            return Err(TrySendError::Closed(value));
+        }
+        if self.shared.is_full(&tail) {
+            return Err(TrySendError::Full(value));
+        }
 
-        tail.notify_rx();
+        Ok(self.shared.write(tail, value))
+    }
 
-        // Release the mutex. This must happen after the slot lock is released,
-        // otherwise the writer lock bit could be cleared while another thread
-        // is in the critical section.
-        drop(tail);
+    /// Sends a value to all active [`Receiver`] handles as [`send`] does,
+    /// waiting for room first if the channel is full and its policy is
+    /// [`OverflowPolicy::BlockSender`].
+    ///
+    /// With the default policy, the channel is never full and this resolves
+    /// at once. The value is handed back if there are no active receivers.
+    ///
+    /// # Cancel safety
+    ///
+    /// If the future is dropped before completing, the value is dropped
+    /// unsent.
+    ///
+    /// [`Receiver`]: crate::sync::broadcast::Receiver
+    /// [`send`]: crate::sync::broadcast::Sender::send
+    /// [`OverflowPolicy::BlockSender`]: crate::sync::broadcast::OverflowPolicy::BlockSender
+    pub fn send_async(&self, value: T) -> SendAsync<'_, T> {
+        SendAsync {
+            sender: self,
+            value: Cell::new(Some(value)),
+            // Safety: the elem is removed from the channel's sender list by
+            // SendAsync's drop.
+            registration: unsafe { Registration::new() },
+        }
+    }
 
-        Ok(rem)
+    /// Blocking send to call outside of asynchronous contexts.
+    ///
+    /// Sends as [`send_async`] does, parking the current thread while the
+    /// channel is full and its policy is [`OverflowPolicy::BlockSender`], as
+    /// a bounded `std::sync::mpsc::SyncSender` does. With the default policy,
+    /// the channel is never full and this returns at once.
+    ///
+    /// This must not be called from async code. By default nothing checks
+    /// for it: the executor thread running the code is parked, stalling its
+    /// other tasks, and a current-thread runtime deadlocks. The `tokio-guard`
+    /// feature turns the call into a panic on a tokio runtime.
+    ///
+    /// # Panics
+    ///
+    /// With the `tokio-guard` feature, this function panics if called within
+    /// an asynchronous execution context of a tokio runtime.
+    ///
+    /// [`send_async`]: crate::sync::broadcast::Sender::send_async
+    /// [`OverflowPolicy::BlockSender`]: crate::sync::broadcast::OverflowPolicy::BlockSender
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use broadcast_rs::broadcast::{self, OverflowPolicy};
+    /// use std::thread;
+    ///
+    /// let (tx, mut rx) = broadcast::channel_with_policy(1, OverflowPolicy::BlockSender);
+    /// tx.send(10).unwrap();
+    ///
+    /// let sync_code = thread::spawn(move || {
+    ///     // Waits for rx to see 10.
+    ///     tx.blocking_send(20).unwrap();
+    /// });
+    ///
+    /// assert_eq!(rx.blocking_recv(), Ok(10));
+    /// assert_eq!(rx.blocking_recv(), Ok(20));
+    /// sync_code.join().unwrap();
+    /// ```
+    pub fn blocking_send(&self, value: T) -> Result<usize, SendError<T>> {
+        crate::util::block_on::block_on(self.send_async(value))
     }
 
     /// Creates a new [`Receiver`] handle that will receive values sent **after**
@@ -641,77 +895,33 @@ impl<T> Sender<T> {
         tail.rx_cnt
     }
 
-    fn close_channel(&self) {
-        let mut tail = self.shared.tail.lock().unwrap();
-        tail.closed = true;
-
-        tail.notify_rx();
-    }
-}
-
-/// Create a new `Receiver` which reads starting from the tail.
-fn new_receiver<T>(shared: Arc<Shared<T>>) -> Receiver<T> {
-    let mut tail = shared.tail.lock().unwrap();
-
-    if tail.rx_cnt == MAX_RECEIVERS {
-        panic!("max receivers");
-    }
-
-    tail.rx_cnt = tail.rx_cnt.checked_add(1).expect("overflow");
-
-    let next = tail.pos;
-
-    drop(tail);
-
-    Receiver { shared, next }
-}
-
-impl Tail {
-    fn notify_rx(&mut self) {
-        while let Some(mut waiter) = self.waiters.pop_back() {
-            // Safety: `waiters` lock is still held.
-            let waiter = unsafe { waiter.as_mut() };
-
-            assert!(waiter.queued);
-            waiter.queued = false;
-
-            let waker = waiter.waker.take().unwrap();
-            waker.wake();
-        }
-    }
-}
-
-impl<T> Clone for Sender<T> {
-    fn clone(&self) -> Sender<T> {
-        let shared = self.shared.clone();
-        shared.num_tx.fetch_add(1, SeqCst);
-
-        Sender { shared }
-    }
-}
-
-impl<T> Drop for Sender<T> {
-    fn drop(&mut self) {
-        if 1 == self.shared.num_tx.fetch_sub(1, SeqCst) {
-            self.close_channel();
-        }
+    /// Returns the number of messages the channel can retain, the capacity
+    /// passed to [`channel`] rounded up to the next power of 2.
+    ///
+    /// [`channel`]: crate::sync::broadcast::channel
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use broadcast_rs::broadcast;
+    ///
+    /// let (tx, _rx) = broadcast::channel::<i32>(10);
+    ///
+    /// assert_eq!(tx.capacity(), 16);
+    /// ```
+    pub fn capacity(&self) -> usize {
+        self.shared.buffer.len()
     }
-}
 
-impl<T> Receiver<T> {
-    /// Returns the number of messages that were sent into the channel and that
-    /// this [`Receiver`] has yet to receive.
+    /// Returns the number of queued messages, those that at least one active
+    /// [`Receiver`] has yet to receive: the [`len`] of the slowest receiver.
     ///
-    /// If the returned value from `len` is larger than the next largest power of 2
-    /// of the capacity of the channel any call to [`recv`] will return an
-    /// `Err(RecvError::Lagged)` and any call to [`try_recv`] will return an
-    /// `Err(TryRecvError::Lagged)`, e.g. if the capacity of the channel is 10,
-    /// [`recv`] will start to return `Err(RecvError::Lagged)` once `len` returns
-    /// values larger than 16.
+    /// As with [`Receiver::len`], the count never exceeds [`capacity`].
     ///
     /// [`Receiver`]: crate::sync::broadcast::Receiver
-    /// [`recv`]: crate::sync::broadcast::Receiver::recv
-    /// [`try_recv`]: crate::sync::broadcast::Receiver::try_recv
+    /// [`Receiver::len`]: crate::sync::broadcast::Receiver::len
+    /// [`len`]: crate::sync::broadcast::Receiver::len
+    /// [`capacity`]: crate::sync::broadcast::Sender::capacity
     ///
     /// # Examples
     ///
@@ -721,26 +931,51 @@ impl<T> Receiver<T> {
     /// #[tokio::main]
     /// async fn main() {
     ///     let (tx, mut rx1) = broadcast::channel(16);
+    ///     let mut rx2 = tx.subscribe();
     ///
     ///     tx.send(10).unwrap();
     ///     tx.send(20).unwrap();
+    ///     tx.send(30).unwrap();
     ///
-    ///     assert_eq!(rx1.len(), 2);
-    ///     assert_eq!(rx1.recv().await.unwrap(), 10);
-    ///     assert_eq!(rx1.len(), 1);
-    ///     assert_eq!(rx1.recv().await.unwrap(), 20);     
-    ///     assert_eq!(rx1.len(), 0);
+    ///     assert_eq!(tx.len(), 3);
+    ///
+    ///     rx1.recv().await.unwrap();
+    ///
+    ///     // The len is still 3 since rx2 hasn't seen the first value yet.
+    ///     assert_eq!(tx.len(), 3);
+    ///
+    ///     rx2.recv().await.unwrap();
+    ///
+    ///     assert_eq!(tx.len(), 2);
     /// }
     /// ```
     pub fn len(&self) -> usize {
-        let next_send_pos = self.shared.tail.lock().unwrap().pos;
-        (next_send_pos - self.next) as usize
+        let tail = self.shared.tail.lock().unwrap();
+
+        // A slot's `rem` reaches zero once every receiver has moved past it,
+        // and receivers read in order, so the slots still held form a run
+        // ending at the last one written. Search for where the run starts,
+        // from the oldest slot.
+        let base_idx = (tail.pos & self.shared.mask as u64) as usize;
+        let mut low = 0;
+        let mut high = self.shared.buffer.len();
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let idx = base_idx.wrapping_add(mid) & self.shared.mask;
+            if self.shared.buffer[idx].read().unwrap().rem.load(SeqCst) == 0 {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+
+        self.shared.buffer.len() - low
     }
 
-    /// Returns true if there aren't any messages in the channel that the [`Receiver`]
-    /// has yet to receive.
+    /// Returns true if there are no queued messages, every active
+    /// [`Receiver`] having received every message sent.
     ///
-    /// [`Receiver]: create::sync::broadcast::Receiver
+    /// [`Receiver`]: crate::sync::broadcast::Receiver
     ///
     /// # Examples
     ///
@@ -750,26 +985,363 @@ impl<T> Receiver<T> {
     /// #[tokio::main]
     /// async fn main() {
     ///     let (tx, mut rx1) = broadcast::channel(16);
+    ///     let mut rx2 = tx.subscribe();
     ///
-    ///     assert!(rx1.is_empty());
+    ///     assert!(tx.is_empty());
     ///
     ///     tx.send(10).unwrap();
-    ///     tx.send(20).unwrap();
     ///
-    ///     assert!(!rx1.is_empty());
-    ///     assert_eq!(rx1.recv().await.unwrap(), 10);
-    ///     assert_eq!(rx1.recv().await.unwrap(), 20);     
-    ///     assert!(rx1.is_empty());
+    ///     assert!(!tx.is_empty());
+    ///
+    ///     rx1.recv().await.unwrap();
+    ///
+    ///     // The queue is still not empty since rx2 hasn't seen the value.
+    ///     assert!(!tx.is_empty());
+    ///
+    ///     rx2.recv().await.unwrap();
+    ///
+    ///     assert!(tx.is_empty());
     /// }
     /// ```
     pub fn is_empty(&self) -> bool {
-        self.len() == 0
+        let tail = self.shared.tail.lock().unwrap();
+
+        // The last slot written is the last to be released.
+        let idx = (tail.pos.wrapping_sub(1) & self.shared.mask as u64) as usize;
+        self.shared.buffer[idx].read().unwrap().rem.load(SeqCst) == 0
     }
 
-    /// Locks the next value if there is one.
+    /// Returns `true` if senders belong to the same channel.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use broadcast_rs::broadcast;
+    ///
+    /// let (tx, _rx) = broadcast::channel::<()>(16);
+    /// let tx2 = tx.clone();
+    ///
+    /// assert!(tx.same_channel(&tx2));
+    ///
+    /// let (tx3, _rx3) = broadcast::channel::<()>(16);
+    ///
+    /// assert!(!tx3.same_channel(&tx2));
+    /// ```
+    pub fn same_channel(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.shared, &other.shared)
+    }
+
+    /// Returns the raw ring positions of the channel, for debugging: the
+    /// absolute position the next value is written to, and the oldest
+    /// position the ring still retains.
+    ///
+    /// Positions count every value ever sent, starting from 0. Compared with a
+    /// receiver's [`debug_position`], they show how far behind it is.
+    ///
+    /// [`debug_position`]: crate::sync::broadcast::Receiver::debug_position
+    #[cfg(feature = "debug")]
+    pub fn debug_positions(&self) -> (u64, u64) {
+        let tail = self.shared.tail.lock().unwrap();
+        let oldest = tail.pos.saturating_sub(self.shared.buffer.len() as u64);
+        (tail.pos, oldest)
+    }
+
+    /// Creates a weak sender, which does not count as a sender keeping the
+    /// channel open.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use broadcast_rs::broadcast;
+    ///
+    /// let (tx, _rx) = broadcast::channel::<i32>(16);
+    /// let weak = tx.downgrade();
+    /// assert!(weak.upgrade().is_some());
+    ///
+    /// drop(tx);
+    /// assert!(weak.upgrade().is_none());
+    /// ```
+    pub fn downgrade(&self) -> WeakSender<T> {
+        WeakSender {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Closes the channel for every sender, as dropping the last one does.
+    ///
+    /// Waiting receivers are woken. They, and the others, receive the values
+    /// still retained and then `Err(RecvError::Closed)`, rather than waiting
+    /// again. Later sends fail, and weak senders no longer upgrade.
+    pub fn close(&self) {
+        self.close_channel();
+    }
+
+    /// Returns true once the channel has been closed, by [`close`] or by the
+    /// last sender being dropped.
+    ///
+    /// [`close`]: crate::sync::broadcast::Sender::close
+    pub fn is_closed(&self) -> bool {
+        self.shared.tail.lock().unwrap().closed
+    }
+
+    fn close_channel(&self) {
+        let mut tail = self.shared.tail.lock().unwrap();
+        tail.closed = true;
+
+        tail.waiters.awake_waiters();
+        // Blocked senders now fail rather than wait for room.
+        tail.send_waiters.awake_waiters();
+    }
+}
+
+impl<T> WeakSender<T> {
+    /// Returns a sender, unless every sender has been dropped or the channel
+    /// closed.
+    pub fn upgrade(&self) -> Option<Sender<T>> {
+        if self.shared.tail.lock().unwrap().closed {
+            return None;
+        }
+
+        // Never up from zero: the last sender's drop may be closing the
+        // channel.
+        let mut n = self.shared.num_tx.load(SeqCst);
+        loop {
+            if n == 0 {
+                return None;
+            }
+            match self.shared.num_tx.compare_exchange_weak(n, n + 1, SeqCst, SeqCst) {
+                Ok(_) => {
+                    return Some(Sender {
+                        shared: self.shared.clone(),
+                    })
+                }
+                Err(actual) => n = actual,
+            }
+        }
+    }
+}
+
+impl<T> Clone for WeakSender<T> {
+    fn clone(&self) -> WeakSender<T> {
+        WeakSender {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Shared<T> {
+    /// Returns true if the next slot to be written still holds a value some
+    /// receiver has yet to see, and the policy forbids overwriting it.
+    fn is_full(&self, tail: &Tail) -> bool {
+        if self.policy == OverflowPolicy::OverwriteOldest {
+            return false;
+        }
+        let idx = (tail.pos & self.mask as u64) as usize;
+        self.buffer[idx].read().unwrap().rem.load(SeqCst) != 0
+    }
+
+    /// Writes `value` to the next slot and notifies the receivers, returning
+    /// the number of receivers expected to see it.
+    fn write(&self, mut tail: MutexGuard<'_, Tail>, value: T) -> usize {
+        // Position to write into
+        let pos = tail.pos;
+        let rem = tail.rx_cnt;
+        let idx = (pos & self.mask as u64) as usize;
+
+        // Update the tail position
+        tail.pos = tail.pos.wrapping_add(1);
+
+        // Get the slot
+        let mut slot = self.buffer[idx].write().unwrap();
+
+        // Track the position
+        slot.pos = pos;
+
+        // Set remaining receivers
+        slot.rem.with_mut(|v| *v = rem);
+
+        // Write the value
+        slot.val = UnsafeCell::new(Some(value));
+
+        // Release the slot lock before notifying the receivers.
+        drop(slot);
+
+        tail.waiters.awake_waiters();
+
+        if self.policy == OverflowPolicy::BlockSender && !self.is_full(&tail) {
+            // The next slot was freed before this one; pass the room on.
+            tail.send_waiters.wake_one();
+        }
+
+        // Release the mutex. This must happen after the slot lock is released,
+        // otherwise the writer lock bit could be cleared while another thread
+        // is in the critical section.
+        drop(tail);
+
+        rem
+    }
+}
+
+/// Create a new `Receiver` which reads starting from the tail.
+fn new_receiver<T>(shared: Arc<Shared<T>>) -> Receiver<T> {
+    let mut tail = shared.tail.lock().unwrap();
+
+    if tail.rx_cnt == MAX_RECEIVERS {
+        panic!("max receivers");
+    }
+
+    tail.rx_cnt = tail.rx_cnt.checked_add(1).expect("overflow");
+
+    let next = tail.pos;
+
+    drop(tail);
+
+    Receiver {
+        shared,
+        next,
+        waiter: None,
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Sender<T> {
+        let shared = self.shared.clone();
+        shared.num_tx.fetch_add(1, SeqCst);
+
+        Sender { shared }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if 1 == self.shared.num_tx.fetch_sub(1, SeqCst) {
+            self.close_channel();
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Returns the number of messages that were sent into the channel and that
+    /// this [`Receiver`] can still receive.
+    ///
+    /// The count is clamped to the size of the channel's buffer, the next
+    /// largest power of 2 of its capacity, as older messages have been
+    /// overwritten. A receiver that has fallen further behind than that has
+    /// lagged: its next call to [`recv`] will return an
+    /// `Err(RecvError::Lagged)` and its next call to [`try_recv`] will return an
+    /// `Err(TryRecvError::Lagged)`, after which `len` messages remain to be
+    /// received. E.g. if the capacity of the channel is 10, `len` returns at
+    /// most 16.
+    ///
+    /// [`Receiver`]: crate::sync::broadcast::Receiver
+    /// [`recv`]: crate::sync::broadcast::Receiver::recv
+    /// [`try_recv`]: crate::sync::broadcast::Receiver::try_recv
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use broadcast_rs::broadcast;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let (tx, mut rx1) = broadcast::channel(16);
+    ///
+    ///     tx.send(10).unwrap();
+    ///     tx.send(20).unwrap();
+    ///
+    ///     assert_eq!(rx1.len(), 2);
+    ///     assert_eq!(rx1.recv().await.unwrap(), 10);
+    ///     assert_eq!(rx1.len(), 1);
+    ///     assert_eq!(rx1.recv().await.unwrap(), 20);     
+    ///     assert_eq!(rx1.len(), 0);
+    /// }
+    /// ```
+    pub fn len(&self) -> usize {
+        let next_send_pos = self.shared.tail.lock().unwrap().pos;
+        let unread = next_send_pos - self.next;
+        unread.min(self.shared.buffer.len() as u64) as usize
+    }
+
+    /// Returns true if there aren't any messages in the channel that the [`Receiver`]
+    /// has yet to receive.
+    ///
+    /// [`Receiver]: create::sync::broadcast::Receiver
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use broadcast_rs::broadcast;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let (tx, mut rx1) = broadcast::channel(16);
+    ///
+    ///     assert!(rx1.is_empty());
+    ///
+    ///     tx.send(10).unwrap();
+    ///     tx.send(20).unwrap();
+    ///
+    ///     assert!(!rx1.is_empty());
+    ///     assert_eq!(rx1.recv().await.unwrap(), 10);
+    ///     assert_eq!(rx1.recv().await.unwrap(), 20);     
+    ///     assert!(rx1.is_empty());
+    /// }
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if receivers belong to the same channel.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use broadcast_rs::broadcast;
+    ///
+    /// let (tx, rx) = broadcast::channel::<()>(16);
+    /// let rx2 = tx.subscribe();
+    ///
+    /// assert!(rx.same_channel(&rx2));
+    ///
+    /// let (_tx3, rx3) = broadcast::channel::<()>(16);
+    ///
+    /// assert!(!rx3.same_channel(&rx2));
+    /// ```
+    pub fn same_channel(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.shared, &other.shared)
+    }
+
+    /// Returns the number of live senders.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use broadcast_rs::broadcast;
+    ///
+    /// let (tx, rx) = broadcast::channel::<i32>(16);
+    /// let _tx2 = tx.clone();
+    ///
+    /// assert_eq!(rx.sender_count(), 2);
+    /// ```
+    pub fn sender_count(&self) -> usize {
+        self.shared.num_tx.load(SeqCst)
+    }
+
+    /// Returns the raw ring position this receiver reads next, for debugging.
+    ///
+    /// A position below the oldest one from [`Sender::debug_positions`] means
+    /// the next receive reports a lag.
+    ///
+    /// [`Sender::debug_positions`]: crate::sync::broadcast::Sender::debug_positions
+    #[cfg(feature = "debug")]
+    pub fn debug_position(&self) -> u64 {
+        self.next
+    }
+
+    /// Locks the next value if there is one.
     fn recv_ref(
         &mut self,
-        waiter: Option<(&UnsafeCell<Waiter>, &Waker)>,
+        waiter: Option<(&Registration, &mut Context<'_>)>,
     ) -> Result<RecvGuard<'_, T>, TryRecvError> {
         let idx = (self.next & self.shared.mask as u64) as usize;
 
@@ -805,29 +1377,10 @@ impl<T> Receiver<T> {
                         return Err(TryRecvError::Closed);
                     }
 
-                    // Store the waker
-                    if let Some((waiter, waker)) = waiter {
-                        // Safety: called while locked.
-                        unsafe {
-                            // Only queue if not already queued
-                            waiter.with_mut(|ptr| {
-                                // If there is no waker **or** if the currently
-                                // stored waker references a **different** task,
-                                // track the tasks' waker to be notified on
-                                // receipt of a new value.
-                                match (*ptr).waker {
-                                    Some(ref w) if w.will_wake(waker) => {}
-                                    _ => {
-                                        (*ptr).waker = Some(waker.clone());
-                                    }
-                                }
-
-                                if !(*ptr).queued {
-                                    (*ptr).queued = true;
-                                    tail.waiters.push_front(NonNull::new_unchecked(&mut *ptr));
-                                }
-                            });
-                        }
+                    // Queue the receiver, or refresh its waker if it is
+                    // already queued, to be woken by the next send.
+                    if let Some((registration, cx)) = waiter {
+                        registration.register(&mut tail.waiters, cx);
                     }
 
                     return Err(TryRecvError::Empty);
@@ -848,7 +1401,10 @@ impl<T> Receiver<T> {
                 if missed == 0 {
                     self.next = self.next.wrapping_add(1);
 
-                    return Ok(RecvGuard { slot });
+                    return Ok(RecvGuard {
+                        shared: &self.shared,
+                        slot: ManuallyDrop::new(slot),
+                    });
                 }
 
                 self.next = next;
@@ -859,7 +1415,10 @@ impl<T> Receiver<T> {
 
         self.next = self.next.wrapping_add(1);
 
-        Ok(RecvGuard { slot })
+        Ok(RecvGuard {
+            shared: &self.shared,
+            slot: ManuallyDrop::new(slot),
+        })
     }
 }
 
@@ -968,6 +1527,180 @@ impl<T: Clone> Receiver<T> {
         fut.await
     }
 
+    /// Receives the next value for this receiver as [`recv`] does, giving up
+    /// with `Err(RecvTimeoutError::Timeout)` once `deadline` has passed.
+    ///
+    /// The receiver is woken at the deadline by a timer thread the crate
+    /// starts, so no runtime timer is needed. See the crate documentation.
+    ///
+    /// [`recv`]: crate::sync::broadcast::Receiver::recv
+    ///
+    /// # Cancel safety
+    ///
+    /// This method is cancel safe, as [`recv`] is.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use broadcast_rs::broadcast::{self, error::RecvTimeoutError};
+    /// use std::time::{Duration, Instant};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let (tx, mut rx) = broadcast::channel(16);
+    ///
+    ///     let deadline = Instant::now() + Duration::from_millis(10);
+    ///     assert_eq!(rx.recv_deadline(deadline).await, Err(RecvTimeoutError::Timeout));
+    ///
+    ///     tx.send(10).unwrap();
+    ///     assert_eq!(rx.recv_deadline(deadline).await, Ok(10));
+    /// }
+    /// ```
+    pub async fn recv_deadline(&mut self, deadline: Instant) -> Result<T, RecvTimeoutError> {
+        let fut = RecvDeadline {
+            recv: Recv::new(self),
+            deadline,
+            // Safety: the elem is removed from the timer by RecvDeadline's
+            // drop.
+            elem: unsafe { Elem::new() },
+        };
+        fut.await
+    }
+
+    /// Receives up to `limit` values into `buf`, waiting only if none are
+    /// available.
+    ///
+    /// Returns the number of values added to `buf`, which is zero only when
+    /// `limit` is. The receiver is queued at most once per batch rather than
+    /// once per value. A batch is never cut short by lag: a receiver found
+    /// behind after taking some values gets them, and then
+    /// `Err(RecvError::Lagged)` on its next call.
+    ///
+    /// # Cancel safety
+    ///
+    /// This method is cancel safe. No values are taken unless it returns.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use broadcast_rs::broadcast;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let (tx, mut rx) = broadcast::channel(16);
+    ///     for i in 0..5 {
+    ///         tx.send(i).unwrap();
+    ///     }
+    ///
+    ///     let mut buf = Vec::new();
+    ///     assert_eq!(rx.recv_many(&mut buf, 3).await, Ok(3));
+    ///     assert_eq!(rx.recv_many(&mut buf, 3).await, Ok(2));
+    ///     assert_eq!(buf, [0, 1, 2, 3, 4]);
+    /// }
+    /// ```
+    pub async fn recv_many(&mut self, buf: &mut Vec<T>, limit: usize) -> Result<usize, RecvError> {
+        poll_fn(|cx| self.poll_recv_many(cx, buf, limit)).await
+    }
+
+    fn poll_recv_many(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut Vec<T>,
+        limit: usize,
+    ) -> Poll<Result<usize, RecvError>> {
+        if limit == 0 {
+            return Poll::Ready(Ok(0));
+        }
+        let value = match self.poll_recv(cx) {
+            Poll::Ready(Ok(value)) => value,
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            Poll::Pending => return Poll::Pending,
+        };
+        buf.push(value);
+
+        let mut n = 1;
+        while n < limit {
+            let next = self.next;
+            match self.try_recv() {
+                Ok(value) => {
+                    buf.push(value);
+                    n += 1;
+                }
+                Err(TryRecvError::Lagged(_)) => {
+                    // Put the cursor back, for the next call to find the lag
+                    // again and report it.
+                    self.next = next;
+                    break;
+                }
+                Err(_) => break,
+            }
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    /// Polls for the next value, queuing the receiver to be woken by the next
+    /// send if it is caught up. For use in hand-written futures.
+    ///
+    /// The receiver, not a future, owns the elem that is queued, in a box
+    /// allocated by the first call. A poll that returns `Pending` may be
+    /// abandoned without any cleanup: the waiter stays queued until the
+    /// receiver is polled again, woken, or dropped, whose drop removes it from
+    /// the list.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<T, RecvError>> {
+        // Taken out for the call, as `recv_ref` borrows the receiver. The box
+        // keeps the elem's address while it is queued.
+        //
+        // Safety: the elem is removed from the channel's list by the
+        // receiver's drop.
+        let waiter = self.waiter.take().unwrap_or_else(|| Box::new(unsafe { Registration::new() }));
+
+        let result = match self.recv_ref(Some((&waiter, cx))) {
+            Ok(guard) => Poll::Ready(guard.clone_value().ok_or(RecvError::Closed)),
+            Err(TryRecvError::Empty) => Poll::Pending,
+            Err(TryRecvError::Lagged(n)) => Poll::Ready(Err(RecvError::Lagged(n))),
+            Err(TryRecvError::Closed) => Poll::Ready(Err(RecvError::Closed)),
+        };
+
+        self.waiter = Some(waiter);
+        result
+    }
+
+    /// Blocking receive to call outside of asynchronous contexts.
+    ///
+    /// Waits for a value as [`recv`] does, parking the current thread, for
+    /// synchronous code reading from the channel.
+    ///
+    /// This must not be called from async code. By default nothing checks
+    /// for it: the executor thread running the code is parked, stalling its
+    /// other tasks, and a current-thread runtime deadlocks. The `tokio-guard`
+    /// feature turns the call into a panic on a tokio runtime.
+    ///
+    /// # Panics
+    ///
+    /// With the `tokio-guard` feature, this function panics if called within
+    /// an asynchronous execution context of a tokio runtime.
+    ///
+    /// [`recv`]: crate::sync::broadcast::Receiver::recv
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use broadcast_rs::broadcast;
+    /// use std::thread;
+    ///
+    /// let (tx, mut rx) = broadcast::channel(16);
+    ///
+    /// let sync_code = thread::spawn(move || {
+    ///     assert_eq!(rx.blocking_recv(), Ok(10));
+    /// });
+    ///
+    /// tx.send(10).unwrap();
+    /// sync_code.join().unwrap();
+    /// ```
+    pub fn blocking_recv(&mut self) -> Result<T, RecvError> {
+        crate::util::block_on::block_on(self.recv())
+    }
+
     /// Attempts to return a pending value on this receiver without awaiting.
     ///
     /// This is useful for a flavor of "optimistic check" before deciding to
@@ -1014,13 +1747,42 @@ impl<T: Clone> Receiver<T> {
     }
 }
 
+/// Yields each value received, and `Err(RecvError::Lagged)` when values were
+/// skipped, as `recv` returns them. The stream ends, rather than yielding
+/// `RecvError::Closed`, once the channel is closed and every retained value has
+/// been received.
+#[cfg(feature = "stream")]
+impl<T: Clone> futures_core::Stream for Receiver<T> {
+    type Item = Result<T, RecvError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<T, RecvError>>> {
+        // The receiver is Unpin, its waiter being boxed.
+        self.get_mut().poll_recv(cx).map(|result| match result {
+            Err(RecvError::Closed) => None,
+            result => Some(result),
+        })
+    }
+}
+
 impl<T> Drop for Receiver<T> {
     fn drop(&mut self) {
         let mut tail = self.shared.tail.lock().unwrap();
 
+        if let Some(waiter) = &self.waiter {
+            // Left queued by a `poll_recv` that returned pending.
+            //
+            // Safety: the elem is only ever queued in this channel's list.
+            unsafe { tail.waiters.remove_waiter(waiter.elem()) };
+        }
+
         tail.rx_cnt -= 1;
         let until = tail.pos;
 
+        if tail.rx_cnt == 0 {
+            // Waiting senders now fail rather than wait for room.
+            tail.send_waiters.awake_waiters();
+        }
+
         drop(tail);
 
         while self.next < until {
@@ -1041,24 +1803,22 @@ impl<'a, T> Recv<'a, T> {
     fn new(receiver: &'a mut Receiver<T>) -> Recv<'a, T> {
         Recv {
             receiver,
-            waiter: UnsafeCell::new(Waiter {
-                queued: false,
-                waker: None,
-                pointers: linked_list::Pointers::new(),
-                _p: PhantomPinned,
-            }),
+            // Safety: the elem is removed from the channel's list by Recv's
+            // drop.
+            registration: unsafe { Registration::new() },
         }
     }
 
-    /// A custom `project` implementation is used in place of `pin-project-lite`
-    /// as a custom drop implementation is needed.
-    fn project(self: Pin<&mut Self>) -> (&mut Receiver<T>, &UnsafeCell<Waiter>) {
+    /// `recv_ref` needs the receiver by `&mut`, which the `&Self` the other
+    /// futures make do with can't give.
+    fn project(self: Pin<&mut Self>) -> (&mut Receiver<T>, &Registration) {
         unsafe {
-            // Safety: Receiver is Unpin
+            // Safety: Receiver is Unpin, and the registration is only handed
+            // out by reference.
             is_unpin::<&mut Receiver<T>>();
 
             let me = self.get_unchecked_mut();
-            (me.receiver, &me.waiter)
+            (me.receiver, &me.registration)
         }
     }
 }
@@ -1070,9 +1830,9 @@ where
     type Output = Result<T, RecvError>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<T, RecvError>> {
-        let (receiver, waiter) = self.project();
+        let (receiver, registration) = self.project();
 
-        let guard = match receiver.recv_ref(Some((waiter, cx.waker()))) {
+        let guard = match receiver.recv_ref(Some((registration, cx))) {
             Ok(value) => value,
             Err(TryRecvError::Empty) => return Poll::Pending,
             Err(TryRecvError::Lagged(n)) => return Poll::Ready(Err(RecvError::Lagged(n))),
@@ -1083,46 +1843,105 @@ where
     }
 }
 
+impl<'a, T> RecvDeadline<'a, T> {
+    fn project(self: Pin<&mut Self>) -> (Pin<&mut Recv<'a, T>>, Instant, &Elem<Instant>) {
+        unsafe {
+            // Safety: the fields are structurally pinned.
+            let me = self.get_unchecked_mut();
+            (Pin::new_unchecked(&mut me.recv), me.deadline, &me.elem)
+        }
+    }
+}
+
+impl<'a, T> Future for RecvDeadline<'a, T>
+where
+    T: Clone,
+{
+    type Output = Result<T, RecvTimeoutError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<T, RecvTimeoutError>> {
+        let (recv, deadline, elem) = self.project();
+
+        // A value already there is received even past the deadline.
+        let res = match recv.poll(cx) {
+            Poll::Ready(res) => res.map_err(RecvTimeoutError::from),
+            Poll::Pending if Instant::now() >= deadline => Err(RecvTimeoutError::Timeout),
+            Poll::Pending => {
+                crate::util::timer::register(elem, cx, deadline);
+                return Poll::Pending;
+            }
+        };
+
+        // Safety: the elem is only queued through `timer::register`.
+        unsafe { crate::util::timer::cancel(elem) };
+        Poll::Ready(res)
+    }
+}
+
+impl<'a, T> Drop for RecvDeadline<'a, T> {
+    fn drop(&mut self) {
+        // The channel waiter is removed by the drop of `recv`.
+        //
+        // Safety: the elem is only queued through `timer::register`.
+        unsafe { crate::util::timer::cancel(&self.elem) };
+    }
+}
+
 impl<'a, T> Drop for Recv<'a, T> {
     fn drop(&mut self) {
-        // Acquire the tail lock. This is required for safety before accessing
-        // the waiter node.
+        if !self.registration.is_registered() {
+            return;
+        }
         let mut tail = self.receiver.shared.tail.lock().unwrap();
 
-        // safety: tail lock is held
-        let queued = self.waiter.with(|ptr| unsafe { (*ptr).queued });
-
-        if queued {
-            // Remove the node
-            //
-            // safety: tail lock is held and the wait node is verified to be in
-            // the list.
-            unsafe {
-                self.waiter.with_mut(|ptr| {
-                    tail.waiters.remove((&mut *ptr).into());
-                });
-            }
-        }
+        // Safety: the elem is only ever queued in this channel's list.
+        unsafe { tail.waiters.remove_waiter(self.registration.elem()) };
     }
 }
 
-/// # Safety
-///
-/// `Waiter` is forced to be !Unpin.
-unsafe impl linked_list::Link for Waiter {
-    type Handle = NonNull<Waiter>;
-    type Target = Waiter;
+impl<'a, T> Future for SendAsync<'a, T> {
+    type Output = Result<usize, SendError<T>>;
 
-    fn as_raw(handle: &NonNull<Waiter>) -> NonNull<Waiter> {
-        *handle
-    }
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<usize, SendError<T>>> {
+        let registration = &self.registration;
+        let shared = &*self.sender.shared;
+        let mut tail = shared.tail.lock().unwrap();
 
-    unsafe fn from_raw(ptr: NonNull<Waiter>) -> NonNull<Waiter> {
-        ptr
+        let open = tail.rx_cnt != 0 && !tail.closed;
+        if open && shared.is_full(&tail) {
+            // Checked and queued under the tail lock, which a receiver freeing
+            // a slot takes before waking the senders, so the wake isn't missed.
+            registration.register(&mut tail.send_waiters, cx);
+            return Poll::Pending;
+        }
+
+        if registration.is_registered() {
+            // Safety: the elem is only ever queued in this channel's list.
+            unsafe { tail.send_waiters.remove_waiter(registration.elem()) };
+            // Done with the wake, if it had one: its drop has nothing to pass on.
+            tail.send_waiters.consume_wake(registration.elem());
+            registration.unregister();
+        }
+
+        let value = self.value.take().expect("SendAsync polled after completion");
+        if !open {
+            return Poll::Ready(Err(SendError(value)));
+        }
+        Poll::Ready(Ok(shared.write(tail, value)))
     }
+}
 
-    unsafe fn pointers(target: NonNull<Waiter>) -> NonNull<linked_list::Pointers<Waiter>> {
-        Waiter::addr_of_pointers(target)
+impl<'a, T> Drop for SendAsync<'a, T> {
+    fn drop(&mut self) {
+        if !self.registration.is_registered() {
+            return;
+        }
+        let mut tail = self.sender.shared.tail.lock().unwrap();
+        // Safety: the elem is only ever queued in this channel's list.
+        //
+        // A sender woken for a free slot but dropped before filling it passes
+        // the wake on.
+        unsafe { tail.send_waiters.remove_waiter_and_wake_next(self.registration.elem()) };
     }
 }
 
@@ -1132,6 +1951,12 @@ impl<T> fmt::Debug for Sender<T> {
     }
 }
 
+impl<T> fmt::Debug for WeakSender<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "broadcast::WeakSender")
+    }
+}
+
 impl<T> fmt::Debug for Receiver<T> {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(fmt, "broadcast::Receiver")
@@ -1139,10 +1964,18 @@ impl<T> fmt::Debug for Receiver<T> {
 }
 
 impl<'a, T> RecvGuard<'a, T> {
+    /// Returns the value, moved out of the slot if this receiver is the last
+    /// one to see it, cloned otherwise.
     fn clone_value(&self) -> Option<T>
     where
         T: Clone,
     {
+        if self.slot.rem.load(SeqCst) == 1 {
+            // Safety: every other receiver of the value is done with it, having
+            // decremented `rem` after cloning, and the sender is kept out by the
+            // slot lock. A receiver subscribing later never reads this slot.
+            return self.slot.val.with_mut(|ptr| unsafe { (*ptr).take() });
+        }
         self.slot.val.with(|ptr| unsafe { (*ptr).clone() })
     }
 }
@@ -1150,11 +1983,612 @@ impl<'a, T> RecvGuard<'a, T> {
 impl<'a, T> Drop for RecvGuard<'a, T> {
     fn drop(&mut self) {
         // Decrement the remaining counter
-        if 1 == self.slot.rem.fetch_sub(1, SeqCst) {
+        let freed = 1 == self.slot.rem.fetch_sub(1, SeqCst);
+        if freed {
             // Safety: Last receiver, drop the value
             self.slot.val.with_mut(|ptr| unsafe { *ptr = None });
         }
+        let pos = self.slot.pos;
+
+        // Release the slot lock before acquiring the tail lock, the order
+        // `send` takes them in.
+        //
+        // Safety: the slot guard is not used again.
+        unsafe { ManuallyDrop::drop(&mut self.slot) };
+
+        if freed && self.shared.policy == OverflowPolicy::BlockSender {
+            // The last receiver to see a value is the slowest one, so a freed
+            // slot is the slowest cursor moving on, found without visiting the
+            // other receivers. Senders only wait for the slot at the tail; a
+            // slot freed further ahead is picked up by the `write` filling the
+            // tail.
+            let mut tail = self.shared.tail.lock().unwrap();
+            if (pos ^ tail.pos) & self.shared.mask as u64 == 0 {
+                tail.send_waiters.wake_one();
+            }
+        }
     }
 }
 
 fn is_unpin<T: Unpin>() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::task::Waker;
+    use tokio_test::task;
+
+    #[test]
+    fn receiver_len() {
+        let (tx, mut rx) = channel(4);
+
+        // Caught up.
+        assert_eq!(rx.len(), 0);
+        assert!(rx.is_empty());
+
+        // Behind.
+        for i in 0..3 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(rx.len(), 3);
+        assert!(!rx.is_empty());
+
+        // Mid-buffer.
+        assert_eq!(rx.try_recv(), Ok(0));
+        assert_eq!(rx.len(), 2);
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(rx.try_recv(), Ok(2));
+        assert!(rx.is_empty());
+    }
+
+    #[test]
+    fn lagged_receiver_len_is_clamped() {
+        let (tx, mut rx) = channel(3);
+        let buffered = tx.shared.buffer.len();
+        assert_eq!(buffered, 4);
+
+        for i in 0..10 {
+            tx.send(i).unwrap();
+        }
+        // Ten sent, but only the last four are still buffered.
+        assert_eq!(rx.len(), buffered);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Lagged(6)));
+        assert_eq!(rx.len(), buffered);
+        assert_eq!(rx.try_recv(), Ok(6));
+        assert_eq!(rx.len(), 3);
+    }
+
+    #[test]
+    fn sender_len_follows_slowest_receiver() {
+        let (tx, mut fast) = channel(4);
+        let mut slow = tx.subscribe();
+        assert_eq!(tx.capacity(), 4);
+        assert_eq!(tx.len(), 0);
+        assert!(tx.is_empty());
+
+        // Grows with each send.
+        for i in 0..3 {
+            tx.send(i).unwrap();
+            assert_eq!(tx.len(), i + 1);
+        }
+        assert!(!tx.is_empty());
+
+        // Unchanged while only the fast receiver reads.
+        for i in 0..3 {
+            assert_eq!(fast.try_recv(), Ok(i));
+        }
+        assert_eq!(tx.len(), 3);
+
+        // Shrinks as the slowest receiver catches up.
+        assert_eq!(slow.try_recv(), Ok(0));
+        assert_eq!(tx.len(), 2);
+        assert_eq!(slow.try_recv(), Ok(1));
+        assert_eq!(slow.try_recv(), Ok(2));
+        assert_eq!(tx.len(), 0);
+        assert!(tx.is_empty());
+
+        // Capped at the capacity when a receiver lags, and released when it drops.
+        for i in 0..6 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(tx.len(), 4);
+        drop(slow);
+        while fast.try_recv() != Err(TryRecvError::Empty) {}
+        assert!(tx.is_empty());
+    }
+
+    #[test]
+    fn channel_closes_with_last_sender() {
+        let (tx1, mut rx) = channel(4);
+        let tx2 = tx1.clone();
+
+        let mut recv = task::spawn(async { rx.recv().await });
+        assert!(recv.poll().is_pending());
+
+        // One of the two senders remains, so the channel stays open.
+        drop(tx1);
+        assert!(!recv.is_woken());
+        assert!(recv.poll().is_pending());
+
+        tx2.send(1).unwrap();
+        assert!(recv.is_woken());
+        assert_eq!(recv.poll(), Poll::Ready(Ok(1)));
+        drop(recv);
+
+        // Dropping the last one closes it.
+        drop(tx2);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Closed));
+    }
+
+    #[test]
+    fn blocking_recv_from_thread() {
+        let (tx, mut rx) = channel(2);
+
+        let reader = std::thread::spawn(move || {
+            let mut values = Vec::new();
+            while let Ok(value) = rx.blocking_recv() {
+                values.push(value);
+            }
+            values
+        });
+
+        for i in 0..2 {
+            tx.send(i).unwrap();
+            // Let the reader keep up, so it doesn't lag.
+            while !tx.is_empty() {
+                std::thread::yield_now();
+            }
+        }
+        drop(tx);
+        assert_eq!(reader.join().unwrap(), [0, 1]);
+    }
+
+    #[cfg(feature = "tokio-guard")]
+    #[tokio::test]
+    #[should_panic(expected = "Cannot start a runtime from within a runtime")]
+    async fn blocking_recv_panics_in_runtime() {
+        let (_tx, mut rx) = channel::<i32>(2);
+        let _ = rx.blocking_recv();
+    }
+
+    #[cfg(feature = "tokio-guard")]
+    #[tokio::test]
+    #[should_panic(expected = "Cannot start a runtime from within a runtime")]
+    async fn blocking_send_panics_in_runtime() {
+        let (tx, _rx) = channel_with_policy(1, OverflowPolicy::BlockSender);
+        tx.send(1).unwrap();
+        let _ = tx.blocking_send(2);
+    }
+
+    #[test]
+    fn overwrite_oldest_when_full() {
+        let (tx, mut rx) = channel(2);
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+
+        // The full channel makes room by overwriting 1.
+        assert_eq!(tx.send(3).unwrap(), 1);
+        assert_eq!(task::spawn(tx.send_async(4)).poll().map(Result::unwrap), Poll::Ready(1));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Lagged(2)));
+        assert_eq!(rx.try_recv(), Ok(3));
+        assert_eq!(rx.try_recv(), Ok(4));
+    }
+
+    #[test]
+    fn block_sender_when_full() {
+        let (tx, mut rx) = channel_with_policy(2, OverflowPolicy::BlockSender);
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+
+        // Nothing is overwritten: the send fails, or waits.
+        assert_eq!(tx.try_send(3), Err(TrySendError::Full(3)));
+        let mut send = task::spawn(tx.send_async(3));
+        assert!(send.poll().is_pending());
+
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert!(send.is_woken());
+        assert_eq!(send.poll().map(Result::unwrap), Poll::Ready(1));
+        assert_eq!(rx.try_recv(), Ok(2));
+        assert_eq!(rx.try_recv(), Ok(3));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+
+        // With no receivers left, the value is handed back.
+        drop(rx);
+        let send = task::spawn(tx.send_async(4)).poll();
+        assert_eq!(send.map(|r| r.unwrap_err().0), Poll::Ready(4));
+        assert_eq!(tx.try_send(5), Err(TrySendError::Closed(5)));
+        assert_eq!(tx.send(6), Err(TrySendError::Closed(6)));
+        assert_eq!(tx.blocking_send(7).unwrap_err().0, 7);
+    }
+
+    #[test]
+    fn block_sender_send_never_waits() {
+        let (tx, mut rx) = channel_with_policy(1, OverflowPolicy::BlockSender);
+        tx.send(1).unwrap();
+        assert_eq!(tx.send(2), Err(TrySendError::Full(2)));
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(tx.send(2), Ok(1));
+    }
+
+    #[test]
+    fn blocking_send_parks_until_room() {
+        let (tx, mut rx) = channel_with_policy(1, OverflowPolicy::BlockSender);
+        tx.send(1).unwrap();
+
+        let sender = std::thread::spawn(move || tx.blocking_send(2).unwrap());
+        // Only freed once the sender is parked, or about to be.
+        while rx.shared.tail.lock().unwrap().send_waiters.is_empty() {
+            std::thread::yield_now();
+        }
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(sender.join().unwrap(), 1);
+        assert_eq!(rx.try_recv(), Ok(2));
+    }
+
+    #[test]
+    fn receiver_progress_wakes_blocked_sender() {
+        let (tx, mut rx1) = channel_with_policy(2, OverflowPolicy::BlockSender);
+        let mut rx2 = tx.subscribe();
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+
+        let mut send = task::spawn(tx.send_async(3));
+        assert!(send.poll().is_pending());
+
+        // The fast receiver moving on frees nothing while the slow one is
+        // still behind.
+        assert_eq!(rx1.try_recv(), Ok(1));
+        assert_eq!(rx1.try_recv(), Ok(2));
+        assert!(!send.is_woken());
+
+        // The slowest receiver frees the slot the sender waits for.
+        assert_eq!(rx2.try_recv(), Ok(1));
+        assert!(send.is_woken());
+        assert_eq!(send.poll().map(Result::unwrap), Poll::Ready(2));
+
+        assert_eq!(rx2.try_recv(), Ok(2));
+        assert_eq!(rx2.try_recv(), Ok(3));
+        assert_eq!(rx1.try_recv(), Ok(3));
+    }
+
+    #[test]
+    fn blocked_senders_woken_in_turn() {
+        let (tx, mut rx1) = channel_with_policy(2, OverflowPolicy::BlockSender);
+        let mut rx2 = tx.subscribe();
+        tx.send(1).unwrap();
+        assert_eq!(rx2.try_recv(), Ok(1));
+        tx.send(2).unwrap();
+
+        let mut a = task::spawn(tx.send_async(3));
+        let mut b = task::spawn(tx.send_async(4));
+        assert!(a.poll().is_pending());
+        assert!(b.poll().is_pending());
+
+        // rx1 frees both slots, the second ahead of the tail.
+        assert_eq!(rx1.try_recv(), Ok(1));
+        assert!(a.is_woken());
+        assert!(!b.is_woken());
+        assert_eq!(rx2.try_recv(), Ok(2));
+        assert_eq!(rx1.try_recv(), Ok(2));
+        assert!(!b.is_woken());
+
+        // a filling the tail slot passes the second on to b.
+        assert!(a.poll().is_ready());
+        assert!(b.is_woken());
+        assert!(b.poll().is_ready());
+    }
+
+    #[test]
+    fn dropped_woken_sender_passes_wake_on() {
+        let (tx, mut rx) = channel_with_policy(1, OverflowPolicy::BlockSender);
+        tx.send(1).unwrap();
+
+        let mut a = task::spawn(tx.send_async(2));
+        let mut b = task::spawn(tx.send_async(3));
+        assert!(a.poll().is_pending());
+        assert!(b.poll().is_pending());
+
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert!(a.is_woken());
+        drop(a);
+        assert!(b.is_woken());
+        assert!(b.poll().is_ready());
+        assert_eq!(rx.try_recv(), Ok(3));
+    }
+
+    #[test]
+    fn last_receiver_drop_fails_blocked_sender() {
+        let (tx, rx) = channel_with_policy(1, OverflowPolicy::BlockSender);
+        tx.send(1).unwrap();
+
+        let mut send = task::spawn(tx.send_async(2));
+        assert!(send.poll().is_pending());
+        drop(rx);
+        assert!(send.is_woken());
+        assert_eq!(send.poll().map(|r| r.unwrap_err().0), Poll::Ready(2));
+    }
+
+    #[test]
+    fn same_channel() {
+        let (tx, rx) = channel::<i32>(2);
+        let rx2 = tx.subscribe();
+        let rx3 = rx.resubscribe();
+        assert!(rx.same_channel(&rx2));
+        assert!(rx2.same_channel(&rx3));
+        assert!(tx.same_channel(&tx.clone()));
+
+        let (other_tx, other_rx) = channel::<i32>(2);
+        assert!(!rx.same_channel(&other_rx));
+        assert!(!tx.same_channel(&other_tx));
+    }
+
+    #[derive(Debug)]
+    struct Counted(Arc<std::sync::atomic::AtomicUsize>);
+
+    impl Clone for Counted {
+        fn clone(&self) -> Counted {
+            self.0.fetch_add(1, SeqCst);
+            Counted(self.0.clone())
+        }
+    }
+
+    #[test]
+    fn last_receiver_takes_value_uncloned() {
+        let clones = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let (tx, mut rx1) = channel(4);
+
+        for _ in 0..3 {
+            tx.send(Counted(clones.clone())).unwrap();
+        }
+        for _ in 0..3 {
+            rx1.try_recv().unwrap();
+        }
+        assert_eq!(clones.load(SeqCst), 0);
+
+        // Subscribed mid-stream, values are cloned for every receiver but the
+        // last to take them.
+        let mut rx2 = tx.subscribe();
+        tx.send(Counted(clones.clone())).unwrap();
+        rx1.try_recv().unwrap();
+        rx2.try_recv().unwrap();
+        assert_eq!(clones.load(SeqCst), 1);
+
+        drop(rx1);
+        tx.send(Counted(clones.clone())).unwrap();
+        rx2.try_recv().unwrap();
+        assert_eq!(clones.load(SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn recv_deadline_times_out_before_send() {
+        let (tx, mut rx) = channel(2);
+
+        let deadline = Instant::now() + std::time::Duration::from_millis(20);
+        assert_eq!(rx.recv_deadline(deadline).await, Err(RecvTimeoutError::Timeout));
+        assert!(Instant::now() >= deadline);
+
+        // Nothing is left queued, and the receiver still works.
+        assert!(tx.shared.tail.lock().unwrap().waiters.is_empty());
+        tx.send(1).unwrap();
+        assert_eq!(rx.recv_deadline(deadline).await, Ok(1));
+    }
+
+    #[tokio::test]
+    async fn recv_deadline_times_out_repeatedly() {
+        let (_tx, mut rx) = channel::<i32>(2);
+
+        // A receiver that timed out can wait on a deadline again. The timer thread exiting and
+        // starting again in between is tested in `util::timer`.
+        for _ in 0..2 {
+            let deadline = Instant::now() + std::time::Duration::from_millis(10);
+            assert_eq!(rx.recv_deadline(deadline).await, Err(RecvTimeoutError::Timeout));
+        }
+    }
+
+    #[test]
+    fn recv_deadline_cancelled() {
+        let (tx, mut rx) = channel::<i32>(2);
+
+        let deadline = Instant::now() + std::time::Duration::from_secs(60);
+        let mut recv = task::spawn(rx.recv_deadline(deadline));
+        assert!(recv.poll().is_pending());
+        assert!(!tx.shared.tail.lock().unwrap().waiters.is_empty());
+
+        drop(recv);
+        assert!(tx.shared.tail.lock().unwrap().waiters.is_empty());
+        #[cfg(feature = "leak-detect")]
+        crate::waiter::debug::assert_no_leaked_waiters();
+    }
+
+    #[cfg(feature = "debug")]
+    #[test]
+    fn debug_positions_advance() {
+        let (tx, mut rx) = channel(2);
+        assert_eq!(tx.debug_positions(), (0, 0));
+        assert_eq!(rx.debug_position(), 0);
+
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        rx.try_recv().unwrap();
+        assert_eq!(tx.debug_positions(), (2, 0));
+        assert_eq!(rx.debug_position(), 1);
+
+        // Overwriting moves the oldest position past the receiver's.
+        tx.send(3).unwrap();
+        tx.send(4).unwrap();
+        assert_eq!(tx.debug_positions(), (4, 2));
+        assert_eq!(rx.debug_position(), 1);
+
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Lagged(1)));
+        assert_eq!(rx.debug_position(), 2);
+        rx.try_recv().unwrap();
+        rx.try_recv().unwrap();
+        assert_eq!(rx.debug_position(), 4);
+
+        // A new receiver starts at the write position.
+        assert_eq!(tx.subscribe().debug_position(), 4);
+    }
+
+    #[test]
+    fn weak_sender() {
+        let (tx, mut rx) = channel::<i32>(2);
+        let weak = tx.downgrade();
+        assert_eq!(rx.sender_count(), 1);
+
+        let tx2 = weak.upgrade().unwrap();
+        assert_eq!(rx.sender_count(), 2);
+        drop(tx2);
+
+        let mut recv = task::spawn(rx.recv());
+        assert!(recv.poll().is_pending());
+        // The weak sender doesn't keep the channel open.
+        drop(tx);
+        assert!(recv.is_woken());
+        assert_eq!(recv.poll(), Poll::Ready(Err(RecvError::Closed)));
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn close_wakes_waiting_receiver() {
+        let (tx, mut rx) = channel::<i32>(2);
+        let weak = tx.downgrade();
+        let mut rx2 = tx.subscribe();
+        tx.send(1).unwrap();
+        assert_eq!(rx.try_recv(), Ok(1));
+
+        let mut recv = task::spawn(rx.recv());
+        assert!(recv.poll().is_pending());
+        assert!(!tx.is_closed());
+
+        tx.close();
+        assert!(tx.is_closed());
+        assert!(recv.is_woken());
+        assert_eq!(recv.poll(), Poll::Ready(Err(RecvError::Closed)));
+        drop(recv);
+        assert!(tx.shared.tail.lock().unwrap().waiters.is_empty());
+
+        // A receiver behind still gets the values retained.
+        assert_eq!(rx2.try_recv(), Ok(1));
+        assert_eq!(rx2.try_recv(), Err(TryRecvError::Closed));
+
+        assert_eq!(tx.send(2), Err(TrySendError::Closed(2)));
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[tokio::test]
+    async fn recv_many() {
+        let (tx, mut rx) = channel(8);
+        let mut buf = vec![];
+
+        let mut recv = task::spawn(rx.recv_many(&mut buf, 4));
+        assert!(recv.poll().is_pending());
+        for i in 0..6 {
+            tx.send(i).unwrap();
+        }
+        assert!(recv.is_woken());
+        assert_eq!(recv.poll(), Poll::Ready(Ok(4)));
+        drop(recv);
+        assert_eq!(buf, [0, 1, 2, 3]);
+
+        assert_eq!(rx.recv_many(&mut buf, 4).await, Ok(2));
+        assert_eq!(buf, [0, 1, 2, 3, 4, 5]);
+        assert_eq!(rx.recv_many(&mut buf, 0).await, Ok(0));
+
+        for i in 0..10 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(rx.recv_many(&mut buf, 100).await, Err(RecvError::Lagged(2)));
+        buf.clear();
+        assert_eq!(rx.recv_many(&mut buf, 100).await, Ok(8));
+        assert_eq!(buf, [2, 3, 4, 5, 6, 7, 8, 9]);
+
+        drop(tx);
+        assert_eq!(rx.recv_many(&mut buf, 100).await, Err(RecvError::Closed));
+    }
+
+    #[test]
+    fn poll_recv_by_hand() {
+        let (tx, mut rx1) = channel(2);
+        let mut rx2 = tx.subscribe();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        assert!(rx1.poll_recv(&mut cx).is_pending());
+        assert!(rx2.poll_recv(&mut cx).is_pending());
+        // Polling again doesn't queue the receiver twice.
+        assert!(rx1.poll_recv(&mut cx).is_pending());
+
+        tx.send(1).unwrap();
+        assert_eq!(rx1.poll_recv(&mut cx), Poll::Ready(Ok(1)));
+
+        // Abandoned while queued, then dropped: the drop takes it out of the
+        // list.
+        assert!(rx1.poll_recv(&mut cx).is_pending());
+        drop(rx1);
+        tx.send(2).unwrap();
+
+        assert_eq!(rx2.poll_recv(&mut cx), Poll::Ready(Ok(1)));
+        assert_eq!(rx2.poll_recv(&mut cx), Poll::Ready(Ok(2)));
+    }
+
+    #[tokio::test]
+    async fn receiver_moves_while_queued() {
+        let (tx, rx) = channel(2);
+        let mut rx = Box::new(rx);
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(rx.poll_recv(&mut cx).is_pending());
+
+        // The waiter is left queued; the receiver moves anyway.
+        let mut rx = *rx;
+        tx.send(7).unwrap();
+        assert_eq!(rx.recv().await, Ok(7));
+    }
+
+    #[cfg(feature = "stream")]
+    #[tokio::test]
+    async fn stream_to_completion() {
+        use futures_core::Stream;
+
+        let (tx, mut rx) = channel(2);
+        let handle = tokio::spawn(async move {
+            let mut items = vec![];
+            while let Some(item) = poll_fn(|cx| Pin::new(&mut rx).poll_next(cx)).await {
+                items.push(item);
+            }
+            items
+        });
+        for i in 0..4 {
+            tx.send(i).unwrap();
+            tokio::task::yield_now().await;
+        }
+        drop(tx);
+
+        let items = handle.await.unwrap();
+        // Sends may outpace the receiving task, so only the tail of the values
+        // is certain.
+        assert!(items.iter().all(|item| !matches!(item, Err(RecvError::Closed))));
+        assert_eq!(items.last(), Some(&Ok(3)));
+    }
+
+    #[test]
+    fn completed_send_does_not_wake_next() {
+        let (tx, mut rx) = channel_with_policy(1, OverflowPolicy::BlockSender);
+        tx.send(1).unwrap();
+
+        let mut a = task::spawn(tx.send_async(2));
+        let mut b = task::spawn(tx.send_async(3));
+        assert!(a.poll().is_pending());
+        assert!(b.poll().is_pending());
+
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert!(a.is_woken());
+        assert!(a.poll().is_ready());
+        // a used the slot it was woken for; dropping it wakes no one.
+        drop(a);
+        assert!(!b.is_woken());
+        assert!(b.poll().is_pending());
+
+        assert_eq!(rx.try_recv(), Ok(2));
+        assert!(b.is_woken());
+        assert!(b.poll().is_ready());
+    }
+}
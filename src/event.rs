@@ -0,0 +1,202 @@
+//! event - a manual-reset event
+//!
+//! An `Event` built on the `waiter` module. Calling `set` signals the event and releases every
+//! waiting task with `awake_waiters`. Unlike a permit stored by `Notify::notify_one`, which the
+//! first task to wait consumes, the signal is sticky: every task that waits while the event is
+//! set completes on its first poll, until `reset` clears it.
+//!
+//! A task woken by `set` completes even if `reset` is called before it is polled again.
+//!
+//! # Examples
+//!
+//! ```
+//! use broadcast_rs::event::Event;
+//! use std::sync::Arc;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let event = Arc::new(Event::new());
+//!     let event2 = event.clone();
+//!
+//!     let handle = tokio::spawn(async move { event2.wait().await });
+//!     event.set();
+//!     handle.await.unwrap();
+//!
+//!     // Still set.
+//!     event.wait().await;
+//! }
+//! ```
+
+use crate::waiter::{List, Registration};
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+
+/// A manual-reset event.
+///
+/// See the module documentation.
+pub struct Event {
+    state: Mutex<State>,
+}
+
+struct State {
+    set: bool,
+    waiters: List<()>,
+}
+
+/// Future returned by [`Event::wait`].
+pub struct Wait<'a> {
+    event: &'a Event,
+
+    /// Entry in the event's waiter list, registered once the elem has been queued, until the event
+    /// is seen set.
+    registration: Registration<()>,
+}
+
+unsafe impl<'a> Send for Wait<'a> {}
+unsafe impl<'a> Sync for Wait<'a> {}
+
+impl Event {
+    /// Creates an event that is not set.
+    pub fn new() -> Event {
+        Event {
+            state: Mutex::new(State {
+                set: false,
+                waiters: List::new(),
+            }),
+        }
+    }
+
+    /// Sets the event, releasing all the waiting tasks. Does nothing if already set.
+    pub fn set(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.set = true;
+        state.waiters.awake_waiters();
+    }
+
+    /// Clears the event, so tasks calling `wait` from now on wait for the next `set`.
+    pub fn reset(&self) {
+        self.state.lock().unwrap().set = false;
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.state.lock().unwrap().set
+    }
+
+    /// Waits for the event to be set. Completes on the first poll if it already is.
+    pub fn wait(&self) -> Wait<'_> {
+        Wait {
+            event: self,
+            // Safety: the elem is removed from the event's list by Wait's drop.
+            registration: unsafe { Registration::new() },
+        }
+    }
+}
+
+impl Default for Event {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Future for Wait<'a> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let event = self.event;
+        let registration = &self.registration;
+        let mut state = event.state.lock().unwrap();
+
+        // Once queued, being woken is what counts; the event may have been reset since.
+        let done = if registration.is_registered() {
+            !state.waiters.is_queued(registration.elem())
+        } else {
+            state.set
+        };
+        if done {
+            registration.unregister();
+            return Poll::Ready(());
+        }
+
+        registration.register(&mut state.waiters, cx);
+        Poll::Pending
+    }
+}
+
+impl<'a> Drop for Wait<'a> {
+    fn drop(&mut self) {
+        if !self.registration.is_registered() {
+            return;
+        }
+        let mut state = self.event.state.lock().unwrap();
+
+        // Safety: the elem is only ever queued in the event's list.
+        unsafe {
+            state.waiters.remove_waiter(self.registration.elem());
+        }
+    }
+}
+
+impl fmt::Debug for Event {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("Event").field("set", &self.is_set()).finish()
+    }
+}
+
+impl<'a> fmt::Debug for Wait<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Wait")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_test::task;
+
+    #[test]
+    fn set_then_wait() {
+        let event = Event::new();
+        event.set();
+        assert!(task::spawn(event.wait()).poll().is_ready());
+        // The signal isn't consumed.
+        assert!(task::spawn(event.wait()).poll().is_ready());
+    }
+
+    #[test]
+    fn wait_then_set() {
+        let event = Event::new();
+        let mut a = task::spawn(event.wait());
+        let mut b = task::spawn(event.wait());
+        assert!(a.poll().is_pending());
+        assert!(b.poll().is_pending());
+
+        event.set();
+        assert!(a.is_woken() && b.is_woken());
+        assert!(a.poll().is_ready());
+        assert!(b.poll().is_ready());
+    }
+
+    #[test]
+    fn reset() {
+        let event = Event::new();
+        let mut a = task::spawn(event.wait());
+        assert!(a.poll().is_pending());
+
+        event.set();
+        event.reset();
+        assert!(!event.is_set());
+        // Woken by the set, a completes despite the reset.
+        assert!(a.is_woken());
+        assert!(a.poll().is_ready());
+
+        // New waiters wait for the next set.
+        let mut b = task::spawn(event.wait());
+        assert!(b.poll().is_pending());
+        event.set();
+        assert!(b.poll().is_ready());
+    }
+}
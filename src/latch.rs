@@ -0,0 +1,191 @@
+//! latch - wait for a count to reach zero
+//!
+//! A `CountdownLatch` built on the `waiter` module. Tasks calling `wait` are queued until
+//! `count_down` has been called as many times as the latch was created with, at which point they
+//! are all released with `awake_waiters`. Once at zero the latch stays open: a later `wait`
+//! completes on its first poll.
+//!
+//! # Examples
+//!
+//! ```
+//! use broadcast_rs::latch::CountdownLatch;
+//! use std::sync::Arc;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let latch = Arc::new(CountdownLatch::new(3));
+//!     for _ in 0..3 {
+//!         let latch = latch.clone();
+//!         tokio::spawn(async move { latch.count_down() });
+//!     }
+//!     latch.wait().await;
+//!     assert_eq!(latch.count(), 0);
+//! }
+//! ```
+
+use crate::waiter::{List, Registration};
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+
+/// A latch that releases its waiters once counted down to zero.
+///
+/// See the module documentation.
+pub struct CountdownLatch {
+    state: Mutex<State>,
+}
+
+struct State {
+    count: usize,
+    waiters: List<()>,
+}
+
+/// Future returned by [`CountdownLatch::wait`].
+pub struct Wait<'a> {
+    latch: &'a CountdownLatch,
+
+    /// Entry in the latch's waiter list, registered once the elem has been queued, until the latch
+    /// opens.
+    registration: Registration<()>,
+}
+
+unsafe impl<'a> Send for Wait<'a> {}
+unsafe impl<'a> Sync for Wait<'a> {}
+
+impl CountdownLatch {
+    /// Creates a latch that opens after `count` calls to `count_down`. A latch created with a
+    /// count of zero is open from the start.
+    pub fn new(count: usize) -> CountdownLatch {
+        CountdownLatch {
+            state: Mutex::new(State {
+                count,
+                waiters: List::new(),
+            }),
+        }
+    }
+
+    /// Decrements the count, releasing all the waiting tasks when it reaches zero. Does
+    /// nothing if the count is already zero.
+    pub fn count_down(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.count == 0 {
+            return;
+        }
+        state.count -= 1;
+        if state.count == 0 {
+            state.waiters.awake_waiters();
+        }
+    }
+
+    /// Returns the current count.
+    pub fn count(&self) -> usize {
+        self.state.lock().unwrap().count
+    }
+
+    /// Waits for the count to reach zero.
+    pub fn wait(&self) -> Wait<'_> {
+        Wait {
+            latch: self,
+            // Safety: the elem is removed from the latch's list by Wait's drop.
+            registration: unsafe { Registration::new() },
+        }
+    }
+}
+
+impl<'a> Future for Wait<'a> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let latch = self.latch;
+        let registration = &self.registration;
+        let mut state = latch.state.lock().unwrap();
+
+        if state.count == 0 {
+            // Woken, or the latch was already open.
+            registration.unregister();
+            return Poll::Ready(());
+        }
+
+        registration.register(&mut state.waiters, cx);
+        Poll::Pending
+    }
+}
+
+impl<'a> Drop for Wait<'a> {
+    fn drop(&mut self) {
+        if !self.registration.is_registered() {
+            return;
+        }
+        let mut state = self.latch.state.lock().unwrap();
+
+        // Safety: the elem is only ever queued in the latch's list.
+        unsafe {
+            state.waiters.remove_waiter(self.registration.elem());
+        }
+    }
+}
+
+impl fmt::Debug for CountdownLatch {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "CountdownLatch({})", self.count())
+    }
+}
+
+impl<'a> fmt::Debug for Wait<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Wait")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_test::task;
+
+    #[test]
+    fn waiters_before_zero() {
+        let latch = CountdownLatch::new(2);
+        let mut a = task::spawn(latch.wait());
+        let mut b = task::spawn(latch.wait());
+        assert!(a.poll().is_pending());
+        assert!(b.poll().is_pending());
+
+        latch.count_down();
+        assert!(!a.is_woken() && !b.is_woken());
+        assert!(a.poll().is_pending());
+
+        latch.count_down();
+        assert!(a.is_woken() && b.is_woken());
+        assert!(a.poll().is_ready());
+        assert!(b.poll().is_ready());
+    }
+
+    #[test]
+    fn waiters_after_zero() {
+        let latch = CountdownLatch::new(1);
+        latch.count_down();
+        // Extra calls leave the latch open.
+        latch.count_down();
+        assert_eq!(latch.count(), 0);
+        assert!(task::spawn(latch.wait()).poll().is_ready());
+
+        assert!(task::spawn(CountdownLatch::new(0).wait()).poll().is_ready());
+    }
+
+    #[test]
+    fn cancelled_wait() {
+        let latch = CountdownLatch::new(1);
+        let mut a = task::spawn(latch.wait());
+        let mut b = task::spawn(latch.wait());
+        assert!(a.poll().is_pending());
+        assert!(b.poll().is_pending());
+        drop(a);
+
+        latch.count_down();
+        assert!(b.is_woken());
+        assert!(b.poll().is_ready());
+    }
+}
@@ -1,6 +1,34 @@
+//! Async synchronization primitives built on an intrusive waiter list, starting from a copy of
+//! tokio's `sync::broadcast`.
+//!
+//! # Threads
+//!
+//! The crate starts one thread of its own. [`broadcast::Receiver::recv_deadline`] is woken at its
+//! deadline by a thread named `broadcast-rs-timer`, shared by the whole process. The thread is
+//! started the first time a deadline receive has to wait, and exits once no receive is waiting on
+//! a deadline, to be started again by the next one. Nothing else in the crate spawns a thread.
+
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+
+#[cfg(any(test, feature = "alloc"))]
+extern crate alloc;
+
 #[macro_use]
 pub mod macros;
+pub mod single_waiter;
 pub mod util;
 
-pub mod broadcast;
+cfg_std! {
+    pub mod barrier;
+    pub mod broadcast;
+    pub mod event;
+    pub mod latch;
+    pub mod mutex;
+    pub mod notify;
+    pub mod oneshot;
+    pub mod rwlock;
+    pub mod semaphore;
+}
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 pub mod waiter;
@@ -1,7 +1,38 @@
 //! This module defines a macro that lets you go from a raw pointer to a struct
 //! to a raw pointer to a field of the struct.
 
+/// Generates methods that go from a `NonNull` pointer to a struct to a `NonNull` pointer to one of
+/// its fields, without creating an intermediate reference.
+///
+/// This is what an implementation of `util::linked_list::Link::pointers` needs, as it may not
+/// create a reference to the node while the node is linked into a list.
+///
+/// # Examples
+///
+/// ```
+/// use broadcast_rs::generate_addr_of_methods;
+/// use broadcast_rs::util::linked_list::Pointers;
+/// use std::ptr::NonNull;
+///
+/// struct Node {
+///     pointers: Pointers<Node>,
+///     val: u32,
+/// }
+///
+/// generate_addr_of_methods! {
+///     impl<> Node {
+///         unsafe fn addr_of_pointers(self: NonNull<Self>) -> NonNull<Pointers<Node>> {
+///             &self.pointers
+///         }
+///     }
+/// }
+///
+/// let mut node = Node { pointers: Pointers::new(), val: 1 };
+/// let pointers = unsafe { Node::addr_of_pointers(NonNull::from(&mut node)) };
+/// assert!(unsafe { pointers.as_ref() }.get_next().is_none());
+/// ```
 #[cfg(not(tokio_no_addr_of))]
+#[macro_export]
 macro_rules! generate_addr_of_methods {
     (
     impl<$($gen:ident)*> $struct_name:ty {$(
@@ -15,7 +46,7 @@ macro_rules! generate_addr_of_methods {
             $(#[$attrs])*
             $vis unsafe fn $fn_name(me: ::core::ptr::NonNull<Self>) -> ::core::ptr::NonNull<$field_type> {
                 let me = me.as_ptr();
-                let field = ::std::ptr::addr_of_mut!((*me) $(.$field_name)+ );
+                let field = ::core::ptr::addr_of_mut!((*me) $(.$field_name)+ );
                 ::core::ptr::NonNull::new_unchecked(field)
             }
         )*}
@@ -25,6 +56,7 @@ macro_rules! generate_addr_of_methods {
 // The `addr_of_mut!` macro is only available for MSRV at least 1.51.0. This
 // version of the macro uses a workaround for older versions of rustc.
 #[cfg(tokio_no_addr_of)]
+#[macro_export]
 macro_rules! generate_addr_of_methods {
     (
     impl<$($gen:ident)*> $struct_name:ty {$(
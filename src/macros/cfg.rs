@@ -0,0 +1,12 @@
+#![allow(unused_macros)]
+
+/// Enables the items only with the `std` feature, for the parts of the crate that need more than
+/// `core`, such as those built on `std::sync::Mutex`.
+macro_rules! cfg_std {
+    ($($item:item)*) => {
+        $(
+            #[cfg(feature = "std")]
+            $item
+        )*
+    }
+}
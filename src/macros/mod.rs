@@ -2,3 +2,9 @@
 
 #[macro_use]
 mod addr_of;
+
+#[macro_use]
+mod cfg;
+
+#[macro_use]
+mod trace;
@@ -0,0 +1,8 @@
+/// Emits a trace level `tracing` event with the `tracing` feature. Without it, the arguments are
+/// not even compiled.
+macro_rules! trace_waiter {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::trace!($($arg)*);
+    };
+}
@@ -0,0 +1,327 @@
+//! mutex - an async mutual exclusion lock
+//!
+//! A `Mutex` built on the `waiter` module. The lock is handed over directly: when a guard is
+//! dropped with tasks waiting, the mutex stays locked and ownership passes to the oldest waiter,
+//! which is woken with `wake_one_with`. No task arriving later can take the lock in between, so
+//! waiters acquire it in FIFO order.
+//!
+//! A waiting `Lock` future that is dropped after being handed the lock, but before being polled
+//! again, passes it on to the next waiter in the same way.
+//!
+//! # Examples
+//!
+//! ```
+//! use broadcast_rs::mutex::Mutex;
+//! use std::sync::Arc;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let count = Arc::new(Mutex::new(0));
+//!     let handles: Vec<_> = (0..4)
+//!         .map(|_| {
+//!             let count = count.clone();
+//!             tokio::spawn(async move { *count.lock().await += 1 })
+//!         })
+//!         .collect();
+//!     for handle in handles {
+//!         handle.await.unwrap();
+//!     }
+//!     assert_eq!(*count.lock().await, 4);
+//! }
+//! ```
+
+use crate::util::unsafe_cell::UnsafeCell;
+use crate::waiter::{List, Registration};
+
+use std::fmt;
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// An async mutual exclusion lock protecting a `T`.
+///
+/// See the module documentation.
+pub struct Mutex<T> {
+    state: std::sync::Mutex<State>,
+    data: UnsafeCell<T>,
+}
+
+struct State {
+    /// True while a guard is held, or the lock has been handed to a waiter not yet polled.
+    locked: bool,
+
+    /// Tasks waiting for the lock. The waiter handed the lock is given a `()` payload.
+    waiters: List<()>,
+}
+
+unsafe impl<T: Send> Send for Mutex<T> {}
+unsafe impl<T: Send> Sync for Mutex<T> {}
+
+/// Guard giving access to the data of a locked [`Mutex`]. The lock is released when it is
+/// dropped.
+#[must_use]
+pub struct MutexGuard<'a, T> {
+    mutex: &'a Mutex<T>,
+}
+
+unsafe impl<'a, T: Sync> Sync for MutexGuard<'a, T> {}
+
+/// Error returned by [`Mutex::try_lock`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TryLockError(());
+
+impl fmt::Display for TryLockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "mutex locked")
+    }
+}
+
+impl std::error::Error for TryLockError {}
+
+/// Future returned by [`Mutex::lock`].
+pub struct Lock<'a, T> {
+    mutex: &'a Mutex<T>,
+
+    /// Entry in the mutex's waiter list, registered once the elem has been queued, until the lock
+    /// is taken.
+    registration: Registration<()>,
+}
+
+unsafe impl<'a, T: Send> Send for Lock<'a, T> {}
+unsafe impl<'a, T: Send> Sync for Lock<'a, T> {}
+
+impl<T> Mutex<T> {
+    /// Creates an unlocked mutex holding `data`.
+    pub fn new(data: T) -> Mutex<T> {
+        Mutex {
+            state: std::sync::Mutex::new(State {
+                locked: false,
+                waiters: List::new(),
+            }),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Locks the mutex, waiting for the lock if it is held.
+    pub fn lock(&self) -> Lock<'_, T> {
+        Lock {
+            mutex: self,
+            // Safety: the elem is removed from the mutex's list by Lock's drop.
+            registration: unsafe { Registration::new() },
+        }
+    }
+
+    /// Locks the mutex if it is free and no task is waiting for it.
+    pub fn try_lock(&self) -> Result<MutexGuard<'_, T>, TryLockError> {
+        let mut state = self.state.lock().unwrap();
+        if state.try_lock() {
+            Ok(MutexGuard { mutex: self })
+        } else {
+            Err(TryLockError(()))
+        }
+    }
+}
+
+impl State {
+    fn try_lock(&mut self) -> bool {
+        if self.locked || !self.waiters.is_empty() {
+            return false;
+        }
+        self.locked = true;
+        true
+    }
+
+    /// Hands the lock to the oldest waiter, or unlocks if there is none.
+    fn unlock(&mut self) {
+        if self.waiters.wake_one_with(()).is_err() {
+            self.locked = false;
+        }
+    }
+}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<'a, T> Deref for MutexGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // Safety: the guard holds the lock.
+        self.mutex.data.with(|ptr| unsafe { &*ptr })
+    }
+}
+
+impl<'a, T> DerefMut for MutexGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // Safety: the guard holds the lock and is borrowed mutably.
+        self.mutex.data.with_mut(|ptr| unsafe { &mut *ptr })
+    }
+}
+
+impl<'a, T> Drop for MutexGuard<'a, T> {
+    fn drop(&mut self) {
+        self.mutex.state.lock().unwrap().unlock();
+    }
+}
+
+impl<'a, T> Future for Lock<'a, T> {
+    type Output = MutexGuard<'a, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<MutexGuard<'a, T>> {
+        let mutex = self.mutex;
+        let registration = &self.registration;
+        let mut state = mutex.state.lock().unwrap();
+
+        if !registration.is_registered() {
+            if state.try_lock() {
+                return Poll::Ready(MutexGuard { mutex });
+            }
+            registration.register(&mut state.waiters, cx);
+            return Poll::Pending;
+        }
+
+        if state.waiters.take_payload(registration.elem()).is_some() {
+            // Handed the lock by unlock.
+            registration.unregister();
+            return Poll::Ready(MutexGuard { mutex });
+        }
+
+        registration.register(&mut state.waiters, cx);
+        Poll::Pending
+    }
+}
+
+impl<'a, T> Drop for Lock<'a, T> {
+    fn drop(&mut self) {
+        if !self.registration.is_registered() {
+            return;
+        }
+        let mut state = self.mutex.state.lock().unwrap();
+
+        // Safety: the elem is only ever queued in the mutex's list.
+        unsafe {
+            state.waiters.remove_waiter(self.registration.elem());
+        }
+
+        // Handed the lock but never took it: pass it on.
+        if state.waiters.take_payload(self.registration.elem()).is_some() {
+            state.unlock();
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Mutex<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.try_lock() {
+            Ok(guard) => fmt.debug_struct("Mutex").field("data", &&*guard).finish(),
+            Err(_) => fmt.debug_struct("Mutex").field("data", &"<locked>").finish(),
+        }
+    }
+}
+
+impl<'a, T: fmt::Debug> fmt::Debug for MutexGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, fmt)
+    }
+}
+
+impl<'a, T> fmt::Debug for Lock<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Lock")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_test::task;
+
+    #[test]
+    fn try_lock() {
+        let mutex = Mutex::new(1);
+        let mut guard = mutex.try_lock().unwrap();
+        assert_eq!(mutex.try_lock().unwrap_err(), TryLockError(()));
+        *guard += 1;
+        drop(guard);
+        assert_eq!(*mutex.try_lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn handoff_is_fifo() {
+        let mutex = Mutex::new(());
+        let guard = mutex.try_lock().unwrap();
+
+        let mut a = task::spawn(mutex.lock());
+        let mut b = task::spawn(mutex.lock());
+        assert!(a.poll().is_pending());
+        assert!(b.poll().is_pending());
+
+        drop(guard);
+        assert!(a.is_woken() && !b.is_woken());
+        // The lock was handed to a, so it isn't free for anyone else.
+        assert!(mutex.try_lock().is_err());
+
+        let guard = match a.poll() {
+            Poll::Ready(guard) => guard,
+            Poll::Pending => panic!("a not ready"),
+        };
+        assert!(b.poll().is_pending());
+        drop(guard);
+        assert!(b.is_woken());
+        assert!(b.poll().is_ready());
+    }
+
+    #[test]
+    fn cancelled_waiter_passes_lock_on() {
+        let mutex = Mutex::new(());
+        let guard = mutex.try_lock().unwrap();
+
+        let mut a = task::spawn(mutex.lock());
+        let mut b = task::spawn(mutex.lock());
+        let mut c = task::spawn(mutex.lock());
+        assert!(a.poll().is_pending());
+        assert!(b.poll().is_pending());
+        assert!(c.poll().is_pending());
+
+        // Cancelled while waiting.
+        drop(b);
+        drop(guard);
+        assert!(a.is_woken());
+        // Cancelled after being handed the lock.
+        drop(a);
+        assert!(c.is_woken());
+        assert!(c.poll().is_ready());
+        drop(c);
+        assert!(mutex.try_lock().is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn mutual_exclusion() {
+        use std::sync::Arc;
+
+        let mutex = Arc::new(Mutex::new((0usize, false)));
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let mutex = mutex.clone();
+                tokio::spawn(async move {
+                    for _ in 0..100 {
+                        let mut guard = mutex.lock().await;
+                        assert!(!guard.1);
+                        guard.1 = true;
+                        tokio::task::yield_now().await;
+                        guard.0 += 1;
+                        guard.1 = false;
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert_eq!(mutex.lock().await.0, 800);
+    }
+}
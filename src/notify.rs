@@ -0,0 +1,270 @@
+//! notify - notify a single task, or all waiting tasks, of an event
+//!
+//! A `Notify` built on the `waiter` module. It follows the Tokio type of the same name: a call to
+//! `notify_one` when no task is waiting stores a single permit that the next `notified().await`
+//! consumes immediately, while `notify_waiters` only wakes the tasks already waiting and stores
+//! nothing.
+//!
+//! The `Notified` future embeds a `waiter::Elem` and removes it from the list in its drop, so
+//! this module is sound to use even though the waiter module is not.
+//!
+//! # Examples
+//!
+//! ```
+//! use broadcast_rs::notify::Notify;
+//! use std::sync::Arc;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let notify = Arc::new(Notify::new());
+//!     let notify2 = notify.clone();
+//!
+//!     let handle = tokio::spawn(async move {
+//!         notify2.notified().await;
+//!         "received notification"
+//!     });
+//!
+//!     notify.notify_one();
+//!     assert_eq!(handle.await.unwrap(), "received notification");
+//! }
+//! ```
+
+use crate::waiter::{List, Registration};
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+
+/// Notifies a single task, or all waiting tasks, of an event.
+///
+/// See the module documentation.
+pub struct Notify {
+    state: Mutex<State>,
+}
+
+struct State {
+    /// Tasks waiting to be notified. A task woken by `notify_one` is handed a `()` payload, a
+    /// task woken by `notify_waiters` is not.
+    waiters: List<()>,
+
+    /// True if `notify_one` was called with no task waiting.
+    permit: bool,
+}
+
+/// Future returned by [`Notify::notified`].
+pub struct Notified<'a> {
+    notify: &'a Notify,
+
+    /// Entry in the notify's waiter list, registered once the elem has been queued.
+    registration: Registration<()>,
+}
+
+unsafe impl<'a> Send for Notified<'a> {}
+unsafe impl<'a> Sync for Notified<'a> {}
+
+impl Notify {
+    pub fn new() -> Notify {
+        Notify {
+            state: Mutex::new(State {
+                waiters: List::new(),
+                permit: false,
+            }),
+        }
+    }
+
+    /// Waits for a notification.
+    ///
+    /// If a permit is stored, because `notify_one` was called with no task waiting, the
+    /// returned future consumes it and completes on its first poll. Otherwise the future waits
+    /// for a call to `notify_one` or `notify_waiters` made after it was first polled.
+    pub fn notified(&self) -> Notified<'_> {
+        Notified {
+            notify: self,
+            // Safety: the elem is removed from the notify's list by Notified's drop.
+            registration: unsafe { Registration::new() },
+        }
+    }
+
+    /// Notifies the oldest waiting task. If no task is waiting, a permit is stored so the next
+    /// call to `notified().await` completes immediately. At most one permit is stored.
+    pub fn notify_one(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.notify_one();
+    }
+
+    /// Notifies all the tasks currently waiting. No permit is stored, so a task that starts
+    /// waiting afterwards is not notified.
+    pub fn notify_waiters(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.waiters.awake_waiters();
+    }
+}
+
+impl State {
+    fn notify_one(&mut self) {
+        if self.waiters.wake_one_with(()).is_err() {
+            self.permit = true;
+        }
+    }
+}
+
+impl Default for Notify {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Future for Notified<'a> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let notify = self.notify;
+        let registration = &self.registration;
+        let mut state = notify.state.lock().unwrap();
+
+        if !registration.is_registered() {
+            if state.permit {
+                state.permit = false;
+                return Poll::Ready(());
+            }
+            registration.register(&mut state.waiters, cx);
+            return Poll::Pending;
+        }
+
+        if !state.waiters.is_queued(registration.elem()) {
+            // Woken: consume the notification, if it was one from notify_one.
+            state.waiters.take_payload(registration.elem());
+            return Poll::Ready(());
+        }
+
+        registration.register(&mut state.waiters, cx);
+        Poll::Pending
+    }
+}
+
+impl<'a> Drop for Notified<'a> {
+    fn drop(&mut self) {
+        let mut state = self.notify.state.lock().unwrap();
+
+        // Safety: the elem is only ever queued in the notify's list.
+        unsafe {
+            state.waiters.remove_waiter(self.registration.elem());
+        }
+
+        // A notification from notify_one that was never consumed is passed on, so it isn't lost.
+        if state.waiters.take_payload(self.registration.elem()).is_some() {
+            state.notify_one();
+        }
+    }
+}
+
+impl fmt::Debug for Notify {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Notify")
+    }
+}
+
+impl<'a> fmt::Debug for Notified<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Notified")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_test::task;
+
+    #[test]
+    fn notify_before_await() {
+        let notify = Notify::new();
+        notify.notify_one();
+        // Only one permit is stored.
+        notify.notify_one();
+
+        let mut a = task::spawn(notify.notified());
+        assert!(a.poll().is_ready());
+
+        let mut b = task::spawn(notify.notified());
+        assert!(b.poll().is_pending());
+        notify.notify_one();
+        assert!(b.is_woken());
+        assert!(b.poll().is_ready());
+    }
+
+    #[test]
+    fn notify_one_wakes_oldest() {
+        let notify = Notify::new();
+        let mut a = task::spawn(notify.notified());
+        let mut b = task::spawn(notify.notified());
+        assert!(a.poll().is_pending());
+        assert!(b.poll().is_pending());
+
+        notify.notify_one();
+        assert!(a.is_woken() && !b.is_woken());
+        assert!(a.poll().is_ready());
+        assert!(b.poll().is_pending());
+    }
+
+    #[test]
+    fn notify_waiters_wakes_all() {
+        let notify = Notify::new();
+        let mut a = task::spawn(notify.notified());
+        let mut b = task::spawn(notify.notified());
+        assert!(a.poll().is_pending());
+        assert!(b.poll().is_pending());
+
+        notify.notify_waiters();
+        assert!(a.is_woken() && b.is_woken());
+        assert!(a.poll().is_ready());
+        assert!(b.poll().is_ready());
+
+        // No permit was stored.
+        let mut c = task::spawn(notify.notified());
+        assert!(c.poll().is_pending());
+    }
+
+    #[test]
+    fn dropped_notified_passes_notification_on() {
+        let notify = Notify::new();
+        let mut a = task::spawn(notify.notified());
+        let mut b = task::spawn(notify.notified());
+        assert!(a.poll().is_pending());
+        assert!(b.poll().is_pending());
+
+        notify.notify_one();
+        assert!(a.is_woken());
+        drop(a);
+        assert!(b.is_woken());
+        assert!(b.poll().is_ready());
+
+        // With no one left to pass it to, it becomes the stored permit.
+        let mut c = task::spawn(notify.notified());
+        assert!(c.poll().is_pending());
+        notify.notify_one();
+        drop(c);
+        assert!(task::spawn(notify.notified()).poll().is_ready());
+    }
+
+    #[tokio::test]
+    async fn notify_across_tasks() {
+        use std::sync::Arc;
+
+        let notify = Arc::new(Notify::new());
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let notify = notify.clone();
+                tokio::spawn(async move { notify.notified().await })
+            })
+            .collect();
+        for _ in 0..4 {
+            notify.notify_one();
+            tokio::task::yield_now().await;
+        }
+        for handle in handles {
+            handle.await.unwrap();
+        }
+    }
+}
@@ -0,0 +1,238 @@
+//! oneshot - a channel for sending a single value
+//!
+//! A [`Sender`] sends one value to a [`Receiver`], which is a future resolving to that value.
+//! Only one task ever waits on the channel, so the receiver parks its waker in a
+//! [`SingleWaiter`] rather than a waiter list.
+//!
+//! If the sender is dropped without sending, the receiver resolves to `Err(Closed)`. If the
+//! receiver is dropped first, [`Sender::send`] hands the value back as `Err(value)`.
+//!
+//! # Examples
+//!
+//! ```
+//! use broadcast_rs::oneshot;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let (tx, rx) = oneshot::channel();
+//!
+//!     tokio::spawn(async move {
+//!         tx.send(3).unwrap();
+//!     });
+//!
+//!     assert_eq!(rx.await, Ok(3));
+//! }
+//! ```
+
+use crate::single_waiter::SingleWaiter;
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+pub mod error {
+    //! Oneshot error types
+
+    use std::fmt;
+
+    /// Error the [`Receiver`](super::Receiver) resolves to when the sender was dropped without
+    /// sending a value.
+    #[derive(Debug, PartialEq, Eq, Clone)]
+    pub struct Closed;
+
+    impl fmt::Display for Closed {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "channel closed")
+        }
+    }
+
+    impl std::error::Error for Closed {}
+}
+
+use self::error::*;
+
+/// Sending half of the channel.
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// Receiving half of the channel. A future resolving to the value sent.
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+struct Shared<T> {
+    slot: Mutex<Slot<T>>,
+
+    /// The receiver's waker, woken when a value is sent or the sender is dropped.
+    rx_waiter: SingleWaiter,
+}
+
+enum Slot<T> {
+    /// Nothing sent yet, and both halves are alive.
+    Empty,
+
+    /// A value sent and not yet received.
+    Value(T),
+
+    /// The value was received.
+    Received,
+
+    /// The sender was dropped without sending.
+    SenderGone,
+
+    /// The receiver was dropped.
+    ReceiverGone,
+}
+
+/// Creates a oneshot channel.
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        slot: Mutex::new(Slot::Empty),
+        rx_waiter: SingleWaiter::new(),
+    });
+    let rx = Receiver {
+        shared: shared.clone(),
+    };
+    (Sender { shared }, rx)
+}
+
+impl<T> Sender<T> {
+    /// Sends `value` to the receiver, waking it if it is waiting.
+    ///
+    /// If the receiver has been dropped, the value is handed back as `Err(value)`.
+    pub fn send(self, value: T) -> Result<(), T> {
+        {
+            let mut slot = self.shared.slot.lock().unwrap();
+            if let Slot::ReceiverGone = *slot {
+                return Err(value);
+            }
+            *slot = Slot::Value(value);
+        }
+        self.shared.rx_waiter.wake();
+        Ok(())
+    }
+
+    /// Returns true if the receiver has been dropped, so a send would fail.
+    pub fn is_closed(&self) -> bool {
+        matches!(*self.shared.slot.lock().unwrap(), Slot::ReceiverGone)
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        {
+            let mut slot = self.shared.slot.lock().unwrap();
+            // After a send, the slot holds the value, or it has already been received.
+            if !matches!(*slot, Slot::Empty) {
+                return;
+            }
+            *slot = Slot::SenderGone;
+        }
+        self.shared.rx_waiter.wake();
+    }
+}
+
+impl<T> Future for Receiver<T> {
+    type Output = Result<T, Closed>;
+
+    /// Resolves to the value sent, or `Err(Closed)` if the sender was dropped without sending.
+    /// Polled again after resolving, it returns `Err(Closed)`.
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<T, Closed>> {
+        // Register before checking, so a send between the check and the register isn't missed.
+        self.shared.rx_waiter.register(cx);
+
+        let mut slot = self.shared.slot.lock().unwrap();
+        match std::mem::replace(&mut *slot, Slot::Received) {
+            Slot::Empty => {
+                *slot = Slot::Empty;
+                Poll::Pending
+            }
+            Slot::Value(value) => Poll::Ready(Ok(value)),
+            Slot::Received | Slot::SenderGone => Poll::Ready(Err(Closed)),
+            Slot::ReceiverGone => unreachable!("receiver polled after drop"),
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        // A value sent but never received is dropped here.
+        let value = std::mem::replace(&mut *self.shared.slot.lock().unwrap(), Slot::ReceiverGone);
+        drop(value);
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "oneshot::Sender")
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "oneshot::Receiver")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_test::task;
+
+    #[test]
+    fn send_wakes_receiver() {
+        let (tx, rx) = channel();
+        let mut rx = task::spawn(rx);
+        assert!(rx.poll().is_pending());
+
+        assert_eq!(tx.send(1), Ok(()));
+        assert!(rx.is_woken());
+        assert_eq!(rx.poll(), Poll::Ready(Ok(1)));
+    }
+
+    #[test]
+    fn send_before_poll() {
+        let (tx, rx) = channel();
+        assert_eq!(tx.send("value"), Ok(()));
+        assert_eq!(task::spawn(rx).poll(), Poll::Ready(Ok("value")));
+    }
+
+    #[test]
+    fn sender_dropped_before_send() {
+        let (tx, rx) = channel::<u32>();
+        let mut rx = task::spawn(rx);
+        assert!(rx.poll().is_pending());
+
+        drop(tx);
+        assert!(rx.is_woken());
+        assert_eq!(rx.poll(), Poll::Ready(Err(Closed)));
+    }
+
+    #[test]
+    fn receiver_dropped_before_send() {
+        let (tx, rx) = channel();
+        assert!(!tx.is_closed());
+        drop(rx);
+        assert!(tx.is_closed());
+        assert_eq!(tx.send(String::from("lost")), Err(String::from("lost")));
+    }
+
+    #[test]
+    fn receiver_dropped_with_value_unreceived() {
+        let value = Arc::new(());
+        let (tx, rx) = channel();
+        tx.send(value.clone()).unwrap();
+        drop(rx);
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+
+    #[tokio::test]
+    async fn send_from_another_task() {
+        let (tx, rx) = channel();
+        tokio::spawn(async move { tx.send(7).unwrap() });
+        assert_eq!(rx.await, Ok(7));
+    }
+}
@@ -0,0 +1,561 @@
+//! rwlock - an async reader-writer lock
+//!
+//! An `RwLock` built on the `waiter` module, with one waiter list for readers and one for
+//! writers. Any number of readers, or a single writer, hold the lock at a time.
+//!
+//! The lock is handed over rather than released to be raced for: when the last reader leaves,
+//! the oldest queued writer is woken already holding the lock, and when a writer leaves, either
+//! all the queued readers are woken together with `awake_waiters`, or the oldest queued writer is
+//! woken with `wake_one`, each already counted as holding it. A woken waiter that is dropped
+//! before being polled again releases what it was handed.
+//!
+//! Which of the two a leaving writer favours, and whether a new reader may join readers already
+//! holding the lock while a writer waits, is set by the lock's [`RwLockPolicy`].
+//!
+//! # Examples
+//!
+//! ```
+//! use broadcast_rs::rwlock::RwLock;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let lock = RwLock::new(5);
+//!
+//!     {
+//!         let r1 = lock.read().await;
+//!         let r2 = lock.read().await;
+//!         assert_eq!(*r1 + *r2, 10);
+//!         assert!(lock.try_write().is_err());
+//!     }
+//!
+//!     *lock.write().await += 1;
+//!     assert_eq!(*lock.read().await, 6);
+//! }
+//! ```
+
+use crate::util::unsafe_cell::UnsafeCell;
+use crate::waiter::{List, Registration};
+
+use std::fmt;
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// An async reader-writer lock protecting a `T`.
+///
+/// See the module documentation.
+pub struct RwLock<T> {
+    state: std::sync::Mutex<State>,
+    data: UnsafeCell<T>,
+}
+
+/// Decides between readers and writers when both are waiting.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RwLockPolicy {
+    /// A new reader joins readers already holding the lock, even while a writer waits, and a
+    /// leaving writer wakes all the queued readers before any queued writer. Readers get the
+    /// most concurrency, but a steady stream of them can starve writers.
+    #[default]
+    PreferReaders,
+
+    /// A new reader queues while any writer waits, and a leaving writer hands the lock to the
+    /// next queued writer before any queued reader. Writers can't be starved by readers, but a
+    /// steady stream of writers can starve readers.
+    PreferWriters,
+}
+
+struct State {
+    policy: RwLockPolicy,
+
+    /// Number of readers holding the lock, including woken readers not yet polled.
+    readers: usize,
+
+    /// True while a writer holds the lock, including a woken writer not yet polled.
+    writer: bool,
+
+    read_waiters: List<()>,
+    write_waiters: List<()>,
+}
+
+unsafe impl<T: Send> Send for RwLock<T> {}
+unsafe impl<T: Send + Sync> Sync for RwLock<T> {}
+
+/// Guard giving shared access to the data of an [`RwLock`]. The read lock is released when it is
+/// dropped.
+#[must_use]
+pub struct RwLockReadGuard<'a, T> {
+    lock: &'a RwLock<T>,
+}
+
+unsafe impl<'a, T: Sync> Send for RwLockReadGuard<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for RwLockReadGuard<'a, T> {}
+
+/// Guard giving exclusive access to the data of an [`RwLock`]. The write lock is released when
+/// it is dropped.
+#[must_use]
+pub struct RwLockWriteGuard<'a, T> {
+    lock: &'a RwLock<T>,
+}
+
+unsafe impl<'a, T: Send + Sync> Send for RwLockWriteGuard<'a, T> {}
+unsafe impl<'a, T: Send + Sync> Sync for RwLockWriteGuard<'a, T> {}
+
+/// Error returned by [`RwLock::try_read`] and [`RwLock::try_write`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TryLockError(());
+
+impl fmt::Display for TryLockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "rwlock locked")
+    }
+}
+
+impl std::error::Error for TryLockError {}
+
+/// Future returned by [`RwLock::read`].
+pub struct Read<'a, T> {
+    lock: &'a RwLock<T>,
+
+    /// Entry in the lock's read waiter list, registered once the elem has been queued, until the
+    /// lock is taken.
+    registration: Registration<()>,
+}
+
+/// Future returned by [`RwLock::write`].
+pub struct Write<'a, T> {
+    lock: &'a RwLock<T>,
+
+    /// Entry in the lock's write waiter list, registered once the elem has been queued, until the
+    /// lock is taken.
+    registration: Registration<()>,
+}
+
+unsafe impl<'a, T: Send + Sync> Send for Read<'a, T> {}
+unsafe impl<'a, T: Send + Sync> Sync for Read<'a, T> {}
+unsafe impl<'a, T: Send + Sync> Send for Write<'a, T> {}
+unsafe impl<'a, T: Send + Sync> Sync for Write<'a, T> {}
+
+impl<T> RwLock<T> {
+    /// Creates an unlocked lock holding `data`, with the default policy.
+    pub fn new(data: T) -> RwLock<T> {
+        RwLock::with_policy(data, RwLockPolicy::default())
+    }
+
+    /// Creates an unlocked lock holding `data`, with the given policy.
+    pub fn with_policy(data: T, policy: RwLockPolicy) -> RwLock<T> {
+        RwLock {
+            state: std::sync::Mutex::new(State {
+                policy,
+                readers: 0,
+                writer: false,
+                read_waiters: List::new(),
+                write_waiters: List::new(),
+            }),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    pub fn policy(&self) -> RwLockPolicy {
+        self.state.lock().unwrap().policy
+    }
+
+    /// Locks for reading, waiting if a writer holds the lock, or is waiting for it and the
+    /// policy prefers writers.
+    pub fn read(&self) -> Read<'_, T> {
+        Read {
+            lock: self,
+            // Safety: the elem is removed from the lock's read list by Read's drop.
+            registration: unsafe { Registration::new() },
+        }
+    }
+
+    /// Locks for writing, waiting until no reader or writer holds the lock.
+    pub fn write(&self) -> Write<'_, T> {
+        Write {
+            lock: self,
+            // Safety: the elem is removed from the lock's write list by Write's drop.
+            registration: unsafe { Registration::new() },
+        }
+    }
+
+    /// Locks for reading if that can be done without waiting.
+    pub fn try_read(&self) -> Result<RwLockReadGuard<'_, T>, TryLockError> {
+        if self.state.lock().unwrap().try_read() {
+            Ok(RwLockReadGuard { lock: self })
+        } else {
+            Err(TryLockError(()))
+        }
+    }
+
+    /// Locks for writing if that can be done without waiting.
+    pub fn try_write(&self) -> Result<RwLockWriteGuard<'_, T>, TryLockError> {
+        if self.state.lock().unwrap().try_write() {
+            Ok(RwLockWriteGuard { lock: self })
+        } else {
+            Err(TryLockError(()))
+        }
+    }
+}
+
+impl State {
+    fn try_read(&mut self) -> bool {
+        if self.writer || !self.read_waiters.is_empty() {
+            return false;
+        }
+        if self.policy == RwLockPolicy::PreferWriters && !self.write_waiters.is_empty() {
+            return false;
+        }
+        self.readers += 1;
+        true
+    }
+
+    fn try_write(&mut self) -> bool {
+        if self.writer || self.readers > 0 || !self.write_waiters.is_empty() {
+            return false;
+        }
+        self.writer = true;
+        true
+    }
+
+    fn read_unlock(&mut self) {
+        self.readers -= 1;
+        if self.readers == 0 && self.write_waiters.wake_one() {
+            self.writer = true;
+        }
+    }
+
+    fn write_unlock(&mut self) {
+        self.writer = false;
+        if self.policy == RwLockPolicy::PreferWriters && self.write_waiters.wake_one() {
+            self.writer = true;
+            return;
+        }
+        self.readers += self.read_waiters.awake_waiters();
+        if self.readers == 0 && self.write_waiters.wake_one() {
+            self.writer = true;
+        }
+    }
+}
+
+impl<T: Default> Default for RwLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<'a, T> Deref for RwLockReadGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // Safety: the guard holds a read lock.
+        self.lock.data.with(|ptr| unsafe { &*ptr })
+    }
+}
+
+impl<'a, T> Drop for RwLockReadGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.state.lock().unwrap().read_unlock();
+    }
+}
+
+impl<'a, T> Deref for RwLockWriteGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // Safety: the guard holds the write lock.
+        self.lock.data.with(|ptr| unsafe { &*ptr })
+    }
+}
+
+impl<'a, T> DerefMut for RwLockWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // Safety: the guard holds the write lock and is borrowed mutably.
+        self.lock.data.with_mut(|ptr| unsafe { &mut *ptr })
+    }
+}
+
+impl<'a, T> Drop for RwLockWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.state.lock().unwrap().write_unlock();
+    }
+}
+
+impl<'a, T> Future for Read<'a, T> {
+    type Output = RwLockReadGuard<'a, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<RwLockReadGuard<'a, T>> {
+        let lock = self.lock;
+        let registration = &self.registration;
+        let mut state = lock.state.lock().unwrap();
+
+        if !registration.is_registered() {
+            if state.try_read() {
+                return Poll::Ready(RwLockReadGuard { lock });
+            }
+            registration.register(&mut state.read_waiters, cx);
+            return Poll::Pending;
+        }
+
+        if state.read_waiters.is_queued(registration.elem()) {
+            registration.register(&mut state.read_waiters, cx);
+            return Poll::Pending;
+        }
+
+        // Woken by write_unlock, which has already counted this reader.
+        registration.unregister();
+        Poll::Ready(RwLockReadGuard { lock })
+    }
+}
+
+impl<'a, T> Drop for Read<'a, T> {
+    fn drop(&mut self) {
+        if !self.registration.is_registered() {
+            return;
+        }
+        let mut state = self.lock.state.lock().unwrap();
+
+        if state.read_waiters.is_queued(self.registration.elem()) {
+            // Safety: the elem is only ever queued in the lock's read list.
+            unsafe {
+                state.read_waiters.remove_waiter(self.registration.elem());
+            }
+        } else {
+            // Handed a read lock but never took it.
+            state.read_unlock();
+        }
+    }
+}
+
+impl<'a, T> Future for Write<'a, T> {
+    type Output = RwLockWriteGuard<'a, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<RwLockWriteGuard<'a, T>> {
+        let lock = self.lock;
+        let registration = &self.registration;
+        let mut state = lock.state.lock().unwrap();
+
+        if !registration.is_registered() {
+            if state.try_write() {
+                return Poll::Ready(RwLockWriteGuard { lock });
+            }
+            registration.register(&mut state.write_waiters, cx);
+            return Poll::Pending;
+        }
+
+        if state.write_waiters.is_queued(registration.elem()) {
+            registration.register(&mut state.write_waiters, cx);
+            return Poll::Pending;
+        }
+
+        // Woken by an unlock, which has already handed the lock to this writer.
+        registration.unregister();
+        Poll::Ready(RwLockWriteGuard { lock })
+    }
+}
+
+impl<'a, T> Drop for Write<'a, T> {
+    fn drop(&mut self) {
+        if !self.registration.is_registered() {
+            return;
+        }
+        let mut state = self.lock.state.lock().unwrap();
+
+        if state.write_waiters.is_queued(self.registration.elem()) {
+            // Safety: the elem is only ever queued in the lock's write list.
+            unsafe {
+                state.write_waiters.remove_waiter(self.registration.elem());
+            }
+            // Readers held back only by this writer, under PreferWriters, may now go ahead.
+            if !state.writer && state.write_waiters.is_empty() {
+                state.readers += state.read_waiters.awake_waiters();
+            }
+        } else {
+            // Handed the write lock but never took it.
+            state.write_unlock();
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for RwLock<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.try_read() {
+            Ok(guard) => fmt.debug_struct("RwLock").field("data", &&*guard).finish(),
+            Err(_) => fmt.debug_struct("RwLock").field("data", &"<locked>").finish(),
+        }
+    }
+}
+
+impl<'a, T: fmt::Debug> fmt::Debug for RwLockReadGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, fmt)
+    }
+}
+
+impl<'a, T: fmt::Debug> fmt::Debug for RwLockWriteGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, fmt)
+    }
+}
+
+impl<'a, T> fmt::Debug for Read<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Read")
+    }
+}
+
+impl<'a, T> fmt::Debug for Write<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Write")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_test::task;
+
+    #[test]
+    fn readers_share() {
+        let lock = RwLock::new(1);
+        let a = lock.try_read().unwrap();
+        let mut b = task::spawn(lock.read());
+        assert!(b.poll().is_ready());
+        assert_eq!(*a, 1);
+        assert!(lock.try_write().is_err());
+    }
+
+    #[test]
+    fn writer_excludes() {
+        let lock = RwLock::new(1);
+        let mut w = lock.try_write().unwrap();
+        *w += 1;
+
+        let mut r1 = task::spawn(lock.read());
+        let mut r2 = task::spawn(lock.read());
+        let mut w2 = task::spawn(lock.write());
+        assert!(r1.poll().is_pending());
+        assert!(r2.poll().is_pending());
+        assert!(w2.poll().is_pending());
+
+        // The readers all go ahead together; the writer waits for them.
+        drop(w);
+        assert!(r1.is_woken() && r2.is_woken() && !w2.is_woken());
+        let g1 = match r1.poll() {
+            Poll::Ready(guard) => guard,
+            Poll::Pending => panic!("r1 not ready"),
+        };
+        assert_eq!(*g1, 2);
+        drop(g1);
+        // r2 was handed a read lock too, so the writer still waits.
+        assert!(!w2.is_woken());
+        drop(r2);
+        assert!(w2.is_woken());
+        assert!(w2.poll().is_ready());
+    }
+
+    #[test]
+    fn prefer_readers_lets_new_reader_in() {
+        let lock = RwLock::new(());
+        let r = lock.try_read().unwrap();
+        let mut w = task::spawn(lock.write());
+        assert!(w.poll().is_pending());
+
+        // The waiting writer doesn't hold back a new reader.
+        let r2 = lock.try_read().unwrap();
+        drop(r);
+        assert!(!w.is_woken());
+        drop(r2);
+        assert!(w.is_woken());
+        assert!(w.poll().is_ready());
+    }
+
+    #[test]
+    fn prefer_writers_holds_back_new_reader() {
+        let lock = RwLock::with_policy((), RwLockPolicy::PreferWriters);
+        let r = lock.try_read().unwrap();
+        let mut w = task::spawn(lock.write());
+        assert!(w.poll().is_pending());
+
+        // A reader arriving after the writer queues behind it, so the writer isn't starved.
+        let mut r2 = task::spawn(lock.read());
+        assert!(r2.poll().is_pending());
+        assert!(lock.try_read().is_err());
+
+        drop(r);
+        assert!(w.is_woken() && !r2.is_woken());
+        let guard = match w.poll() {
+            Poll::Ready(guard) => guard,
+            Poll::Pending => panic!("w not ready"),
+        };
+        drop(guard);
+        assert!(r2.is_woken());
+        assert!(r2.poll().is_ready());
+    }
+
+    #[test]
+    fn cancelled_writer_releases_held_back_readers() {
+        let lock = RwLock::with_policy((), RwLockPolicy::PreferWriters);
+        let r = lock.try_read().unwrap();
+        let mut w = task::spawn(lock.write());
+        let mut r2 = task::spawn(lock.read());
+        assert!(w.poll().is_pending());
+        assert!(r2.poll().is_pending());
+
+        drop(w);
+        assert!(r2.is_woken());
+        assert!(r2.poll().is_ready());
+        drop(r);
+    }
+
+    #[test]
+    fn cancelled_after_handoff() {
+        let lock = RwLock::new(());
+        let r = lock.try_read().unwrap();
+        let mut w1 = task::spawn(lock.write());
+        let mut w2 = task::spawn(lock.write());
+        assert!(w1.poll().is_pending());
+        assert!(w2.poll().is_pending());
+
+        drop(r);
+        assert!(w1.is_woken());
+        // Handed the lock but dropped before taking it: it goes on to the next writer.
+        drop(w1);
+        assert!(w2.is_woken());
+        assert!(w2.poll().is_ready());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn readers_and_writers() {
+        use std::sync::Arc;
+
+        for policy in [RwLockPolicy::PreferReaders, RwLockPolicy::PreferWriters] {
+            let lock = Arc::new(RwLock::with_policy((0usize, 0usize), policy));
+            let handles: Vec<_> = (0..8)
+                .map(|i| {
+                    let lock = lock.clone();
+                    tokio::spawn(async move {
+                        for _ in 0..50 {
+                            if i % 2 == 0 {
+                                let mut guard = lock.write().await;
+                                guard.0 += 1;
+                                tokio::task::yield_now().await;
+                                guard.1 += 1;
+                            } else {
+                                let guard = lock.read().await;
+                                let before = guard.0;
+                                tokio::task::yield_now().await;
+                                assert_eq!(guard.0, before);
+                                assert_eq!(guard.0, guard.1);
+                            }
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                handle.await.unwrap();
+            }
+            assert_eq!(*lock.read().await, (200, 200));
+        }
+    }
+}
@@ -0,0 +1,389 @@
+//! semaphore - an async counting semaphore
+//!
+//! A `Semaphore` built on the `waiter` module. Each waiter is queued with the number of permits
+//! it wants as its payload, and waiters are served strictly in FIFO order: a waiter wanting more
+//! permits than are available holds back the ones queued behind it, even if they want fewer.
+//!
+//! While any task is waiting, the available permits are held for the oldest waiter. Should that
+//! waiter be cancelled, the permits it had gathered go on to the waiters behind it.
+//!
+//! # Examples
+//!
+//! ```
+//! use broadcast_rs::semaphore::Semaphore;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let semaphore = Semaphore::new(3);
+//!
+//!     let a = semaphore.acquire().await;
+//!     let b = semaphore.acquire_many(2).await;
+//!     assert_eq!(semaphore.available_permits(), 0);
+//!     assert!(semaphore.try_acquire().is_err());
+//!
+//!     drop(a);
+//!     assert_eq!(semaphore.available_permits(), 1);
+//!     drop(b);
+//!     assert_eq!(semaphore.available_permits(), 3);
+//! }
+//! ```
+
+use crate::waiter::{List, Registration};
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+
+/// An async counting semaphore.
+///
+/// See the module documentation.
+pub struct Semaphore {
+    state: Mutex<State>,
+}
+
+struct State {
+    /// Permits not handed out. While `waiters` is not empty, these are fewer than the oldest
+    /// waiter wants and are held for it.
+    permits: usize,
+
+    /// Tasks waiting for permits, each with the number it wants as its payload.
+    waiters: List<usize>,
+}
+
+/// Error returned by [`Semaphore::try_acquire`] and [`Semaphore::try_acquire_many`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TryAcquireError(());
+
+impl fmt::Display for TryAcquireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no permits available")
+    }
+}
+
+impl std::error::Error for TryAcquireError {}
+
+/// Permits acquired from a [`Semaphore`]. The permits are returned when it is dropped.
+#[must_use]
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+    permits: usize,
+}
+
+/// Future returned by [`Semaphore::acquire`] and [`Semaphore::acquire_many`].
+pub struct Acquire<'a> {
+    semaphore: &'a Semaphore,
+
+    /// Permits wanted.
+    permits: usize,
+
+    /// Entry in the semaphore's waiter list, registered while the elem is queued or has been woken
+    /// with the permits not yet taken.
+    registration: Registration<usize>,
+}
+
+unsafe impl<'a> Send for Acquire<'a> {}
+unsafe impl<'a> Sync for Acquire<'a> {}
+
+impl Semaphore {
+    /// Creates a semaphore with the given number of permits.
+    pub fn new(permits: usize) -> Semaphore {
+        Semaphore {
+            state: Mutex::new(State {
+                permits,
+                waiters: List::new(),
+            }),
+        }
+    }
+
+    /// Returns the number of permits available to a new acquire. This is zero while any task
+    /// is waiting.
+    pub fn available_permits(&self) -> usize {
+        let state = self.state.lock().unwrap();
+        if state.waiters.is_empty() {
+            state.permits
+        } else {
+            0
+        }
+    }
+
+    /// Adds `n` permits to the semaphore, waking the waiters they satisfy.
+    pub fn add_permits(&self, n: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.release(n);
+    }
+
+    /// Acquires a single permit, waiting for one if needed.
+    pub fn acquire(&self) -> Acquire<'_> {
+        self.acquire_many(1)
+    }
+
+    /// Acquires `n` permits, waiting for them if needed.
+    ///
+    /// The permits are handed out all at once, in the order the futures were first polled.
+    pub fn acquire_many(&self, n: usize) -> Acquire<'_> {
+        Acquire {
+            semaphore: self,
+            permits: n,
+            // Safety: the elem is removed from the semaphore's list by Acquire's drop.
+            registration: unsafe { Registration::new() },
+        }
+    }
+
+    /// Acquires a single permit if one is available and no task is waiting.
+    pub fn try_acquire(&self) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        self.try_acquire_many(1)
+    }
+
+    /// Acquires `n` permits if they are available and no task is waiting.
+    pub fn try_acquire_many(&self, n: usize) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        let mut state = self.state.lock().unwrap();
+        if state.try_take(n) {
+            Ok(self.permit(n))
+        } else {
+            Err(TryAcquireError(()))
+        }
+    }
+
+    fn permit(&self, permits: usize) -> SemaphorePermit<'_> {
+        SemaphorePermit {
+            semaphore: self,
+            permits,
+        }
+    }
+}
+
+impl State {
+    /// Takes `n` permits if they are available and no task is waiting ahead.
+    fn try_take(&mut self, n: usize) -> bool {
+        if self.waiters.is_empty() && self.permits >= n {
+            self.permits -= n;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns `n` permits and wakes, oldest first, the waiters the permits now cover.
+    fn release(&mut self, n: usize) {
+        self.permits += n;
+        self.grant();
+    }
+
+    /// Hands the available permits to the oldest waiters, for as long as they cover the next
+    /// waiter's want, and wakes those waiters.
+    fn grant(&mut self) {
+        let mut granted = 0;
+        for wanted in self.waiters.iter_payloads() {
+            let wanted = *wanted.unwrap();
+            if wanted > self.permits {
+                break;
+            }
+            self.permits -= wanted;
+            granted += 1;
+        }
+        self.waiters.wake_n(granted);
+    }
+}
+
+impl Default for Semaphore {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl<'a> SemaphorePermit<'a> {
+    /// Returns the number of permits held.
+    pub fn num_permits(&self) -> usize {
+        self.permits
+    }
+
+    /// Forgets the permits without returning them to the semaphore.
+    pub fn forget(mut self) {
+        self.permits = 0;
+    }
+}
+
+impl<'a> Drop for SemaphorePermit<'a> {
+    fn drop(&mut self) {
+        if self.permits > 0 {
+            self.semaphore.add_permits(self.permits);
+        }
+    }
+}
+
+impl<'a> Future for Acquire<'a> {
+    type Output = SemaphorePermit<'a>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<SemaphorePermit<'a>> {
+        let semaphore = self.semaphore;
+        let permits = self.permits;
+        let registration = &self.registration;
+        let mut state = semaphore.state.lock().unwrap();
+
+        if !registration.is_registered() {
+            if state.try_take(permits) {
+                return Poll::Ready(semaphore.permit(permits));
+            }
+            registration.register_with(&mut state.waiters, cx, permits);
+            return Poll::Pending;
+        }
+
+        if state.waiters.is_queued(registration.elem()) {
+            registration.register(&mut state.waiters, cx);
+            return Poll::Pending;
+        }
+
+        // Woken by grant, which has already taken the permits from the count.
+        registration.unregister();
+        Poll::Ready(semaphore.permit(permits))
+    }
+}
+
+impl<'a> Drop for Acquire<'a> {
+    fn drop(&mut self) {
+        if !self.registration.is_registered() {
+            return;
+        }
+        let mut state = self.semaphore.state.lock().unwrap();
+
+        if state.waiters.is_queued(self.registration.elem()) {
+            // Safety: the elem is only ever queued in the semaphore's list.
+            unsafe {
+                state.waiters.remove_waiter(self.registration.elem());
+            }
+            // Permits held for this waiter, if it was the oldest, may now cover the next ones.
+            state.grant();
+        } else {
+            // Granted, but the permits were never taken.
+            state.release(self.permits);
+        }
+    }
+}
+
+impl fmt::Debug for Semaphore {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Semaphore")
+    }
+}
+
+impl<'a> fmt::Debug for SemaphorePermit<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "SemaphorePermit({})", self.permits)
+    }
+}
+
+impl<'a> fmt::Debug for Acquire<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Acquire")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_test::task;
+
+    #[test]
+    fn try_acquire_many() {
+        let semaphore = Semaphore::new(3);
+        let a = semaphore.try_acquire_many(2).unwrap();
+        assert_eq!(a.num_permits(), 2);
+        assert_eq!(semaphore.try_acquire_many(2).unwrap_err(), TryAcquireError(()));
+        let b = semaphore.try_acquire().unwrap();
+        assert_eq!(semaphore.available_permits(), 0);
+        drop(a);
+        drop(b);
+        assert_eq!(semaphore.available_permits(), 3);
+    }
+
+    #[test]
+    fn fifo_fairness() {
+        let semaphore = Semaphore::new(0);
+        let mut a = task::spawn(semaphore.acquire_many(2));
+        let mut b = task::spawn(semaphore.acquire());
+        assert!(a.poll().is_pending());
+        assert!(b.poll().is_pending());
+
+        // One permit is not enough for a, and b, behind it, must not jump the queue.
+        semaphore.add_permits(1);
+        assert!(!a.is_woken() && !b.is_woken());
+        assert_eq!(semaphore.available_permits(), 0);
+        assert!(semaphore.try_acquire().is_err());
+
+        semaphore.add_permits(1);
+        assert!(a.is_woken() && !b.is_woken());
+        let permit = match a.poll() {
+            Poll::Ready(permit) => permit,
+            Poll::Pending => panic!("a not ready"),
+        };
+        assert_eq!(permit.num_permits(), 2);
+
+        drop(permit);
+        assert!(b.is_woken());
+        assert!(b.poll().is_ready());
+        assert_eq!(semaphore.available_permits(), 2);
+    }
+
+    #[test]
+    fn cancel_partially_granted() {
+        let semaphore = Semaphore::new(1);
+        let mut a = task::spawn(semaphore.acquire_many(3));
+        let mut b = task::spawn(semaphore.acquire());
+        assert!(a.poll().is_pending());
+        assert!(b.poll().is_pending());
+
+        // The permit is held for a; dropping a passes it on to b.
+        drop(a);
+        assert!(b.is_woken());
+        assert!(b.poll().is_ready());
+        assert_eq!(semaphore.available_permits(), 1);
+    }
+
+    #[test]
+    fn cancel_after_grant() {
+        let semaphore = Semaphore::new(0);
+        let mut a = task::spawn(semaphore.acquire_many(2));
+        let mut b = task::spawn(semaphore.acquire_many(2));
+        assert!(a.poll().is_pending());
+        assert!(b.poll().is_pending());
+
+        semaphore.add_permits(2);
+        assert!(a.is_woken() && !b.is_woken());
+
+        // a was granted its permits but is dropped before taking them.
+        drop(a);
+        assert!(b.is_woken());
+        assert!(b.poll().is_ready());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn contention() {
+        use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+        use std::sync::Arc;
+
+        let semaphore = Arc::new(Semaphore::new(3));
+        let active = Arc::new(AtomicUsize::new(0));
+        let handles: Vec<_> = (0..16)
+            .map(|i| {
+                let semaphore = semaphore.clone();
+                let active = active.clone();
+                tokio::spawn(async move {
+                    for _ in 0..50 {
+                        let n = 1 + i % 3;
+                        let _permit = semaphore.acquire_many(n).await;
+                        let now = active.fetch_add(n, SeqCst) + n;
+                        assert!(now <= 3);
+                        tokio::task::yield_now().await;
+                        active.fetch_sub(n, SeqCst);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert_eq!(semaphore.available_permits(), 3);
+    }
+}
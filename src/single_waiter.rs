@@ -0,0 +1,240 @@
+//! single_waiter - a slot for the waker of the one task waiting on a resource
+//!
+//! For resources that at most one task ever waits on, a oneshot's receiver or a single consumer's
+//! notification, a whole `waiter::List` is more than needed. A `SingleWaiter` holds a single
+//! waker inline, like the `AtomicWaker` of the futures crate: the waiting task calls `register`
+//! each time it is polled, and whoever makes the resource ready calls `wake`.
+//!
+//! As with the list, nothing is allocated: the waker is stored in the `SingleWaiter` itself, and
+//! a task registering again with the same waker keeps the one already stored rather than cloning
+//! it.
+//!
+//! A wake with no waker registered is not remembered. As with `AtomicWaker`, the task must check
+//! the resource after registering, so that a wake coming between its check and its register is
+//! not lost.
+//!
+//! `register` and `wake` may race from different threads. Only one task should register at a
+//! time; if two do, one of the registrations is dropped.
+//!
+//! # Examples
+//!
+//! ```
+//! use broadcast_rs::single_waiter::SingleWaiter;
+//! use std::future::poll_fn;
+//! use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+//! use std::sync::Arc;
+//! use std::task::Poll;
+//!
+//! struct Flag {
+//!     set: AtomicBool,
+//!     waiter: SingleWaiter,
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let flag = Arc::new(Flag { set: AtomicBool::new(false), waiter: SingleWaiter::new() });
+//!     let flag2 = flag.clone();
+//!
+//!     tokio::spawn(async move {
+//!         flag2.set.store(true, SeqCst);
+//!         flag2.waiter.wake();
+//!     });
+//!
+//!     poll_fn(|cx| {
+//!         flag.waiter.register(cx);
+//!         if flag.set.load(SeqCst) {
+//!             Poll::Ready(())
+//!         } else {
+//!             Poll::Pending
+//!         }
+//!     })
+//!     .await;
+//! }
+//! ```
+
+use crate::waiter::refresh_waker;
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering::*};
+use core::task::{Context, Waker};
+
+/// Neither registering nor waking.
+const IDLE: usize = 0;
+/// A task is storing its waker.
+const REGISTERING: usize = 0b01;
+/// The stored waker is being taken to be woken.
+const WAKING: usize = 0b10;
+
+/// Holds the waker of the single task waiting on a resource.
+///
+/// See the module documentation.
+pub struct SingleWaiter {
+    state: AtomicUsize,
+    waker: UnsafeCell<Option<Waker>>,
+}
+
+// Safety: the waker is only accessed by whoever moved the state out of IDLE.
+unsafe impl Send for SingleWaiter {}
+unsafe impl Sync for SingleWaiter {}
+
+impl SingleWaiter {
+    pub const fn new() -> SingleWaiter {
+        SingleWaiter {
+            state: AtomicUsize::new(IDLE),
+            waker: UnsafeCell::new(None),
+        }
+    }
+
+    /// Stores the waker from `cx`, to be woken by the next `wake`, replacing any waker from an
+    /// earlier call unless it wakes the same task.
+    ///
+    /// If a `wake` runs at the same time, the task is woken instead, so that it polls again.
+    pub fn register(&self, cx: &mut Context<'_>) {
+        match self.state.compare_exchange(IDLE, REGISTERING, Acquire, Acquire) {
+            Ok(_) => {
+                // Safety: the state is REGISTERING, so nothing else touches the waker.
+                unsafe { refresh_waker(&mut *self.waker.get(), cx.waker()) };
+
+                if let Err(actual) = self.state.compare_exchange(REGISTERING, IDLE, AcqRel, Acquire)
+                {
+                    // A wake came in while the waker was stored, and left it for us to wake.
+                    debug_assert_eq!(actual, REGISTERING | WAKING);
+                    // Safety: the state is still REGISTERING, with WAKING set.
+                    let waker = unsafe { (*self.waker.get()).take() };
+                    self.state.swap(IDLE, AcqRel);
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                }
+            }
+            Err(WAKING) => {
+                // The stored waker is being woken; it may not be this task's, so wake this one
+                // too.
+                cx.waker().wake_by_ref();
+            }
+            Err(_) => {
+                // Another task is registering at the same time; only one registration is kept.
+            }
+        }
+    }
+
+    /// Wakes the registered task, if any, taking its waker out. A later `wake` does nothing
+    /// until the task registers again.
+    pub fn wake(&self) {
+        if let Some(waker) = self.take() {
+            waker.wake();
+        }
+    }
+
+    /// Takes the registered waker out without waking it.
+    pub fn take(&self) -> Option<Waker> {
+        match self.state.fetch_or(WAKING, AcqRel) {
+            IDLE => {
+                // Safety: the state is WAKING, so nothing else touches the waker.
+                let waker = unsafe { (*self.waker.get()).take() };
+                self.state.fetch_and(!WAKING, Release);
+                waker
+            }
+            // A register running now sees WAKING and wakes its own waker. A wake running now
+            // takes the waker itself.
+            _ => None,
+        }
+    }
+}
+
+impl Default for SingleWaiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for SingleWaiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SingleWaiter")
+            .field("state", &self.state.load(Relaxed))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::CountingWaker;
+
+    #[test]
+    fn register_then_wake() {
+        let waiter = SingleWaiter::new();
+        let counter = CountingWaker::new();
+        let waker = counter.waker();
+
+        waiter.register(&mut Context::from_waker(&waker));
+        assert_eq!(counter.waker_count(), 2);
+        waiter.wake();
+        assert_eq!(counter.wake_count(), 1);
+        assert_eq!(counter.waker_count(), 1);
+
+        // The waker was taken out, so a second wake has nothing to wake.
+        waiter.wake();
+        assert_eq!(counter.wake_count(), 1);
+    }
+
+    #[test]
+    fn wake_then_register() {
+        let waiter = SingleWaiter::new();
+        let counter = CountingWaker::new();
+        let waker = counter.waker();
+
+        // Nothing is registered, and the wake is not remembered.
+        waiter.wake();
+        waiter.register(&mut Context::from_waker(&waker));
+        assert_eq!(counter.wake_count(), 0);
+
+        waiter.wake();
+        assert_eq!(counter.wake_count(), 1);
+    }
+
+    #[test]
+    fn register_coalesces_wakers() {
+        let waiter = SingleWaiter::new();
+        let first = CountingWaker::new();
+        let waker = first.waker();
+
+        for _ in 0..3 {
+            waiter.register(&mut Context::from_waker(&waker));
+        }
+        assert_eq!(first.waker_count(), 2);
+
+        // A different task replaces the stored waker, and is the one woken.
+        let second = CountingWaker::new();
+        waiter.register(&mut Context::from_waker(&second.waker()));
+        assert_eq!(first.waker_count(), 1);
+        waiter.wake();
+        assert_eq!(first.wake_count(), 0);
+        assert_eq!(second.wake_count(), 1);
+    }
+
+    #[test]
+    fn register_during_wake_wakes_registering_task() {
+        let waiter = SingleWaiter::new();
+        let counter = CountingWaker::new();
+        let waker = counter.waker();
+
+        // Stand in for a wake on another thread, caught between taking and releasing the slot.
+        waiter.state.store(WAKING, SeqCst);
+        waiter.register(&mut Context::from_waker(&waker));
+        assert_eq!(counter.wake_count(), 1);
+        assert_eq!(counter.waker_count(), 1);
+    }
+
+    #[test]
+    fn wake_from_another_thread() {
+        let waiter = std::sync::Arc::new(SingleWaiter::new());
+        let counter = CountingWaker::new();
+        waiter.register(&mut Context::from_waker(&counter.waker()));
+
+        let waiter2 = waiter.clone();
+        std::thread::spawn(move || waiter2.wake()).join().unwrap();
+        assert_eq!(counter.wake_count(), 1);
+    }
+}
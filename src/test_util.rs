@@ -0,0 +1,264 @@
+//! test_util - helpers for testing futures built on the waiter list
+//!
+//! Only compiled with the `test-util` feature. It needs `alloc`, not `std`.
+//!
+//! A `CountingWaker` hands out real wakers and counts how many times they are woken, so a test
+//! can assert exactly how many wakes an operation on the list caused.
+//!
+//! With the `futures` feature, the [`ArcWake`] trait builds a waker from any `Arc`, for tests on
+//! executors that construct their wakers that way rather than through tokio.
+//!
+//! # Examples
+//!
+//! ```
+//! use broadcast_rs::test_util::CountingWaker;
+//! use broadcast_rs::waiter::{Elem, List};
+//! use std::pin::pin;
+//! use std::task::Context;
+//!
+//! let counter = CountingWaker::new();
+//! let waker = counter.waker();
+//! let mut cx = Context::from_waker(&waker);
+//!
+//! let mut list: List = List::new();
+//! // Safety: the elem is woken out of the list before it is dropped.
+//! let elem = pin!(unsafe { Elem::new() });
+//! list.enqueue_waiter(&elem, &mut cx);
+//!
+//! assert_eq!(counter.wake_count(), 0);
+//! assert_eq!(list.awake_waiters(), 1);
+//! assert_eq!(counter.wake_count(), 1);
+//! ```
+
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use core::task::Waker;
+
+/// Hands out wakers that count the times they are woken.
+///
+/// All the wakers from one `CountingWaker`, and their clones, share the one count.
+pub struct CountingWaker {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    wakes: AtomicUsize,
+}
+
+impl CountingWaker {
+    pub fn new() -> CountingWaker {
+        CountingWaker {
+            inner: Arc::new(Inner {
+                wakes: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Returns a waker that adds to the count each time it is woken, by value or by reference.
+    pub fn waker(&self) -> Waker {
+        Waker::from(self.inner.clone())
+    }
+
+    /// Returns the number of times the wakers have been woken.
+    pub fn wake_count(&self) -> usize {
+        self.inner.wakes.load(SeqCst)
+    }
+
+    /// Returns the number of wakers, including clones, that are still alive. A list that has
+    /// dropped the wakers it stored no longer adds to this.
+    pub fn waker_count(&self) -> usize {
+        Arc::strong_count(&self.inner) - 1
+    }
+}
+
+impl Default for CountingWaker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Wake for Inner {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.wakes.fetch_add(1, SeqCst);
+    }
+}
+
+impl fmt::Debug for CountingWaker {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("CountingWaker")
+            .field("wake_count", &self.wake_count())
+            .field("waker_count", &self.waker_count())
+            .finish()
+    }
+}
+
+/// A task that can be woken through an `Arc` to itself.
+///
+/// The same shape as the `ArcWake` of the futures-task crate, so a wake type written for an
+/// executor built on it carries over: implement `wake_by_ref`, then pass the `Arc` to [`waker`].
+///
+/// # Examples
+///
+/// Driving a future queued on a waiter list by hand, with a waker built from an `ArcWake`:
+///
+/// ```
+/// use broadcast_rs::test_util::{waker, ArcWake};
+/// use broadcast_rs::waiter::{Elem, List};
+/// use std::cell::{Cell, RefCell};
+/// use std::future::Future;
+/// use std::pin::{pin, Pin};
+/// use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+/// use std::sync::Arc;
+/// use std::task::{Context, Poll};
+///
+/// struct Flag(AtomicBool);
+///
+/// impl ArcWake for Flag {
+///     fn wake_by_ref(arc_self: &Arc<Self>) {
+///         arc_self.0.store(true, SeqCst);
+///     }
+/// }
+///
+/// /// Pending until woken out of the list.
+/// struct Bar<'a> {
+///     list: &'a RefCell<List>,
+///     polled: Cell<bool>,
+///     elem: Elem,
+/// }
+///
+/// impl Future for Bar<'_> {
+///     type Output = ();
+///
+///     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+///         let mut list = self.list.borrow_mut();
+///         if self.polled.replace(true) && !list.is_queued(&self.elem) {
+///             return Poll::Ready(());
+///         }
+///         list.enqueue_waiter(&self.elem, cx);
+///         Poll::Pending
+///     }
+/// }
+///
+/// impl Drop for Bar<'_> {
+///     fn drop(&mut self) {
+///         // Safety: the elem is removed from the one list it is queued on.
+///         unsafe { self.list.borrow_mut().remove_waiter(&self.elem) };
+///     }
+/// }
+///
+/// let list = RefCell::new(List::new());
+/// // Safety: Bar's drop removes the elem from the list.
+/// let mut bar = pin!(Bar { list: &list, polled: Cell::new(false), elem: unsafe { Elem::new() } });
+///
+/// let flag = Arc::new(Flag(AtomicBool::new(false)));
+/// let waker = waker(flag.clone());
+/// let mut cx = Context::from_waker(&waker);
+///
+/// assert!(bar.as_mut().poll(&mut cx).is_pending());
+/// list.borrow_mut().awake_waiters();
+/// assert!(flag.0.load(SeqCst));
+/// assert!(bar.as_mut().poll(&mut cx).is_ready());
+/// ```
+#[cfg(any(test, feature = "futures"))]
+pub trait ArcWake: Send + Sync {
+    /// Wakes the task, consuming the `Arc`. By default, wakes by reference.
+    fn wake(self: Arc<Self>) {
+        Self::wake_by_ref(&self)
+    }
+
+    /// Wakes the task without consuming the `Arc`.
+    fn wake_by_ref(arc_self: &Arc<Self>);
+}
+
+/// Returns a waker that wakes `task` through its `ArcWake` implementation.
+#[cfg(any(test, feature = "futures"))]
+pub fn waker<W: ArcWake + 'static>(task: Arc<W>) -> Waker {
+    Waker::from(Arc::new(ArcWaker(task)))
+}
+
+/// Adapts an `ArcWake` to `Wake`, which can't be implemented for it directly.
+#[cfg(any(test, feature = "futures"))]
+struct ArcWaker<W>(Arc<W>);
+
+#[cfg(any(test, feature = "futures"))]
+impl<W: ArcWake> Wake for ArcWaker<W> {
+    fn wake(self: Arc<Self>) {
+        match Arc::try_unwrap(self) {
+            Ok(ArcWaker(task)) => W::wake(task),
+            Err(this) => W::wake_by_ref(&this.0),
+        }
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        W::wake_by_ref(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_wakes_and_wakers() {
+        let counter = CountingWaker::new();
+        assert_eq!(counter.waker_count(), 0);
+
+        let waker = counter.waker();
+        let clone = waker.clone();
+        assert_eq!(counter.waker_count(), 2);
+
+        waker.wake_by_ref();
+        assert_eq!(counter.wake_count(), 1);
+        clone.wake();
+        assert_eq!(counter.wake_count(), 2);
+        assert_eq!(counter.waker_count(), 1);
+
+        drop(waker);
+        assert_eq!(counter.waker_count(), 0);
+        assert_eq!(counter.wake_count(), 2);
+    }
+
+    #[test]
+    fn arc_wake_waker() {
+        struct Task {
+            wakes: AtomicUsize,
+            by_value: AtomicUsize,
+        }
+
+        impl ArcWake for Task {
+            fn wake(self: Arc<Self>) {
+                self.by_value.fetch_add(1, SeqCst);
+                Self::wake_by_ref(&self);
+            }
+
+            fn wake_by_ref(arc_self: &Arc<Self>) {
+                arc_self.wakes.fetch_add(1, SeqCst);
+            }
+        }
+
+        let task = Arc::new(Task {
+            wakes: AtomicUsize::new(0),
+            by_value: AtomicUsize::new(0),
+        });
+        let waker = waker(task.clone());
+        let clone = waker.clone();
+
+        waker.wake_by_ref();
+        assert_eq!(task.wakes.load(SeqCst), 1);
+        clone.wake();
+        assert_eq!(task.wakes.load(SeqCst), 2);
+        assert_eq!(task.by_value.load(SeqCst), 0);
+
+        // The last waker wakes by value, handing over the task's `Arc`.
+        waker.wake();
+        assert_eq!(task.wakes.load(SeqCst), 3);
+        assert_eq!(task.by_value.load(SeqCst), 1);
+        assert_eq!(Arc::strong_count(&task), 1);
+    }
+}
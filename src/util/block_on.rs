@@ -0,0 +1,45 @@
+//! Runs a future to completion on the current thread, for the blocking methods that let
+//! synchronous code use the channels.
+
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+/// Wakes a thread parked in `block_on`.
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Polls `future` until it is ready, parking the thread while it is pending.
+///
+/// With the `tokio-guard` feature, a thread that has a tokio runtime entered hands the future to
+/// the runtime's `Handle::block_on` instead, which panics if the thread is driving async tasks,
+/// as tokio's own blocking calls do, and otherwise, as in a `spawn_blocking` closure, runs it.
+/// Without the feature, nothing tells an executor thread from any other, and it is parked too.
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    #[cfg(feature = "tokio-guard")]
+    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+        return handle.block_on(future);
+    }
+
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        // A wake that came before the park leaves the thread's token set, so it isn't lost.
+        thread::park();
+    }
+}
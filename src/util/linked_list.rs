@@ -23,6 +23,9 @@ pub struct LinkedList<L, T> {
     /// Linked list tail
     tail: Option<NonNull<T>>,
 
+    /// Number of nodes in the list.
+    len: usize,
+
     /// Node type marker.
     _marker: PhantomData<*const L>,
 }
@@ -40,6 +43,19 @@ unsafe impl<L: Link> Sync for LinkedList<L, L::Target> where L::Target: Sync {}
 /// Implementations must guarantee that `Target` types are pinned in memory. In
 /// other words, when a node is inserted, the value will not be moved as long as
 /// it is stored in the list.
+///
+/// In addition, implementations outside this crate must ensure:
+///
+/// - `from_raw` turns the pointer returned by `as_raw` back into the same
+///   handle, so a handle given to the list is the one handed back out.
+/// - `pointers` always returns the same `Pointers` field of the given node,
+///   one that is used by no other list. A node with one `Pointers` field can
+///   be in at most one list at a time.
+/// - The node is not dropped while it is in a list. The list does not empty
+///   itself on drop, so nodes must be removed before it or they go away.
+///
+/// The `generate_addr_of_methods!` macro generates a suitable accessor for
+/// `pointers`.
 pub unsafe trait Link {
     /// Handle to the list entry.
     ///
@@ -120,6 +136,7 @@ impl<L, T> LinkedList<L, T> {
         LinkedList {
             head: None,
             tail: None,
+            len: 0,
             _marker: PhantomData,
         }
     }
@@ -146,6 +163,78 @@ impl<L: Link> LinkedList<L, L::Target> {
                 self.tail = Some(ptr);
             }
         }
+        self.len += 1;
+    }
+
+    /// Adds an element last in the list.
+    pub fn push_back(&mut self, val: L::Handle) {
+        // The value should not be dropped, it is being inserted into the list
+        let val = ManuallyDrop::new(val);
+        let ptr = L::as_raw(&val);
+        assert_ne!(self.tail, Some(ptr));
+        unsafe {
+            L::pointers(ptr).as_mut().set_prev(self.tail);
+            L::pointers(ptr).as_mut().set_next(None);
+
+            if let Some(tail) = self.tail {
+                L::pointers(tail).as_mut().set_next(Some(ptr));
+            }
+
+            self.tail = Some(ptr);
+
+            if self.head.is_none() {
+                self.head = Some(ptr);
+            }
+        }
+        self.len += 1;
+    }
+
+    /// Inserts `val` right after `node`, on the side of `node` toward the
+    /// back of the list.
+    ///
+    /// # Safety
+    ///
+    /// The caller **must** ensure that `node` is currently contained by
+    /// `self`.
+    pub unsafe fn insert_after(&mut self, node: NonNull<L::Target>, val: L::Handle) {
+        let val = ManuallyDrop::new(val);
+        let ptr = L::as_raw(&val);
+        assert_ne!(node, ptr);
+
+        let next = L::pointers(node).as_ref().get_next();
+        L::pointers(ptr).as_mut().set_prev(Some(node));
+        L::pointers(ptr).as_mut().set_next(next);
+        L::pointers(node).as_mut().set_next(Some(ptr));
+
+        match next {
+            Some(next) => L::pointers(next).as_mut().set_prev(Some(ptr)),
+            None => self.tail = Some(ptr),
+        }
+        self.len += 1;
+    }
+
+    /// Inserts `val` right before `node`, on the side of `node` toward the
+    /// front of the list.
+    ///
+    /// # Safety
+    ///
+    /// The caller **must** ensure that `node` is currently contained by
+    /// `self`.
+    pub unsafe fn insert_before(&mut self, node: NonNull<L::Target>, val: L::Handle) {
+        let val = ManuallyDrop::new(val);
+        let ptr = L::as_raw(&val);
+        assert_ne!(node, ptr);
+
+        let prev = L::pointers(node).as_ref().get_prev();
+        L::pointers(ptr).as_mut().set_next(Some(node));
+        L::pointers(ptr).as_mut().set_prev(prev);
+        L::pointers(node).as_mut().set_prev(Some(ptr));
+
+        match prev {
+            Some(prev) => L::pointers(prev).as_mut().set_next(Some(ptr)),
+            None => self.head = Some(ptr),
+        }
+        self.len += 1;
     }
 
     /// Removes the last element from a list and returns it, or None if it is
@@ -164,10 +253,32 @@ impl<L: Link> LinkedList<L, L::Target> {
             L::pointers(last).as_mut().set_prev(None);
             L::pointers(last).as_mut().set_next(None);
 
+            self.len -= 1;
             Some(L::from_raw(last))
         }
     }
 
+    /// Removes the first element from a list and returns it, or None if it is
+    /// empty.
+    pub fn pop_front(&mut self) -> Option<L::Handle> {
+        unsafe {
+            let first = self.head?;
+            self.head = L::pointers(first).as_ref().get_next();
+
+            if let Some(next) = L::pointers(first).as_ref().get_next() {
+                L::pointers(next).as_mut().set_prev(None);
+            } else {
+                self.tail = None
+            }
+
+            L::pointers(first).as_mut().set_prev(None);
+            L::pointers(first).as_mut().set_next(None);
+
+            self.len -= 1;
+            Some(L::from_raw(first))
+        }
+    }
+
     /// Returns whether the linked list does not contain any node
     pub fn is_empty(&self) -> bool {
         if self.head.is_some() {
@@ -175,6 +286,7 @@ impl<L: Link> LinkedList<L, L::Target> {
         }
 
         assert!(self.tail.is_none());
+        debug_assert_eq!(self.len, 0);
         true
     }
 
@@ -215,28 +327,126 @@ impl<L: Link> LinkedList<L, L::Target> {
         L::pointers(node).as_mut().set_next(None);
         L::pointers(node).as_mut().set_prev(None);
 
+        self.len -= 1;
         Some(L::from_raw(node))
     }
 
-    /// Returns the length of the linked list, counting forwards.
+    /// Splits the list in two at `at`, counting from the front. The first `at` nodes stay in
+    /// `self`; the rest are moved, in order, to the returned list.
     ///
-    /// This is an O(n) operation.
-    /// Primarily for debugging and unit tests in other modules.
-    pub fn len(&self) -> usize {
-        let mut n: usize = 0;
-        let mut next = self.head;
-        while let Some(ptr) = next {
+    /// No node moves in memory, only the pointers at the seam change. Finding the seam is
+    /// O(at).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at` is greater than the length of the list.
+    pub fn split_off(&mut self, at: usize) -> LinkedList<L, L::Target> {
+        if at == 0 {
+            return core::mem::take(self);
+        }
+
+        // The last node to stay.
+        let mut last = self.head;
+        for _ in 1..at {
+            // Safety: the node is in self, which is held mutably.
+            last = last.and_then(|node| unsafe { L::pointers(node).as_ref().get_next() });
+        }
+        let last = last.expect("split_off index out of bounds");
+
+        // Safety: the nodes are in self, which is held mutably.
+        unsafe {
+            let first = match L::pointers(last).as_ref().get_next() {
+                Some(first) => first,
+                None => return LinkedList::new(),
+            };
+            L::pointers(last).as_mut().set_next(None);
+            L::pointers(first).as_mut().set_prev(None);
+
+            let back = LinkedList {
+                head: Some(first),
+                tail: self.tail,
+                len: self.len - at,
+                _marker: PhantomData,
+            };
+            self.tail = Some(last);
+            self.len = at;
+            back
+        }
+    }
+
+    /// Moves all the nodes of `other` onto the back of `self`, keeping their order, and leaves
+    /// `other` empty. This is O(1); only the pointers at the seam change.
+    pub fn append(&mut self, other: &mut LinkedList<L, L::Target>) {
+        let (first, last) = match (other.head.take(), other.tail.take()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return,
+        };
+
+        match self.tail {
+            // Safety: `tail` is in self and `first` was in other, both held mutably.
+            Some(tail) => unsafe {
+                L::pointers(tail).as_mut().set_next(Some(first));
+                L::pointers(first).as_mut().set_prev(Some(tail));
+            },
+            None => self.head = Some(first),
+        }
+        self.tail = Some(last);
+        self.len += core::mem::take(&mut other.len);
+    }
+
+    /// Reverses the order of the nodes, swapping every node's `prev` and `next` pointers and the
+    /// list's head and tail. This is O(n) and allocates nothing.
+    pub fn reverse(&mut self) {
+        let mut curr = self.head;
+        while let Some(node) = curr {
+            // Safety: the node is in the list, which is held mutably.
             unsafe {
-                next = L::pointers(ptr).as_ref().get_next();
+                let mut pointers = L::pointers(node);
+                let pointers = pointers.as_mut();
+                let (prev, next) = (pointers.get_prev(), pointers.get_next());
+                pointers.set_prev(next);
+                pointers.set_next(prev);
+                curr = next;
             }
-            n += 1;
         }
-        n
+        core::mem::swap(&mut self.head, &mut self.tail);
+    }
+
+    /// Moves `node` from `self` to the front of `other`, as `remove` then `push_front` would.
+    ///
+    /// Holding both lists mutably for the whole move is what makes relocating a node sound; the
+    /// node is never reachable from both lists, nor left out of both where another holder could
+    /// see it. Returns false, moving nothing, if `remove` can tell the node is not in `self`.
+    ///
+    /// # Safety
+    ///
+    /// The same as for `remove`: the caller **must** ensure that `node` is currently contained
+    /// by `self` or not contained by any other list.
+    pub unsafe fn transfer(
+        &mut self,
+        other: &mut LinkedList<L, L::Target>,
+        node: NonNull<L::Target>,
+    ) -> bool {
+        match self.remove(node) {
+            Some(handle) => {
+                other.push_front(handle);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the number of nodes in the list.
+    ///
+    /// The count is kept as nodes are added and removed, so this is O(1).
+    pub const fn len(&self) -> usize {
+        self.len
     }
 
     /// Returns the length of the linked list, counting backwards.
     ///
-    /// This is an O(n) operation.
+    /// This is an O(n) operation. It doesn't use the count `len` returns, so comparing the two
+    /// checks the count and the pointers agree.
     /// Primarily for debugging and unit tests in other modules.
     pub fn len_backwards(&self) -> usize {
         let mut n: usize = 0;
@@ -249,6 +459,25 @@ impl<L: Link> LinkedList<L, L::Target> {
         }
         n
     }
+
+    /// Returns whether `node` is one of the nodes in the list.
+    ///
+    /// The list is walked comparing node addresses, so the answer doesn't depend on any flag
+    /// kept by the node itself. This is an O(n) operation, only compiled with debug assertions
+    /// or the `validate` feature, for checking that a node really left the list.
+    #[cfg(any(debug_assertions, feature = "validate"))]
+    pub fn contains(&self, node: NonNull<L::Target>) -> bool {
+        let mut next = self.head;
+        while let Some(ptr) = next {
+            if ptr == node {
+                return true;
+            }
+            unsafe {
+                next = L::pointers(ptr).as_ref().get_next();
+            }
+        }
+        false
+    }
 }
 
 impl<L: Link> fmt::Debug for LinkedList<L, L::Target> {
@@ -256,22 +485,48 @@ impl<L: Link> fmt::Debug for LinkedList<L, L::Target> {
         f.debug_struct("LinkedList")
             .field("head", &self.head)
             .field("tail", &self.tail)
+            .field("len", &self.len)
             .finish()
     }
 }
 
-#[cfg(any(
-    feature = "fs",
-    feature = "rt",
-    all(unix, feature = "process"),
-    feature = "signal",
-    feature = "sync",
-))]
 impl<L: Link> LinkedList<L, L::Target> {
+    /// Returns a reference to the first element, the one `pop_front` would return next.
+    pub fn first(&self) -> Option<&L::Target> {
+        let head = self.head.as_ref()?;
+        unsafe { Some(&*head.as_ptr()) }
+    }
+
+    /// Returns a reference to the last element, the one `pop_back` would return next.
     pub fn last(&self) -> Option<&L::Target> {
         let tail = self.tail.as_ref()?;
         unsafe { Some(&*tail.as_ptr()) }
     }
+
+    /// Removes every node for which `f` returns true, walking from the front, and returns the
+    /// number removed. The handles of the removed nodes are dropped.
+    ///
+    /// The next node is found before `f` is called on a node, and a node is unlinked only once
+    /// `f` has returned, so if `f` panics the list is left valid, holding every node not yet
+    /// removed.
+    pub fn remove_matching<F>(&mut self, mut f: F) -> usize
+    where
+        F: FnMut(&L::Target) -> bool,
+    {
+        let mut removed = 0;
+        let mut next = self.head;
+        while let Some(node) = next {
+            // Safety: the node is in self, which is held mutably.
+            unsafe {
+                next = L::pointers(node).as_ref().get_next();
+                if f(node.as_ref()) {
+                    drop(self.remove(node));
+                    removed += 1;
+                }
+            }
+        }
+        removed
+    }
 }
 
 impl<L: Link> Default for LinkedList<L, L::Target> {
@@ -280,6 +535,140 @@ impl<L: Link> Default for LinkedList<L, L::Target> {
     }
 }
 
+// ===== impl Drain =====
+
+/// Iterator returned by [`LinkedList::drain`].
+pub struct Drain<'a, L: Link> {
+    list: &'a mut LinkedList<L, L::Target>,
+}
+
+impl<L: Link> LinkedList<L, L::Target> {
+    /// Returns an iterator that removes the nodes from the back of the list, the order
+    /// `pop_back` would return them in, as it is advanced.
+    ///
+    /// Dropping the iterator removes the nodes not yet returned, so the list is empty afterward
+    /// whether or not the iterator was run to the end.
+    pub fn drain(&mut self) -> Drain<'_, L> {
+        Drain { list: self }
+    }
+}
+
+impl<'a, L: Link> Iterator for Drain<'a, L> {
+    type Item = L::Handle;
+
+    fn next(&mut self) -> Option<L::Handle> {
+        self.list.pop_back()
+    }
+}
+
+impl<'a, L: Link> Drop for Drain<'a, L> {
+    fn drop(&mut self) {
+        while self.list.pop_back().is_some() {}
+    }
+}
+
+// ===== impl Cursor =====
+
+/// A cursor over a list, pointing at one of its nodes or at a "ghost" position past both ends.
+///
+/// Starts at the front of the list. Moving next goes toward the back; moving next from the back
+/// reaches the ghost, and moving next from the ghost reaches the front again. Moving prev goes
+/// the other way.
+pub struct Cursor<'a, L: Link> {
+    list: &'a LinkedList<L, L::Target>,
+    curr: Option<NonNull<L::Target>>,
+}
+
+/// A cursor, as [`Cursor`], that can also remove the node it points at.
+pub struct CursorMut<'a, L: Link> {
+    list: &'a mut LinkedList<L, L::Target>,
+    curr: Option<NonNull<L::Target>>,
+}
+
+impl<L: Link> LinkedList<L, L::Target> {
+    /// Returns a cursor at the front of the list.
+    pub fn cursor(&self) -> Cursor<'_, L> {
+        Cursor {
+            curr: self.head,
+            list: self,
+        }
+    }
+
+    /// Returns a cursor at the front of the list that can remove nodes.
+    ///
+    /// The list is borrowed mutably for as long as the cursor lives, which is the same contract
+    /// the other mutating calls have.
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, L> {
+        CursorMut {
+            curr: self.head,
+            list: self,
+        }
+    }
+}
+
+impl<'a, L: Link> Cursor<'a, L> {
+    /// Returns the node the cursor points at, or None at the ghost position.
+    pub fn current(&self) -> Option<&'a L::Target> {
+        // Safety: the list is borrowed, so the node is still in it.
+        self.curr.map(|curr| unsafe { &*curr.as_ptr() })
+    }
+
+    /// Moves the cursor toward the back of the list.
+    pub fn move_next(&mut self) {
+        self.curr = match self.curr {
+            // Safety: the list is borrowed, so the node is still in it.
+            Some(curr) => unsafe { L::pointers(curr).as_ref().get_next() },
+            None => self.list.head,
+        };
+    }
+
+    /// Moves the cursor toward the front of the list.
+    pub fn move_prev(&mut self) {
+        self.curr = match self.curr {
+            // Safety: the list is borrowed, so the node is still in it.
+            Some(curr) => unsafe { L::pointers(curr).as_ref().get_prev() },
+            None => self.list.tail,
+        };
+    }
+}
+
+impl<'a, L: Link> CursorMut<'a, L> {
+    /// Returns the node the cursor points at, or None at the ghost position.
+    pub fn current(&self) -> Option<&L::Target> {
+        // Safety: the list is borrowed mutably, so the node is still in it.
+        self.curr.map(|curr| unsafe { &*curr.as_ptr() })
+    }
+
+    /// Moves the cursor toward the back of the list.
+    pub fn move_next(&mut self) {
+        self.curr = match self.curr {
+            // Safety: the list is borrowed mutably, so the node is still in it.
+            Some(curr) => unsafe { L::pointers(curr).as_ref().get_next() },
+            None => self.list.head,
+        };
+    }
+
+    /// Moves the cursor toward the front of the list.
+    pub fn move_prev(&mut self) {
+        self.curr = match self.curr {
+            // Safety: the list is borrowed mutably, so the node is still in it.
+            Some(curr) => unsafe { L::pointers(curr).as_ref().get_prev() },
+            None => self.list.tail,
+        };
+    }
+
+    /// Removes the node the cursor points at and returns it, moving the cursor to the next
+    /// node. Returns None, and does nothing, at the ghost position.
+    pub fn remove_current(&mut self) -> Option<L::Handle> {
+        let curr = self.curr?;
+        // Safety: the list is borrowed mutably, so the node is still in it.
+        unsafe {
+            self.curr = L::pointers(curr).as_ref().get_next();
+            self.list.remove(curr)
+        }
+    }
+}
+
 /* Commented out, as not used by wait module.
 // ===== impl DrainFilter =====
 
@@ -518,6 +907,393 @@ mod tests {
         assert!(list.pop_back().is_none());
     }
 
+    #[test]
+    fn push_and_pop_front() {
+        let a = entry(5);
+        let b = entry(7);
+        let c = entry(31);
+
+        let mut list = LinkedList::<&Entry, <&Entry as Link>::Target>::new();
+        assert!(list.pop_front().is_none());
+
+        push_all(&mut list, &[a.as_ref(), b.as_ref(), c.as_ref()]);
+        assert_eq!(31, list.first().unwrap().val);
+        assert_eq!(5, list.last().unwrap().val);
+
+        assert_eq!(31, list.pop_front().unwrap().val);
+        assert_clean!(c);
+        assert_eq!(7, list.pop_front().unwrap().val);
+        assert_clean!(b);
+        assert_ptr_eq!(a, list.head);
+        assert_ptr_eq!(a, list.tail);
+        assert_eq!(5, list.pop_front().unwrap().val);
+        assert_clean!(a);
+
+        assert!(list.is_empty());
+        assert!(list.first().is_none());
+        assert!(list.last().is_none());
+    }
+
+    #[test]
+    fn push_back_and_pop_both_ends() {
+        let a = entry(5);
+        let b = entry(7);
+        let c = entry(31);
+
+        let mut list = LinkedList::<&Entry, <&Entry as Link>::Target>::new();
+
+        // A single element is both head and tail, whichever end it went in at.
+        list.push_back(a.as_ref());
+        assert_ptr_eq!(a, list.head);
+        assert_ptr_eq!(a, list.tail);
+        assert_eq!(5, list.pop_front().unwrap().val);
+        assert!(list.is_empty());
+        list.push_back(a.as_ref());
+        assert_eq!(5, list.pop_back().unwrap().val);
+        assert_clean!(a);
+        assert!(list.is_empty());
+
+        // Used as a queue from the back: push_back with pop_front.
+        list.push_back(a.as_ref());
+        list.push_back(b.as_ref());
+        list.push_back(c.as_ref());
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.len_backwards(), 3);
+        assert_eq!(5, list.first().unwrap().val);
+        assert_eq!(31, list.last().unwrap().val);
+        assert_eq!(5, list.pop_front().unwrap().val);
+        assert_eq!(7, list.pop_front().unwrap().val);
+        assert_eq!(31, list.pop_front().unwrap().val);
+        assert!(list.is_empty());
+
+        // Used as a stack from the back: push_back with pop_back.
+        list.push_back(a.as_ref());
+        list.push_back(b.as_ref());
+        assert_eq!(7, list.pop_back().unwrap().val);
+
+        // Mixing both ends.
+        list.push_front(c.as_ref());
+        list.push_back(b.as_ref());
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.len_backwards(), 3);
+        assert_eq!(31, list.pop_front().unwrap().val);
+        assert_eq!(7, list.pop_back().unwrap().val);
+        assert_eq!(5, list.pop_back().unwrap().val);
+        assert_clean!(a);
+        assert_clean!(b);
+        assert_clean!(c);
+        assert!(list.is_empty());
+        assert!(list.pop_front().is_none());
+        assert!(list.pop_back().is_none());
+    }
+
+    #[test]
+    fn insert_after_and_before() {
+        let a = entry(5);
+        let b = entry(7);
+        let c = entry(31);
+        let d = entry(43);
+
+        let mut list = LinkedList::<&Entry, <&Entry as Link>::Target>::new();
+        list.push_front(a.as_ref());
+
+        unsafe {
+            // After the tail becomes the new tail, before the head becomes the new head.
+            list.insert_after(ptr(&a), b.as_ref());
+            assert_ptr_eq!(b, list.tail);
+            list.insert_before(ptr(&a), c.as_ref());
+            assert_ptr_eq!(c, list.head);
+
+            // And in the middle.
+            list.insert_after(ptr(&a), d.as_ref());
+            assert_ptr_eq!(d, a.pointers.get_next());
+            assert_ptr_eq!(a, d.pointers.get_prev());
+            assert_ptr_eq!(b, d.pointers.get_next());
+            assert_ptr_eq!(d, b.pointers.get_prev());
+        }
+
+        assert_eq!(list.len(), 4);
+        assert_eq!(list.len_backwards(), 4);
+        let items = collect_list(&mut list);
+        assert_eq!([7, 43, 5, 31].to_vec(), items);
+    }
+
+    #[test]
+    fn drain() {
+        let a = entry(5);
+        let b = entry(7);
+        let c = entry(31);
+
+        let mut list = LinkedList::<&Entry, <&Entry as Link>::Target>::new();
+        push_all(&mut list, &[a.as_ref(), b.as_ref(), c.as_ref()]);
+
+        let items: Vec<i32> = list.drain().map(|entry| entry.val).collect();
+        assert_eq!([5, 7, 31].to_vec(), items);
+        assert!(list.is_empty());
+
+        // Dropped after taking only one: the rest are removed too.
+        push_all(&mut list, &[a.as_ref(), b.as_ref(), c.as_ref()]);
+        let mut drain = list.drain();
+        assert_eq!(5, drain.next().unwrap().val);
+        drop(drain);
+        assert!(list.is_empty());
+        assert_clean!(b);
+        assert_clean!(c);
+    }
+
+    #[test]
+    fn split_off() {
+        let entries: Vec<_> = (0..3).map(entry).collect();
+        let refs: Vec<_> = entries.iter().map(|e| e.as_ref()).collect();
+
+        for at in 0..=3 {
+            let mut list = LinkedList::<&Entry, <&Entry as Link>::Target>::new();
+            // Pushed to the front, so the front to back order is 2, 1, 0.
+            push_all(&mut list, &refs);
+
+            let mut back = list.split_off(at);
+            assert_eq!(list.len(), at);
+            assert_eq!(list.len_backwards(), at);
+            assert_eq!(back.len(), 3 - at);
+            assert_eq!(back.len_backwards(), 3 - at);
+
+            let mut items = collect_list(&mut back);
+            items.extend(collect_list(&mut list));
+            assert_eq!([0, 1, 2].to_vec(), items);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn split_off_past_end() {
+        let a = entry(5);
+        let mut list = LinkedList::<&Entry, <&Entry as Link>::Target>::new();
+        list.push_front(a.as_ref());
+        let _ = list.split_off(2);
+    }
+
+    #[test]
+    fn reverse() {
+        let entries: Vec<_> = (0..4).map(entry).collect();
+        let refs: Vec<_> = entries.iter().map(|e| e.as_ref()).collect();
+
+        let mut list = LinkedList::<&Entry, <&Entry as Link>::Target>::new();
+        list.reverse();
+        assert!(list.is_empty());
+
+        push_all(&mut list, &refs);
+        list.reverse();
+        assert_eq!(list.len(), 4);
+        assert_eq!(list.len_backwards(), 4);
+        assert_eq!(list.first().unwrap().val, 0);
+        assert_eq!(list.last().unwrap().val, 3);
+
+        let items = collect_list(&mut list);
+        assert_eq!([3, 2, 1, 0].to_vec(), items);
+    }
+
+    #[test]
+    fn append() {
+        let entries: Vec<_> = (0..6).map(entry).collect();
+        let refs: Vec<_> = entries.iter().map(|e| e.as_ref()).collect();
+
+        let mut list = LinkedList::<&Entry, <&Entry as Link>::Target>::new();
+        let mut other = LinkedList::<&Entry, <&Entry as Link>::Target>::new();
+        push_all(&mut list, &refs[3..]);
+        push_all(&mut other, &refs[..3]);
+
+        list.append(&mut other);
+        assert!(other.is_empty());
+        assert_eq!(list.len(), 6);
+        assert_eq!(list.len_backwards(), 6);
+
+        // Appending an empty list, or to an empty list, works too.
+        list.append(&mut other);
+        other.append(&mut list);
+        assert!(list.is_empty());
+
+        let items = collect_list(&mut other);
+        assert_eq!([0, 1, 2, 3, 4, 5].to_vec(), items);
+    }
+
+    #[test]
+    fn remove_matching() {
+        let entries: Vec<_> = (0..6).map(entry).collect();
+        let refs: Vec<_> = entries.iter().map(|e| e.as_ref()).collect();
+
+        let mut list = LinkedList::<&Entry, <&Entry as Link>::Target>::new();
+        push_all(&mut list, &refs);
+
+        // Every other node, including the head.
+        assert_eq!(list.remove_matching(|entry| entry.val % 2 == 1), 3);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.len_backwards(), 3);
+        for entry in entries.iter().filter(|e| e.val % 2 == 1) {
+            assert_clean!(entry);
+        }
+        assert_eq!(list.remove_matching(|_| false), 0);
+
+        let items = collect_list(&mut list);
+        assert_eq!([0, 2, 4].to_vec(), items);
+    }
+
+    #[test]
+    fn remove_matching_panic_leaves_list_valid() {
+        let entries: Vec<_> = (0..4).map(entry).collect();
+        let refs: Vec<_> = entries.iter().map(|e| e.as_ref()).collect();
+
+        let mut list = LinkedList::<&Entry, <&Entry as Link>::Target>::new();
+        push_all(&mut list, &refs);
+
+        // Front to back the list is 3, 2, 1, 0: 3 is removed before the panic at 1.
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            list.remove_matching(|entry| match entry.val {
+                1 => panic!("predicate panicked"),
+                val => val == 3,
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.len_backwards(), 3);
+
+        let items = collect_list(&mut list);
+        assert_eq!([0, 1, 2].to_vec(), items);
+    }
+
+    #[test]
+    fn len_matches_walk() {
+        let entries: Vec<_> = (0..6).map(entry).collect();
+        let refs: Vec<_> = entries.iter().map(|e| e.as_ref()).collect();
+
+        let mut list = LinkedList::<&Entry, <&Entry as Link>::Target>::new();
+        macro_rules! check {
+            ($n:expr) => {{
+                assert_eq!(list.len(), $n);
+                assert_eq!(list.len_backwards(), $n);
+            }};
+        }
+        check!(0);
+
+        push_all(&mut list, &refs[..3]);
+        check!(3);
+
+        unsafe {
+            list.insert_after(ptr(&entries[1]), refs[3]);
+            check!(4);
+            list.insert_before(ptr(&entries[1]), refs[4]);
+            check!(5);
+
+            assert!(list.remove(ptr(&entries[3])).is_some());
+            check!(4);
+            // Not in the list.
+            assert!(list.remove(ptr(&entries[5])).is_none());
+            check!(4);
+        }
+
+        list.pop_back();
+        list.pop_front();
+        check!(2);
+
+        let mut back = list.split_off(1);
+        check!(1);
+        assert_eq!(back.len(), 1);
+        list.append(&mut back);
+        check!(2);
+
+        list.cursor_mut().remove_current();
+        check!(1);
+
+        list.drain().for_each(drop);
+        check!(0);
+    }
+
+    #[test]
+    fn transfer() {
+        let a = entry(5);
+        let b = entry(7);
+        let c = entry(31);
+        let d = entry(43);
+
+        let mut list = LinkedList::<&Entry, <&Entry as Link>::Target>::new();
+        let mut other = LinkedList::<&Entry, <&Entry as Link>::Target>::new();
+        push_all(&mut list, &[a.as_ref(), b.as_ref(), c.as_ref()]);
+        other.push_front(d.as_ref());
+
+        unsafe {
+            assert!(list.transfer(&mut other, ptr(&b)));
+            // In no list: `remove` sees it isn't the head of the list.
+            let e = entry(47);
+            assert!(!list.transfer(&mut other, ptr(&e)));
+        }
+
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.len_backwards(), 2);
+        assert_eq!(other.len(), 2);
+        assert_eq!(other.len_backwards(), 2);
+
+        assert_eq!([5, 31].to_vec(), collect_list(&mut list));
+        assert_eq!([43, 7].to_vec(), collect_list(&mut other));
+    }
+
+    #[test]
+    fn cursor_walks_both_ways() {
+        let a = entry(5);
+        let b = entry(7);
+        let c = entry(31);
+
+        let mut list = LinkedList::<&Entry, <&Entry as Link>::Target>::new();
+        push_all(&mut list, &[a.as_ref(), b.as_ref(), c.as_ref()]);
+
+        let mut cursor = list.cursor();
+        let mut forward = vec![];
+        while let Some(entry) = cursor.current() {
+            forward.push(entry.val);
+            cursor.move_next();
+        }
+        assert_eq!([31, 7, 5].to_vec(), forward);
+
+        // From the ghost, prev goes to the back.
+        cursor.move_prev();
+        assert_eq!(5, cursor.current().unwrap().val);
+        cursor.move_prev();
+        cursor.move_prev();
+        cursor.move_prev();
+        assert!(cursor.current().is_none());
+        cursor.move_next();
+        assert_eq!(31, cursor.current().unwrap().val);
+
+        let _ = collect_list(&mut list);
+    }
+
+    #[test]
+    fn cursor_removes_middle() {
+        let a = entry(5);
+        let b = entry(7);
+        let c = entry(31);
+
+        let mut list = LinkedList::<&Entry, <&Entry as Link>::Target>::new();
+        push_all(&mut list, &[a.as_ref(), b.as_ref(), c.as_ref()]);
+
+        let mut cursor = list.cursor_mut();
+        cursor.move_next();
+        assert_eq!(7, cursor.remove_current().unwrap().val);
+        // The cursor moved on to the next node.
+        assert_eq!(5, cursor.current().unwrap().val);
+        assert_clean!(b);
+
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.len_backwards(), 2);
+        assert_ptr_eq!(a, c.pointers.get_next());
+        assert_ptr_eq!(c, a.pointers.get_prev());
+
+        let mut cursor = list.cursor_mut();
+        cursor.move_prev();
+        assert!(cursor.remove_current().is_none());
+
+        let items = collect_list(&mut list);
+        assert_eq!([5, 31].to_vec(), items);
+    }
+
     #[test]
     fn remove_by_address() {
         let a = entry(5);
@@ -1,3 +1,9 @@
-pub mod atomic_usize;
+cfg_std! {
+    pub mod atomic_usize;
+    pub(crate) mod block_on;
+    pub(crate) mod timer;
+}
 pub mod linked_list;
 pub mod unsafe_cell;
+#[cfg(any(test, feature = "alloc"))]
+pub mod wake_list;
@@ -0,0 +1,186 @@
+//! A thread that wakes the waiters of a shared `DeadlineList` once their deadlines pass, for the
+//! receives that give up at a deadline.
+//!
+//! The thread is started when a waiter is queued and none is running, sleeps until the earliest
+//! deadline in the list, or until a waiter with an earlier one is queued, and exits once the list
+//! is empty. The expired waiters are taken off the list under the lock and woken once it is
+//! released, so a waker that goes straight back to the timer doesn't deadlock on it.
+
+use crate::util::wake_list::WakeList;
+use crate::waiter::{DeadlineList, Elem};
+
+use std::sync::{Condvar, Mutex, OnceLock};
+use std::task::Context;
+use std::thread;
+use std::time::Instant;
+
+struct State {
+    list: DeadlineList,
+
+    /// Whether the thread is running. Only set and cleared with the lock held, so a waiter is
+    /// never queued between the thread finding the list empty and exiting.
+    running: bool,
+}
+
+struct Timer {
+    state: Mutex<State>,
+
+    /// Signalled when a waiter is queued ahead of the earliest deadline the thread sleeps until.
+    cond: Condvar,
+}
+
+static TIMER: OnceLock<Timer> = OnceLock::new();
+
+fn timer() -> &'static Timer {
+    TIMER.get_or_init(|| Timer {
+        state: Mutex::new(State {
+            list: DeadlineList::new(),
+            running: false,
+        }),
+        cond: Condvar::new(),
+    })
+}
+
+fn run() {
+    let timer = timer();
+    let mut wakers = WakeList::new();
+    let mut state = timer.state.lock().unwrap();
+    loop {
+        if state.list.drain_expired_into(Instant::now(), &mut wakers) > 0 {
+            drop(state);
+            wakers.wake_all();
+            // More deadlines may have passed while the wakers ran.
+            state = timer.state.lock().unwrap();
+            continue;
+        }
+        state = match state.list.next_deadline() {
+            Some(deadline) => {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                timer.cond.wait_timeout(state, timeout).unwrap().0
+            }
+            None => {
+                state.running = false;
+                return;
+            }
+        };
+    }
+}
+
+/// Queues `elem` to be woken through the waker from `cx` once `deadline` has passed, moving it if
+/// it is already queued with another deadline.
+///
+/// # Panics
+///
+/// Panics if the timer thread isn't running and can't be spawned. The elem is left unqueued.
+pub(crate) fn register(elem: &Elem<Instant>, cx: &mut Context<'_>, deadline: Instant) {
+    let timer = timer();
+    let mut state = timer.state.lock().unwrap();
+    if !state.running {
+        // The thread waits for the lock until this returns.
+        let spawned = thread::Builder::new().name("broadcast-rs-timer".into()).spawn(run);
+        if let Err(err) = spawned {
+            // Released first, so the panic doesn't poison the lock for every later deadline.
+            drop(state);
+            panic!("failed to spawn the timer thread: {}", err);
+        }
+        state.running = true;
+    }
+    state.list.enqueue_waiter(elem, cx, deadline);
+    if state.list.next_deadline() == Some(deadline) {
+        timer.cond.notify_one();
+    }
+}
+
+/// Removes `elem` from the timer's list, if it was queued.
+///
+/// # Safety
+///
+/// `elem` may only have been queued through `register`. This must be called before it is
+/// dropped.
+pub(crate) unsafe fn cancel(elem: &Elem<Instant>) {
+    // Nothing was ever registered if the timer was never set up.
+    if let Some(timer) = TIMER.get() {
+        timer.state.lock().unwrap().list.remove_waiter(elem);
+    }
+}
+
+/// Returns true if the timer thread is running.
+#[cfg(test)]
+fn is_running() -> bool {
+    TIMER.get().is_some_and(|timer| timer.state.lock().unwrap().running)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::CountingWaker;
+
+    use std::time::Duration;
+
+    /// Waits for the timer thread to exit, which it does once no deadline is queued. Other tests
+    /// share the timer, so give them time to finish with it.
+    fn wait_until_stopped() {
+        let start = Instant::now();
+        while is_running() {
+            assert!(start.elapsed() < Duration::from_secs(10), "timer thread never exited");
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Waits for the waker of `counter` to have been woken `n` times.
+    fn wait_for_wakes(counter: &CountingWaker, n: usize) {
+        let start = Instant::now();
+        while counter.wake_count() < n {
+            assert!(start.elapsed() < Duration::from_secs(10), "deadline never woken");
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn thread_exits_when_idle_and_restarts() {
+        let counter = CountingWaker::new();
+        let waker = counter.waker();
+        let mut cx = Context::from_waker(&waker);
+        // Safety: the elem is cancelled before it is dropped.
+        let elem = unsafe { Elem::new() };
+
+        for n in 1..=2 {
+            wait_until_stopped();
+            register(&elem, &mut cx, Instant::now() + Duration::from_millis(5));
+            assert!(is_running());
+            wait_for_wakes(&counter, n);
+        }
+        wait_until_stopped();
+        unsafe { cancel(&elem) };
+    }
+
+    #[test]
+    fn wakes_outside_the_lock() {
+        use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+        use std::sync::Arc;
+        use std::task::{Wake, Waker};
+
+        /// Takes the timer's lock from within its wake, which deadlocks if the wake is made with
+        /// the lock held.
+        struct Relock(AtomicBool);
+
+        impl Wake for Relock {
+            fn wake(self: Arc<Self>) {
+                self.0.store(is_running(), SeqCst);
+            }
+        }
+
+        let relock = Arc::new(Relock(AtomicBool::new(false)));
+        let waker = Waker::from(relock.clone());
+        // Safety: the elem is cancelled before it is dropped.
+        let elem = unsafe { Elem::new() };
+        register(&elem, &mut Context::from_waker(&waker), Instant::now());
+
+        let start = Instant::now();
+        while !relock.0.load(SeqCst) {
+            assert!(start.elapsed() < Duration::from_secs(10), "deadline never woken");
+            thread::sleep(Duration::from_millis(1));
+        }
+        unsafe { cancel(&elem) };
+    }
+}
@@ -1,16 +1,132 @@
+// With the `loom` feature, the cell is loom's, so that loom can check every access to it made
+// inside `loom::model`. Outside a model, loom's cell panics when accessed.
+#[cfg(feature = "loom")]
+pub use loom::cell::UnsafeCell;
+
+#[cfg(not(feature = "loom"))]
 #[derive(Debug)]
-pub struct UnsafeCell<T>(std::cell::UnsafeCell<T>);
+pub struct UnsafeCell<T> {
+    data: core::cell::UnsafeCell<T>,
+
+    /// With the `track` feature in debug builds, the number of `with` closures running, or -1
+    /// while a `with_mut` closure runs.
+    #[cfg(all(feature = "track", debug_assertions))]
+    borrow: core::sync::atomic::AtomicIsize,
+}
 
+#[cfg(not(feature = "loom"))]
 impl<T> UnsafeCell<T> {
     pub const fn new(data: T) -> UnsafeCell<T> {
-        UnsafeCell(std::cell::UnsafeCell::new(data))
+        UnsafeCell {
+            data: core::cell::UnsafeCell::new(data),
+            #[cfg(all(feature = "track", debug_assertions))]
+            borrow: core::sync::atomic::AtomicIsize::new(0),
+        }
     }
 
+    /// Calls `f` with a pointer for reading the value. With the `track` feature in debug
+    /// builds, panics if called from within a `with_mut` closure on the same cell.
     pub fn with<R>(&self, f: impl FnOnce(*const T) -> R) -> R {
-        f(self.0.get())
+        #[cfg(all(feature = "track", debug_assertions))]
+        let _borrow = track::Borrow::shared(&self.borrow);
+        f(self.data.get())
     }
 
+    /// Calls `f` with a pointer for changing the value. With the `track` feature in debug
+    /// builds, panics if called from within a `with` or `with_mut` closure on the same cell.
     pub fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
-        f(self.0.get())
+        #[cfg(all(feature = "track", debug_assertions))]
+        let _borrow = track::Borrow::exclusive(&self.borrow);
+        f(self.data.get())
+    }
+
+    /// Returns a mutable reference to the value. Holding `&mut self` already guarantees no
+    /// other access, so no closure or unsafe code is needed. Loom's cell has no equivalent, so
+    /// code shared with the `loom` feature keeps to `with_mut`.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Consumes the cell, returning the value. Loom's cell provides the same method.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+#[cfg(all(feature = "track", debug_assertions, not(feature = "loom")))]
+mod track {
+    use core::sync::atomic::{AtomicIsize, Ordering::SeqCst};
+
+    /// Marks a cell borrowed for as long as it lives, including while unwinding from a panic in
+    /// the closure.
+    pub(super) struct Borrow<'a>(&'a AtomicIsize);
+
+    impl<'a> Borrow<'a> {
+        pub(super) fn shared(flag: &'a AtomicIsize) -> Borrow<'a> {
+            let prev = flag.fetch_add(1, SeqCst);
+            if prev < 0 {
+                flag.fetch_sub(1, SeqCst);
+                panic!("UnsafeCell::with called within with_mut");
+            }
+            Borrow(flag)
+        }
+
+        pub(super) fn exclusive(flag: &'a AtomicIsize) -> Borrow<'a> {
+            if let Err(prev) = flag.compare_exchange(0, -1, SeqCst, SeqCst) {
+                if prev < 0 {
+                    panic!("UnsafeCell::with_mut called within with_mut");
+                }
+                panic!("UnsafeCell::with_mut called within with");
+            }
+            Borrow(flag)
+        }
+    }
+
+    impl<'a> Drop for Borrow<'a> {
+        fn drop(&mut self) {
+            if self.0.load(SeqCst) < 0 {
+                self.0.store(0, SeqCst);
+            } else {
+                self.0.fetch_sub(1, SeqCst);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(feature = "loom"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_mut() {
+        let mut cell = UnsafeCell::new(1);
+        *cell.get_mut() += 1;
+        assert_eq!(cell.with(|ptr| unsafe { *ptr }), 2);
+    }
+
+    #[test]
+    fn into_inner() {
+        let cell = UnsafeCell::new(String::from("waiter"));
+        cell.with_mut(|ptr| unsafe { (*ptr).push('s') });
+        assert_eq!(cell.into_inner(), "waiters");
+    }
+
+    #[test]
+    #[cfg(all(feature = "track", debug_assertions))]
+    #[should_panic(expected = "with_mut called within with_mut")]
+    fn reentrant_with_mut_panics() {
+        let cell = UnsafeCell::new(0);
+        cell.with_mut(|_| cell.with_mut(|_| ()));
+    }
+
+    #[test]
+    #[cfg(all(feature = "track", debug_assertions))]
+    fn nested_with_allowed() {
+        let cell = UnsafeCell::new(1);
+        let sum = cell.with(|a| cell.with(|b| unsafe { *a + *b }));
+        assert_eq!(sum, 2);
+        // The flag is cleared once the closures return.
+        cell.with_mut(|ptr| unsafe { *ptr = 3 });
     }
 }
@@ -0,0 +1,137 @@
+//! A batch of wakers to wake once a lock is released.
+//!
+//! Modeled on Tokio's internal `WakeList`: the first `NUM_WAKERS` wakers are kept inline, so a
+//! small batch can be gathered on the stack without allocating. Unlike Tokio's, which the caller
+//! must empty whenever it fills, this one spills the rest onto the heap.
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::ptr;
+use core::task::Waker;
+
+/// The number of wakers kept inline.
+pub const NUM_WAKERS: usize = 32;
+
+/// Wakers gathered while a lock is held, to be woken with `wake_all` once it is released.
+pub struct WakeList {
+    inner: [MaybeUninit<Waker>; NUM_WAKERS],
+    /// The number of initialized wakers at the start of `inner`.
+    curr: usize,
+    /// The wakers pushed once `inner` was full.
+    spill: Vec<Waker>,
+}
+
+impl WakeList {
+    pub fn new() -> WakeList {
+        WakeList {
+            inner: [const { MaybeUninit::uninit() }; NUM_WAKERS],
+            curr: 0,
+            spill: Vec::new(),
+        }
+    }
+
+    /// Adds `waker` to the batch, onto the heap if the inline slots are all taken.
+    pub fn push(&mut self, waker: Waker) {
+        if self.curr < NUM_WAKERS {
+            self.inner[self.curr].write(waker);
+            self.curr += 1;
+        } else {
+            self.spill.push(waker);
+        }
+    }
+
+    /// Returns the number of wakers in the batch.
+    pub fn len(&self) -> usize {
+        self.curr + self.spill.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns true if the batch has grown past its inline slots and allocated.
+    pub fn spilled(&self) -> bool {
+        self.spill.capacity() != 0
+    }
+
+    /// Wakes every waker in the batch, in the order they were pushed, leaving it empty.
+    ///
+    /// The heap allocation, if any, is kept for the next batch.
+    pub fn wake_all(&mut self) {
+        // Reset the length first, so a panicking waker leaks the remaining inline wakers rather
+        // than leaving them to be dropped twice.
+        let curr = core::mem::replace(&mut self.curr, 0);
+        for slot in &self.inner[..curr] {
+            // Safety: the first `curr` slots are initialized, and each is read only once since
+            // `curr` has been reset.
+            unsafe { slot.assume_init_read() }.wake();
+        }
+        for waker in self.spill.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+impl Default for WakeList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for WakeList {
+    fn drop(&mut self) {
+        let slice = ptr::slice_from_raw_parts_mut(self.inner.as_mut_ptr() as *mut Waker, self.curr);
+        // Safety: the first `curr` slots are initialized, and not used again.
+        unsafe { ptr::drop_in_place(slice) };
+    }
+}
+
+impl fmt::Debug for WakeList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WakeList")
+            .field("len", &self.len())
+            .field("spilled", &self.spilled())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::CountingWaker;
+
+    #[test]
+    fn spills_past_inline_capacity() {
+        let counter = CountingWaker::new();
+        let mut wakers = WakeList::new();
+        for _ in 0..NUM_WAKERS {
+            wakers.push(counter.waker());
+        }
+        assert!(!wakers.spilled());
+
+        wakers.push(counter.waker());
+        wakers.push(counter.waker());
+        assert!(wakers.spilled());
+        assert_eq!(wakers.len(), NUM_WAKERS + 2);
+
+        wakers.wake_all();
+        assert!(wakers.is_empty());
+        assert_eq!(counter.wake_count(), NUM_WAKERS + 2);
+        assert_eq!(counter.waker_count(), 0);
+    }
+
+    #[test]
+    fn drop_releases_unwoken_wakers() {
+        let counter = CountingWaker::new();
+        let mut wakers = WakeList::new();
+        for _ in 0..NUM_WAKERS + 1 {
+            wakers.push(counter.waker());
+        }
+        assert_eq!(counter.waker_count(), NUM_WAKERS + 1);
+
+        drop(wakers);
+        assert_eq!(counter.wake_count(), 0);
+        assert_eq!(counter.waker_count(), 0);
+    }
+}
@@ -4,6 +4,8 @@
 //! with no additional memory allocations and when the resouce might be available, for at least one
 //! of the waiters, awake them all, draining the list. The list is managed in FIFO order. New
 //! Futures are added to the back and when they are all awoken, they are awoken from front to back.
+//! A list can instead be built with `WakeOrder::Lifo`, in which case the newest waiter is awoken
+//! first.
 //!
 //! This module is not sound! Failure to call remove_waiter at the right time or calling it for the
 //! wrong list is UB. Most modules are sound. Improper use of a sound module should leave the
@@ -45,53 +47,500 @@
 
 use crate::util::linked_list;
 use crate::util::unsafe_cell::UnsafeCell;
+#[cfg(any(test, feature = "alloc"))]
+use crate::util::wake_list::WakeList;
 
-use std::marker::PhantomPinned;
-use std::ptr::NonNull;
-use std::task::{Context, Waker};
+use core::cell::{Cell, RefCell};
+use core::fmt;
+use core::marker::PhantomPinned;
+use core::pin::Pin;
+use core::ptr::NonNull;
+use core::task::{Context, Poll, Waker};
+#[cfg(all(feature = "metrics", feature = "std"))]
+use std::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
 
 // Logic has been extracted from broadcast.rs to provide the list and element types, List and Elem.
 
-pub struct List {
-    waiters: linked_list::LinkedList<Waiter, <Waiter as linked_list::Link>::Target>,
+/// The head of a list of waiters.
+///
+/// # Memory
+///
+/// The list is purely intrusive: each waiter, along with its payload, lives in the `Elem` the
+/// future holds, and the list itself is a fixed size head. Queuing, waking and removing waiters
+/// allocate nothing, so there is no spare capacity to reclaim after a burst and no
+/// `shrink_to_fit`. The only memory a waiter brings is what its waker's clone or its payload
+/// owns. The one exception is `awake_waiters` with more than 32 waiters, which holds their
+/// wakers on the heap while it wakes them. With the `leak-detect` feature, queuing also records
+/// the elem in a per-thread `Vec`.
+///
+/// ```
+/// use broadcast_rs::waiter::{Elem, List};
+/// use std::alloc::{GlobalAlloc, Layout, System};
+/// use std::pin::pin;
+/// use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+/// use std::task::{Context, Waker};
+///
+/// struct Counting;
+///
+/// static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+///
+/// unsafe impl GlobalAlloc for Counting {
+///     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+///         ALLOCS.fetch_add(1, SeqCst);
+///         System.alloc(layout)
+///     }
+///     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+///         System.dealloc(ptr, layout)
+///     }
+/// }
+///
+/// #[global_allocator]
+/// static GLOBAL: Counting = Counting;
+///
+/// fn main() {
+///     let mut cx = Context::from_waker(Waker::noop());
+///     let mut list = List::<u32>::new();
+///     // Safety: the elems are removed from, or woken out of, the list before they are dropped.
+///     let a = pin!(unsafe { Elem::new() });
+///     let b = pin!(unsafe { Elem::new() });
+///     let c = pin!(unsafe { Elem::new() });
+///
+///     let before = ALLOCS.load(SeqCst);
+///     list.enqueue_waiter_with(&a, &mut cx, 1);
+///     list.enqueue_waiter_with(&b, &mut cx, 2);
+///     list.enqueue_waiter(&c, &mut cx);
+///     assert!(list.wake_one());
+///     unsafe { list.remove_waiter(&b) };
+///     assert_eq!(list.awake_waiters(), 1);
+///     if !cfg!(feature = "leak-detect") {
+///         assert_eq!(ALLOCS.load(SeqCst), before);
+///     }
+/// }
+/// ```
+pub struct List<T = ()> {
+    waiters: linked_list::LinkedList<Waiter<T>, <Waiter<T> as linked_list::Link>::Target>,
+    order: WakeOrder,
+
+    /// Largest number of waiters queued at once since creation or the last `reset_max_len`.
+    #[cfg(feature = "metrics")]
+    max_len: usize,
+
+    /// Number of times `enqueue_waiter` had to clone the context's waker.
+    #[cfg(feature = "metrics")]
+    waker_clones: u64,
+
+    /// Number of times `enqueue_waiter` kept the stored waker because it would wake the same task.
+    #[cfg(feature = "metrics")]
+    waker_reuses: u64,
+
+    /// Whether a queued waiter's waker is replaced by a later registration.
+    waker_policy: WakerPolicy,
+
+    /// Most waiters `try_enqueue_waiter` lets queue at once, None if unbounded.
+    capacity: Option<usize>,
+
+    /// Number of times a waiter was newly queued.
+    #[cfg(feature = "metrics")]
+    total_enqueues: u64,
+
+    /// Number of times a waiter was taken off the list to be woken.
+    #[cfg(feature = "metrics")]
+    total_wakes: u64,
+
+    /// Unique id of the list. Stamped on each elem it queues, with debug assertions or the
+    /// `validate` feature, to catch an elem being used with two lists, and checked by
+    /// `PinnedWaiter` in every build to catch its list being replaced.
+    id: usize,
+}
+
+/// Source of `List` ids. Zero is never handed out; it marks an elem never queued.
+static NEXT_LIST_ID: core::sync::atomic::AtomicUsize = core::sync::atomic::AtomicUsize::new(1);
+
+/// Source of `Elem` generations, each elem getting its own.
+#[cfg(any(debug_assertions, feature = "validate"))]
+static NEXT_GENERATION: core::sync::atomic::AtomicUsize = core::sync::atomic::AtomicUsize::new(1);
+
+/// Error returned by [`List::try_enqueue_waiter`] when the list is at its capacity.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ListFull(());
+
+impl fmt::Display for ListFull {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "waiter list full")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ListFull {}
+
+/// The order in which a `List` wakes its waiters.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WakeOrder {
+    /// The oldest waiter is woken first. This is fair to all waiters.
+    #[default]
+    Fifo,
+    /// The newest waiter is woken first. This favors the task most recently run, whose state is
+    /// more likely to still be in cache, at the cost of fairness.
+    Lifo,
+}
+
+/// What a `List` does with the waker of an elem that is enqueued again while already queued.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WakerPolicy {
+    /// The latest registration's waker replaces the stored one, unless both wake the same task.
+    #[default]
+    ReplaceLatest,
+    /// The waker stored when the elem was queued is kept until the elem is woken or removed;
+    /// later registrations only keep the elem queued.
+    KeepFirst,
 }
 
-impl List {
-    pub fn new() -> List {
+impl<T> List<T> {
+    pub fn new() -> List<T> {
+        List::with_order(WakeOrder::Fifo)
+    }
+
+    pub fn with_order(order: WakeOrder) -> List<T> {
         List {
             waiters: linked_list::LinkedList::new(),
+            order,
+            #[cfg(feature = "metrics")]
+            max_len: 0,
+            #[cfg(feature = "metrics")]
+            waker_clones: 0,
+            #[cfg(feature = "metrics")]
+            waker_reuses: 0,
+            waker_policy: WakerPolicy::ReplaceLatest,
+            capacity: None,
+            #[cfg(feature = "metrics")]
+            total_enqueues: 0,
+            #[cfg(feature = "metrics")]
+            total_wakes: 0,
+            id: NEXT_LIST_ID.fetch_add(1, core::sync::atomic::Ordering::Relaxed),
+        }
+    }
+
+    /// Creates a FIFO list that `try_enqueue_waiter` keeps to at most `max` waiters, for
+    /// primitives that push back on new waiters rather than let any number of tasks park.
+    pub fn with_capacity(max: usize) -> List<T> {
+        List {
+            capacity: Some(max),
+            ..List::new()
+        }
+    }
+
+    /// Returns the most waiters `try_enqueue_waiter` lets queue at once, None if unbounded.
+    pub const fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    /// Returns true if the list holds as many waiters as its capacity, so `try_enqueue_waiter`
+    /// would reject a new one. Always false for an unbounded list.
+    pub const fn is_full(&self) -> bool {
+        match self.capacity {
+            Some(max) => self.waiters.len() >= max,
+            None => false,
         }
     }
+
+    pub fn waker_policy(&self) -> WakerPolicy {
+        self.waker_policy
+    }
+
+    pub fn set_waker_policy(&mut self, policy: WakerPolicy) {
+        self.waker_policy = policy;
+    }
+
+    pub fn order(&self) -> WakeOrder {
+        self.order
+    }
 }
 
-impl Default for List {
+impl<T> Default for List<T> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl List {
-    pub fn enqueue_waiter(&mut self, elem: &Elem, cx: &mut Context<'_>) {
+impl<T> List<T> {
+    /// Queues `elem`, if it is not already queued, and stores the waker from `cx` in it.
+    ///
+    /// Returns true if the elem was newly queued, false if it was already queued and only had its
+    /// waker refreshed.
+    #[inline]
+    pub fn enqueue_waiter(&mut self, elem: &Elem<T>, cx: &mut Context<'_>) -> bool {
         let waker = cx.waker();
+        // A future polled again by the same task finds itself queued with its waker already
+        // stored. Check for that with a read, so the common re-poll writes nothing to the elem.
+        //
+        // Safety: the list is held mutably, so the waiter is not being changed.
+        let redundant = elem.waiter.with(|ptr| unsafe {
+            self.check_owner_queued(ptr);
+            (*ptr).queued && (*ptr).waker.as_ref().is_some_and(|w| w.will_wake(waker))
+        });
+        if redundant {
+            #[cfg(feature = "metrics")]
+            {
+                self.waker_reuses += 1;
+            }
+            return false;
+        }
+        self.enqueue_waiter_slow(elem, waker)
+    }
+
+    /// The rest of `enqueue_waiter`, for an elem that is not queued or has a different waker.
+    #[inline(never)]
+    fn enqueue_waiter_slow(&mut self, elem: &Elem<T>, waker: &Waker) -> bool {
         // Safety: the mutable reference is held for the duration of the list traversal and list
         // and element changes.
         unsafe {
             // Store the waker unless it is the same as already stored.
             // Queue if not already queued.
             elem.waiter.with_mut(|ptr| {
-                match (*ptr).waker {
-                    Some(ref w) if w.will_wake(waker) => {}
-                    _ => {
-                        (*ptr).waker = Some(waker.clone());
-                    }
+                self.check_owner(ptr);
+                self.store_waker(ptr, waker);
+
+                if (*ptr).queued {
+                    return false;
+                }
+                (*ptr).queued = true;
+                #[cfg(feature = "leak-detect")]
+                debug::queued(ptr as usize);
+                (*ptr).notified = false;
+                (*ptr).priority = 0;
+                #[cfg(all(feature = "metrics", feature = "std"))]
+                {
+                    (*ptr).enqueued_at = Some(Instant::now());
                 }
+                // The pointer from the cell is used as is: a `&mut` made from it here would be
+                // retagged, and under Miri the neighbours' pointers to the waiter would not be
+                // derived from it.
+                self.waiters.push_front(NonNull::new_unchecked(ptr));
+                #[cfg(feature = "metrics")]
+                {
+                    self.max_len = self.max_len.max(self.waiters.len());
+                    self.total_enqueues += 1;
+                }
+                trace_waiter!(len = self.waiters.len(), "waiter enqueued");
+                true
+            })
+        }
+    }
+
+    /// Stamps the waiter with this list's id, and panics if it is still queued in another list. A
+    /// waiter about to be queued is also stamped with its generation and address, and one already
+    /// queued is checked against them. Only checked with debug assertions or the `validate`
+    /// feature; a no-op otherwise.
+    ///
+    /// # Safety
+    ///
+    /// The caller must hold the list mutably and `ptr` must point to a valid waiter.
+    #[inline]
+    unsafe fn check_owner(&self, _ptr: *mut Waiter<T>) {
+        #[cfg(any(debug_assertions, feature = "validate"))]
+        {
+            let list_id = (*_ptr).list_id;
+            assert!(
+                !(*_ptr).queued || list_id == self.id,
+                "elem of list {} enqueued on list {}",
+                list_id,
+                self.id
+            );
+            (*_ptr).list_id = self.id;
+            if (*_ptr).queued {
+                check_stamp(_ptr);
+            } else {
+                (*_ptr).queued_as = ((*_ptr).generation, _ptr as usize);
+            }
+        }
+    }
 
-                if !(*ptr).queued {
-                    (*ptr).queued = true;
-                    self.waiters.push_front(NonNull::new_unchecked(&mut *ptr));
+    /// Panics if the waiter is queued on another list. The read-only counterpart of
+    /// `check_owner`, with the same builds checking it.
+    ///
+    /// # Safety
+    ///
+    /// The caller must hold the list mutably and `ptr` must point to a valid waiter.
+    #[inline]
+    unsafe fn check_owner_queued(&self, _ptr: *const Waiter<T>) {
+        #[cfg(any(debug_assertions, feature = "validate"))]
+        if (*_ptr).queued {
+            let list_id = (*_ptr).list_id;
+            assert!(list_id == self.id, "elem of list {} enqueued on list {}", list_id, self.id);
+            check_stamp(_ptr);
+        }
+    }
+
+    /// Stores `waker` in the waiter, following the list's waker policy, unless the stored waker
+    /// already wakes the same task.
+    ///
+    /// # Safety
+    ///
+    /// The caller must hold the list mutably and `ptr` must point to one of its elems.
+    unsafe fn store_waker(&mut self, ptr: *mut Waiter<T>, waker: &Waker) {
+        let keep_first = (*ptr).queued && self.waker_policy == WakerPolicy::KeepFirst;
+        let keep = keep_first && (*ptr).waker.is_some();
+        let _cloned = !keep && refresh_waker(&mut (*ptr).waker, waker);
+        #[cfg(feature = "metrics")]
+        if _cloned {
+            self.waker_clones += 1;
+        } else {
+            self.waker_reuses += 1;
+        }
+    }
+
+    /// Queues `elem` ahead of every waiter with a lower `priority`, and behind every waiter with
+    /// the same or a higher one, and stores the waker from `cx` in it.
+    ///
+    /// Waiters queued with `enqueue_waiter` count as priority 0. Among equal priorities the
+    /// list's wake order is kept. An elem that is already queued keeps its place; only its waker
+    /// is refreshed. Returns true if the elem was newly queued.
+    ///
+    /// Unlike `enqueue_waiter`, which is O(1), this walks the list to find the insertion point
+    /// and so is O(n). In a LIFO list, a later `enqueue_waiter` puts its elem ahead of all the
+    /// others regardless of their priority.
+    pub fn enqueue_waiter_with_priority(
+        &mut self,
+        elem: &Elem<T>,
+        cx: &mut Context<'_>,
+        priority: u8,
+    ) -> bool {
+        // Safety: the mutable reference is held for the duration of the list traversal and
+        // element check.
+        if elem.waiter.with(|ptr| unsafe { (*ptr).queued }) {
+            return self.enqueue_waiter(elem, cx);
+        }
+
+        let order = self.order;
+        self.insert_sorted(elem, cx, priority, |waiter| match order {
+            WakeOrder::Fifo => waiter.priority >= priority,
+            WakeOrder::Lifo => waiter.priority > priority,
+        });
+        true
+    }
+
+    /// Queues `elem`, which must not already be queued, ahead of the first waiter for which
+    /// `wakes_first` returns false, walking from the end of the list that is woken first.
+    fn insert_sorted<F: Fn(&Waiter<T>) -> bool>(
+        &mut self,
+        elem: &Elem<T>,
+        cx: &mut Context<'_>,
+        priority: u8,
+        wakes_first: F,
+    ) {
+        let order = self.order;
+        let mut cursor = self.peek_next().map(NonNull::from);
+        // Safety: the mutable reference is held, so every waiter reached is still in this list.
+        unsafe {
+            while let Some(ptr) = cursor {
+                if !wakes_first(ptr.as_ref()) {
+                    break;
                 }
-            });
+                let pointers = Waiter::addr_of_pointers(ptr);
+                cursor = match order {
+                    WakeOrder::Fifo => pointers.as_ref().get_prev(),
+                    WakeOrder::Lifo => pointers.as_ref().get_next(),
+                };
+            }
+        }
+
+        elem.waiter.with_mut(|ptr| unsafe {
+            debug_assert!(!(*ptr).queued);
+            self.check_owner(ptr);
+            self.store_waker(ptr, cx.waker());
+            (*ptr).queued = true;
+            #[cfg(feature = "leak-detect")]
+            debug::queued(ptr as usize);
+            (*ptr).notified = false;
+            (*ptr).priority = priority;
+            #[cfg(all(feature = "metrics", feature = "std"))]
+            {
+                (*ptr).enqueued_at = Some(Instant::now());
+            }
+            let handle = NonNull::new_unchecked(ptr);
+            match (order, cursor) {
+                (WakeOrder::Fifo, Some(node)) => self.waiters.insert_after(node, handle),
+                (WakeOrder::Lifo, Some(node)) => self.waiters.insert_before(node, handle),
+                (WakeOrder::Fifo, None) => self.waiters.push_front(handle),
+                (WakeOrder::Lifo, None) => match self.waiters.last() {
+                    Some(tail) => self.waiters.insert_after(NonNull::from(tail), handle),
+                    None => self.waiters.push_front(handle),
+                },
+            }
+        });
+        #[cfg(feature = "metrics")]
+        {
+            self.max_len = self.max_len.max(self.waiters.len());
+            self.total_enqueues += 1;
+        }
+        trace_waiter!(len = self.waiters.len(), priority, "waiter enqueued");
+    }
+
+    /// Queues `elem` as `enqueue_waiter` does, unless it is not yet queued and the list is
+    /// full.
+    ///
+    /// Returns `Err(ListFull)` if the elem was rejected, having been left unqueued with no waker
+    /// stored. An elem already queued is never rejected, so a waiter polled again keeps its
+    /// place. The other enqueue methods don't check the capacity.
+    ///
+    /// A rejected waiter will not be woken by the list, so its future should not return
+    /// `Pending`; it should resolve to an error, passing the backpressure on to its caller.
+    pub fn try_enqueue_waiter(
+        &mut self,
+        elem: &Elem<T>,
+        cx: &mut Context<'_>,
+    ) -> Result<bool, ListFull> {
+        if self.is_full() && !self.is_queued(elem) {
+            return Err(ListFull(()));
         }
+        Ok(self.enqueue_waiter(elem, cx))
+    }
+
+    /// Stores `payload` in `elem`, replacing any payload already there, then queues it as
+    /// `enqueue_waiter` does.
+    ///
+    /// Returns true if the elem was newly queued.
+    pub fn enqueue_waiter_with(
+        &mut self,
+        elem: &Elem<T>,
+        cx: &mut Context<'_>,
+        payload: T,
+    ) -> bool {
+        // Safety: the mutable reference is held for the duration of the element change.
+        elem.waiter.with_mut(|ptr| unsafe { (*ptr).payload = Some(payload) });
+        self.enqueue_waiter(elem, cx)
+    }
+
+    /// Returns a reference to the payload stored in `elem`, if any.
+    pub fn payload<'b>(&'b self, elem: &'b Elem<T>) -> Option<&'b T> {
+        // Safety: the payload is only changed through a mutable reference to the list, and the
+        // shared reference is held for as long as the returned reference lives.
+        elem.waiter.with(|ptr| unsafe { (*ptr).payload.as_ref() })
+    }
+
+    /// Takes the payload out of `elem`, if any, leaving None in its place.
+    pub fn take_payload(&mut self, elem: &Elem<T>) -> Option<T> {
+        // Safety: the mutable reference is held for the duration of the element change.
+        elem.waiter.with_mut(|ptr| unsafe { (*ptr).payload.take() })
+    }
+
+    /// Clears what `elem` kept from its last wait, its waker, payload, priority and whether it
+    /// was woken, so a long-lived future can park the same pinned elem again as if it were new.
+    ///
+    /// Without it, a waker stored by an earlier cycle is kept alive until replaced, and a wake
+    /// from an earlier cycle is passed on by `remove_waiter_and_wake_next` as if it were the
+    /// current one. The elem must not be queued.
+    pub fn reset_elem(&mut self, elem: &Elem<T>) {
+        // Safety: the mutable reference is held for the duration of the element change.
+        elem.waiter.with_mut(|ptr| unsafe {
+            debug_assert!(!(*ptr).queued, "reset of a queued elem");
+            (*ptr).notified = false;
+            (*ptr).waker = None;
+            (*ptr).priority = 0;
+            (*ptr).payload = None;
+        });
     }
 
     /// Removes the `elem` from self, the list. This *must* be called by the Future's drop.
@@ -116,6 +565,9 @@ impl List {
     /// with another task working the list in parallel as the Elem drop would have not a mutable
     /// reference to the list.
     ///
+    /// An elem that is not queued, because it never was or because the list has since woken it,
+    /// is left alone, so calling this after a wake is always allowed and does nothing.
+    ///
     /// Note! This is the biggest footgun in this module.
     ///
     /// # Safety
@@ -128,11 +580,17 @@ impl List {
     /// The original design, in the Tokio broadcast type, used a mutex to ensure only one element
     /// at a time was being mutated.
     ///
+    /// With debug assertions or the `validate` feature, each elem remembers the list it was last
+    /// queued on and a call with a different list, while the elem is still queued, panics
+    /// instead. Queuing an elem that isn't
+    /// queued moves it to the new list, for a primitive that parks its waiters in one of several;
+    /// see `Elem::remove_from_current`.
+    ///
     /// # Safety2 The first safety outlines that this must be called for the List it may be
     /// enqueued on. This safety note is a reminder, as the initial comments above stated:
     ///
     ///   ** This *must* be called when the Future it is embedded in is dropped. **
-    pub unsafe fn remove_waiter(&mut self, elem: &Elem) {
+    pub unsafe fn remove_waiter(&mut self, elem: &Elem<T>) {
         // Note: There is no lock, but does hold &mut. So the caller was required to ensure sole
         // access to the list at this time. I believe holding the mutable reference serves the same
         // purpose.
@@ -146,232 +604,3244 @@ impl List {
         // and element changes.
         let queued = elem.waiter.with(|ptr| unsafe { (*ptr).queued });
 
-        if queued {
-            // Remove the element
-            //
-            // Safety: the element may only be in this list, the caller is responsible for that.
-            unsafe {
-                elem.waiter.with_mut(|ptr| {
-                    self.waiters.remove((&mut *ptr).into());
-                    (*ptr).queued = false;
-                });
-            }
+        if !queued {
+            // Never queued, or already taken off the list by a wake or `clear`: its pointers are
+            // stale, so neither they nor the list are touched. A future cancelled after being
+            // woken, but before its drop ran, ends up here, whichever list it is removed from.
+            return;
         }
-    }
-
-    pub fn awake_waiters(&mut self) {
-        while let Some(mut waiter) = self.waiters.pop_back() {
-            // Safety: the mutable reference is held for the duration of the list traversal and list
-            // and element changes.
-            let waiter = unsafe { waiter.as_mut() };
 
-            assert!(waiter.queued);
-            waiter.queued = false;
+        #[cfg(any(debug_assertions, feature = "validate"))]
+        elem.waiter.with(|ptr| unsafe {
+            let list_id = (*ptr).list_id;
+            assert!(list_id == self.id, "elem of list {} removed from list {}", list_id, self.id);
+            check_stamp(ptr);
+        });
 
-            let waker = waiter.waker.take().unwrap();
-            waker.wake();
+        // Remove the element
+        //
+        // Safety: the element may only be in this list, the caller is responsible for that.
+        unsafe {
+            elem.waiter.with_mut(|ptr| {
+                self.waiters.remove(NonNull::new_unchecked(ptr));
+                (*ptr).queued = false;
+                #[cfg(feature = "leak-detect")]
+                debug::dequeued(ptr as usize);
+            });
         }
+        trace_waiter!(len = self.waiters.len(), "waiter removed");
     }
 
-    pub fn is_empty(&self) -> bool {
-        // Safety: the reference is held for the duration of the list traversal.
-        self.waiters.is_empty()
-    }
+    /// Removes `elem`, a waiter being cancelled, from the list as `remove_waiter` does. If the
+    /// elem had already been woken by the list, the wake is passed on to the next waiter so it
+    /// isn't lost.
+    ///
+    /// Returns true if a next waiter was woken. This is the call to make from the drop of a
+    /// future whose wake stands for a single resource, such as a permit, that the future will now
+    /// never consume. A value handed over with `wake_one_with` is not passed on; take it with
+    /// `take_payload` and hand it on with `wake_one_with` instead.
+    ///
+    /// The list can't tell whether the future went on to take the resource. A future that does
+    /// must mark its wake consumed with `consume_wake` when it takes it; otherwise its drop passes
+    /// on a wake it has already used, waking the next waiter for nothing.
+    ///
+    /// # Safety
+    ///
+    /// The same as for `remove_waiter`.
+    pub unsafe fn remove_waiter_and_wake_next(&mut self, elem: &Elem<T>) -> bool {
+        self.remove_waiter(elem);
 
-    pub fn len(&self) -> usize {
-        // Safety: the reference is held for the duration of the list traversal.
-        self.waiters.len()
+        let notified = elem.waiter.with_mut(|ptr| core::mem::take(&mut (*ptr).notified));
+        notified && self.wake_one()
     }
 
-    pub fn len_backwards(&self) -> usize {
-        // Safety: the reference is held for the duration of the list traversal.
-        self.waiters.len_backwards()
+    /// Marks the wake `elem` was given by the list as consumed, so it is no longer passed on by
+    /// `remove_waiter_and_wake_next`. Returns true if the elem had been woken by the list.
+    ///
+    /// Called by a future taking the resource its wake stood for. Queuing the elem again, or
+    /// `reset_elem`, also clears the wake.
+    pub fn consume_wake(&mut self, elem: &Elem<T>) -> bool {
+        // Safety: the mutable reference is held for the duration of the element change.
+        elem.waiter.with_mut(|ptr| unsafe { core::mem::take(&mut (*ptr).notified) })
     }
-}
-
-pub struct Elem {
-    waiter: UnsafeCell<Waiter>,
-}
 
-impl Elem {
-    /// # Safety
+    /// Polls a future waiting on the list: runs `check`, and if it returns `Pending`, queues
+    /// `elem` with the waker from `cx` as `enqueue_waiter` does; if it returns `Ready`, removes
+    /// `elem` from the list as `remove_waiter` does. Either way the result of `check` is returned.
     ///
-    /// Constructing an Elem is only safe if the `remove_waiter` method on the list it is designed
-    /// for gets called from the drop method of the type that embeds Elem.
-    /// Failure to do so leads to UB.
+    /// This is the check, enqueue or remove, return sequence most futures built on the list
+    /// repeat in their `poll`. The future's drop must still call `remove_waiter`.
     ///
-    /// Refer to the unit test below for an example.
-    pub unsafe fn new() -> Elem {
-        Elem {
-            waiter: UnsafeCell::new(Waiter {
-                queued: false,
-                waker: None,
-                pointers: linked_list::Pointers::new(),
-                _p: PhantomPinned,
-            }),
+    /// # Examples
+    ///
+    /// ```
+    /// use broadcast_rs::waiter::{Elem, List};
+    /// use std::cell::{Cell, RefCell};
+    /// use std::future::Future;
+    /// use std::pin::{pin, Pin};
+    /// use std::task::{Context, Poll, Waker};
+    ///
+    /// struct Gate {
+    ///     open: Cell<bool>,
+    ///     list: RefCell<List>,
+    /// }
+    ///
+    /// /// Ready once the gate is open.
+    /// struct Opened<'a> {
+    ///     gate: &'a Gate,
+    ///     elem: Elem,
+    /// }
+    ///
+    /// impl Future for Opened<'_> {
+    ///     type Output = ();
+    ///
+    ///     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+    ///         let open = || if self.gate.open.get() { Poll::Ready(()) } else { Poll::Pending };
+    ///         // Safety: the elem is only ever queued in the gate's list.
+    ///         unsafe { self.gate.list.borrow_mut().poll_with(&self.elem, cx, open) }
+    ///     }
+    /// }
+    ///
+    /// impl Drop for Opened<'_> {
+    ///     fn drop(&mut self) {
+    ///         // Safety: the elem is only ever queued in the gate's list.
+    ///         unsafe { self.gate.list.borrow_mut().remove_waiter(&self.elem) };
+    ///     }
+    /// }
+    ///
+    /// let gate = Gate { open: Cell::new(false), list: RefCell::new(List::new()) };
+    /// // Safety: Opened's drop removes the elem from the list.
+    /// let mut opened = pin!(Opened { gate: &gate, elem: unsafe { Elem::new() } });
+    /// let mut cx = Context::from_waker(Waker::noop());
+    ///
+    /// assert!(opened.as_mut().poll(&mut cx).is_pending());
+    /// assert_eq!(gate.list.borrow().len(), 1);
+    ///
+    /// gate.open.set(true);
+    /// assert!(opened.as_mut().poll(&mut cx).is_ready());
+    /// assert!(gate.list.borrow().is_empty());
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// The same as for `remove_waiter`: if `elem` is queued, it must be queued in this list.
+    pub unsafe fn poll_with<R, F: FnMut() -> Poll<R>>(
+        &mut self,
+        elem: &Elem<T>,
+        cx: &mut Context<'_>,
+        mut check: F,
+    ) -> Poll<R> {
+        let poll = check();
+        if poll.is_pending() {
+            self.enqueue_waiter(elem, cx);
+        } else {
+            self.remove_waiter(elem);
         }
+        poll
     }
-}
 
-impl Drop for Elem {
-    fn drop(&mut self) {
-        // For those embedding this code into their source, if you understand the risks,
-        // you may want to change this assert to a debug_assert, or remove it entirely.
-        //
-        // This function cannot be used to remove an element from a list, but it can trigger a
-        // panic if it detects it was left in a list.
-        let queued = self.waiter.with(|ptr| unsafe { (*ptr).queued });
-        assert!(!queued);
+    /// Returns true if `elem` is currently queued, waiting to be woken.
+    ///
+    /// Only the elem's own flag is read; the list is not searched. An elem queued in a different
+    /// list also reports true.
+    pub fn is_queued(&self, elem: &Elem<T>) -> bool {
+        // Safety: the reference to the list is held, so the flag cannot be changed by a list
+        // operation while it is read.
+        elem.waiter.with(|ptr| unsafe { (*ptr).queued })
     }
-}
 
-// Waiter has been copied from broadcast.rs.
+    /// Returns true if `elem` is found by walking this list.
+    ///
+    /// Unlike `is_queued`, the elem's flag is not trusted, so this can check a `remove_waiter`
+    /// really took the elem out. O(n), and only compiled with debug assertions or the `validate`
+    /// feature.
+    #[cfg(any(debug_assertions, feature = "validate"))]
+    pub fn contains(&self, elem: &Elem<T>) -> bool {
+        // Only the address is taken; the waiter is not read.
+        elem.waiter.with(|ptr| self.waiters.contains(NonNull::new(ptr as *mut _).unwrap()))
+    }
 
-/// An entry in the wait queue.
-struct Waiter {
-    /// True if queued.
-    queued: bool,
+    /// Moves `elem` to the newest position of the list, queuing it if it was not already queued,
+    /// and stores the waker from `cx`.
+    ///
+    /// Where `enqueue_waiter` leaves an already queued elem in place, this gives up the elem's
+    /// place in line. With the default FIFO order the elem will be woken after every waiter
+    /// currently queued, so a future that requeues itself on every poll can be starved by others
+    /// that don't.
+    ///
+    /// # Safety
+    ///
+    /// The same as for `remove_waiter`: if `elem` is queued, it must be queued in this list.
+    pub unsafe fn requeue_to_back(&mut self, elem: &Elem<T>, cx: &mut Context<'_>) {
+        self.remove_waiter(elem);
+        self.enqueue_waiter(elem, cx);
+    }
 
-    /// Future waiting to be awoken (with awake_waiters).
-    waker: Option<Waker>,
+    /// Removes the waiter that is next to be woken, based on the list's order. Every wake goes
+    /// through here.
+    fn pop_next(&mut self) -> Option<NonNull<Waiter<T>>> {
+        let waiter = match self.order {
+            WakeOrder::Fifo => self.waiters.pop_back(),
+            WakeOrder::Lifo => self.waiters.pop_front(),
+        }?;
+        self.taken_to_wake();
+        Some(waiter)
+    }
 
-    /// Intrusive linked-list pointers.
-    pointers: linked_list::Pointers<Waiter>,
+    /// Counts and traces a waiter just taken off the list to be woken.
+    fn taken_to_wake(&mut self) {
+        #[cfg(feature = "metrics")]
+        {
+            self.total_wakes += 1;
+        }
+        trace_waiter!(len = self.waiters.len(), "waiter woken");
+    }
 
-    /// Should not be `Unpin`.
-    _p: PhantomPinned,
-}
+    /// Wakes the first waiter, in wake order, for which `f` returns true, removing it from the
+    /// list. Returns true if a waiter was woken.
+    fn wake_first_waiter<F: FnMut(&Waiter<T>) -> bool>(&mut self, mut f: F) -> bool {
+        let found = self.iter_waiter_ptrs().find(|ptr| {
+            // Safety: the waiter is linked in this list, which is held.
+            f(unsafe { ptr.as_ref() })
+        });
+        let Some(waiter) = found else {
+            return false;
+        };
+        // Safety: the waiter was just found in this list.
+        unsafe { self.waiters.remove(waiter) };
+        self.taken_to_wake();
+        // Safety: the mutable reference is held for the duration of the list and element
+        // changes.
+        unsafe { wake_waiter(waiter) }
+    }
 
-generate_addr_of_methods! {
-    impl<> Waiter {
-        unsafe fn addr_of_pointers(self: NonNull<Self>) -> NonNull<linked_list::Pointers<Waiter>> {
-            &self.pointers
+    /// Returns the waiter that is next to be woken, based on the list's order.
+    fn peek_next(&self) -> Option<&Waiter<T>> {
+        match self.order {
+            WakeOrder::Fifo => self.waiters.last(),
+            WakeOrder::Lifo => self.waiters.first(),
         }
     }
-}
-
-/// # Safety
-///
-/// `Waiter` is required and forced to be !Unpin.
-unsafe impl linked_list::Link for Waiter {
-    type Handle = NonNull<Waiter>;
-    type Target = Waiter;
 
-    fn as_raw(handle: &NonNull<Waiter>) -> NonNull<Waiter> {
-        *handle
+    /// Returns a clone of the waker `wake_one` would wake next, or None if the list is empty.
+    ///
+    /// The list and its waiters are left untouched. With the default FIFO order this is the
+    /// waker at the back of the list, the oldest.
+    pub fn peek_back_waker(&self) -> Option<Waker> {
+        self.peek_next().and_then(|waiter| waiter.waker.clone())
     }
 
-    unsafe fn from_raw(ptr: NonNull<Waiter>) -> NonNull<Waiter> {
-        ptr
+    /// Returns an iterator over the queued waiters, in wake order.
+    fn iter_waiters(&self) -> impl Iterator<Item = &Waiter<T>> + '_ {
+        // Safety: the shared reference to the list is held for as long as the references.
+        self.iter_waiter_ptrs().map(|ptr| unsafe { &*ptr.as_ptr() })
     }
 
-    unsafe fn pointers(target: NonNull<Waiter>) -> NonNull<linked_list::Pointers<Waiter>> {
-        Waiter::addr_of_pointers(target)
+    /// As `iter_waiters`, but yields the raw pointers, which can be used to change the waiters
+    /// once the iteration is done.
+    fn iter_waiter_ptrs(&self) -> impl Iterator<Item = NonNull<Waiter<T>>> + '_ {
+        let order = self.order;
+        let mut next = self.peek_next().map(NonNull::from);
+        core::iter::from_fn(move || {
+            let ptr = next?;
+            // Safety: the shared reference to the list is held for the duration of the
+            // traversal, so every waiter reached is still linked in this list.
+            unsafe {
+                let pointers = Waiter::addr_of_pointers(ptr);
+                next = match order {
+                    WakeOrder::Fifo => pointers.as_ref().get_prev(),
+                    WakeOrder::Lifo => pointers.as_ref().get_next(),
+                };
+                Some(ptr)
+            }
+        })
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use core::pin::Pin;
-    use core::task::Poll;
-    use core::cell::RefCell;
-    use core::future::Future;
-    use std::rc::Rc;
-    use tokio::task;
+    /// Returns an iterator over clones of the stored wakers, in wake order.
+    ///
+    /// Nothing is removed from the list. The list is borrowed for as long as the iterator lives,
+    /// so no waiter can be added or removed during the iteration.
+    pub fn iter_wakers(&self) -> impl Iterator<Item = Waker> + '_ {
+        self.iter_waiters().filter_map(|waiter| waiter.waker.clone())
+    }
 
-    #[derive(Clone, Default)]
-    /// Foo is the manager of the Futures.
-    struct Foo {
-        list: Rc<RefCell<List>>,
+    /// Returns an iterator over the payloads of the queued waiters, in wake order.
+    ///
+    /// A waiter queued with `enqueue_waiter`, and never given a payload, yields None.
+    pub fn iter_payloads(&self) -> impl Iterator<Item = Option<&T>> + '_ {
+        self.iter_waiters().map(|waiter| waiter.payload.as_ref())
     }
-    /// Bar is the Future. It may not outlive its manager.
-    struct Bar<'a> {
-        foo: &'a Foo,
-        countdown: usize,
-        elem: Elem,
+
+    /// Wakes waiters, in wake order, for as long as `f` returns true for the waiter's waker.
+    ///
+    /// The walk stops at the first waiter for which `f` returns false; that waiter and all the
+    /// ones behind it are left in place. Returns the number of waiters woken.
+    ///
+    /// `f` is always called on a waiter that is still linked in the list and before the list is
+    /// changed, so if `f` panics the list is left consistent: the waiters already woken are out
+    /// of the list and the rest, including the one `f` was looking at, are still queued.
+    pub fn wake_while<F: FnMut(&Waker) -> bool>(&mut self, mut f: F) -> usize {
+        // A waiter without a waker, which should not happen, is dropped from the list unseen.
+        self.wake_while_waiter(|waiter| waiter.waker.as_ref().is_none_or(&mut f))
     }
-    // Define Bar to be Unpin, despite the Foo reference. The Foo reference is not a self
-    // reference. The Future in this case must be Unpin to allow its countdown field to be
-    // modified when it is polled.
-    impl<'a> Unpin for Bar<'a> {}
 
-    impl Foo {
-        fn new() -> Foo {
-            Foo {
-                list: Default::default(),
+    /// As `wake_while`, but `f` is given the whole waiter.
+    fn wake_while_waiter<F: FnMut(&Waiter<T>) -> bool>(&mut self, mut f: F) -> usize {
+        let mut woken = 0;
+        loop {
+            let wake = match self.peek_next() {
+                Some(waiter) => f(waiter),
+                None => false,
+            };
+            if !wake {
+                break;
             }
-        }
-        /// Returns a Future that will become ready once its polled countdown is reached.
-        fn bar<'a>(&'a self, countdown: usize) -> Bar<'a> {
-            Bar {
-                foo: self,
-                countdown,
-                // Safety: This Elem is removed by Bar's drop: list.remove_waiter(&self.elem).
-                elem: unsafe { Elem::new() },
+            let waiter = self.pop_next().unwrap();
+            // Safety: the mutable reference is held for the duration of the list and element
+            // changes.
+            if unsafe { wake_waiter(waiter) } {
+                woken += 1;
             }
         }
+        woken
     }
 
-    impl<'a> Future for Bar<'a> {
-        type Output = ();
+    /// Removes all the waiters without waking them, dropping their wakers, draining the list.
+    ///
+    /// Returns the number of waiters removed. Meant for tearing down a list whose futures are
+    /// known to be going away; a future left waiting on a cleared list is never woken.
+    pub fn clear(&mut self) -> usize {
+        // Nothing is woken, so the wake order doesn't matter.
+        let mut removed = 0;
+        for mut waiter in self.waiters.drain() {
+            // Safety: the mutable reference is held for the duration of the list traversal and
+            // element changes.
+            let waiter = unsafe { waiter.as_mut() };
+            waiter.queued = false;
+            #[cfg(feature = "leak-detect")]
+            debug::dequeued(waiter as *mut Waiter<T> as usize);
+            drop(waiter.waker.take());
+            removed += 1;
+        }
+        removed
+    }
 
-        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
-            if self.countdown == 0 {
+    /// Wakes all the waiters, in wake order, draining the list.
+    ///
+    /// Returns the number of waiters woken.
+    ///
+    /// With the `alloc` feature, every waiter is taken off the list, into a `WakeList`, before
+    /// any is woken. A waker that runs its task inline, and so has it poll again, finds the list
+    /// already empty and every elem no longer queued, and once it can get at the list, the task
+    /// is queued behind none of the waiters woken with it. Beyond the `WakeList`'s 32 inline
+    /// slots this allocates. Without `alloc`, each waiter is woken as it is taken off the list.
+    #[cfg(any(test, feature = "alloc"))]
+    pub fn awake_waiters(&mut self) -> usize {
+        let mut wakers = WakeList::new();
+        let woken = self.drain_into_wake_list(&mut wakers);
+        wakers.wake_all();
+        woken
+    }
+
+    /// Wakes all the waiters, in wake order, draining the list.
+    ///
+    /// Returns the number of waiters woken.
+    #[cfg(not(any(test, feature = "alloc")))]
+    pub fn awake_waiters(&mut self) -> usize {
+        let mut woken = 0;
+        while let Some(waiter) = self.pop_next() {
+            // Safety: the mutable reference is held for the duration of the list traversal and list
+            // and element changes.
+            if unsafe { wake_waiter(waiter) } {
+                woken += 1;
+            }
+        }
+        woken
+    }
+
+    /// Takes the wakers of all the waiters, in wake order, pushing them onto `buf` and draining
+    /// the list, without waking any of them.
+    ///
+    /// Returns the number of wakers pushed. The waiters are marked as woken just as with
+    /// `awake_waiters`, but the wakes are left to the caller, who can release the lock around
+    /// the list first. That way a task woken inline, that goes straight back to the list, doesn't
+    /// find the lock still held.
+    #[cfg(any(test, feature = "alloc"))]
+    pub fn drain_wakers_into(&mut self, buf: &mut alloc::vec::Vec<Waker>) -> usize {
+        let mut taken = 0;
+        while let Some(waiter) = self.pop_next() {
+            // Safety: the mutable reference is held for the duration of the list and element
+            // changes.
+            if let Some(waker) = unsafe { take_waker(waiter) } {
+                buf.push(waker);
+                taken += 1;
+            }
+        }
+        taken
+    }
+
+    /// Takes the wakers of at most `n` waiters, in wake order, pushing them onto `buf` and
+    /// removing the waiters from the list, without waking any of them.
+    ///
+    /// Returns the number of wakers pushed, less than `n` when the list runs out first. The
+    /// bounded form of `drain_wakers_into`, as `wake_n` is of `awake_waiters`: a semaphore
+    /// releasing `n` permits takes this many wakers under its lock and wakes them after.
+    #[cfg(any(test, feature = "alloc"))]
+    pub fn drain_n_into(&mut self, n: usize, buf: &mut alloc::vec::Vec<Waker>) -> usize {
+        let mut taken = 0;
+        while taken < n {
+            let Some(waiter) = self.pop_next() else {
+                break;
+            };
+            // Safety: the mutable reference is held for the duration of the list and element
+            // changes.
+            if let Some(waker) = unsafe { take_waker(waiter) } {
+                buf.push(waker);
+                taken += 1;
+            }
+        }
+        taken
+    }
+
+    /// Takes the wakers of all the waiters, in wake order, returning them and draining the list,
+    /// without waking any of them.
+    ///
+    /// The `Vec` returning form of `drain_wakers_into`. Wakers taken from several lists can be
+    /// gathered this way and woken together, once all the locks are released.
+    #[cfg(any(test, feature = "alloc"))]
+    pub fn wake_all_drain_returning_wakers(&mut self) -> alloc::vec::Vec<Waker> {
+        let mut wakers = alloc::vec::Vec::new();
+        self.drain_wakers_into(&mut wakers);
+        wakers
+    }
+
+    /// Takes the wakers of all the waiters, in wake order, adding them to `wakers` and draining
+    /// the list, without waking any of them.
+    ///
+    /// Like `drain_wakers_into`, but the first wakers go into the inline slots of the
+    /// `WakeList`, so waking a small batch outside the lock doesn't allocate. Returns the number
+    /// of wakers added.
+    #[cfg(any(test, feature = "alloc"))]
+    pub fn drain_into_wake_list(&mut self, wakers: &mut WakeList) -> usize {
+        let mut taken = 0;
+        while let Some(waiter) = self.pop_next() {
+            // Safety: the mutable reference is held for the duration of the list and element
+            // changes.
+            if let Some(waker) = unsafe { take_waker(waiter) } {
+                wakers.push(waker);
+                taken += 1;
+            }
+        }
+        taken
+    }
+
+    /// Wakes the next waiter, removing it from the list. With the default FIFO order, this is
+    /// the oldest waiter.
+    ///
+    /// The same order used by `awake_waiters` is used here, so a mix of the two calls stays
+    /// consistent. Returns true if a waiter was woken, false if the list was empty.
+    pub fn wake_one(&mut self) -> bool {
+        while let Some(waiter) = self.pop_next() {
+            // Safety: the mutable reference is held for the duration of the list and element
+            // changes.
+            if unsafe { wake_waiter(waiter) } {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Reverses the order of the queued waiters in O(n), without allocating, so the waiter that
+    /// would have been woken last is woken first.
+    ///
+    /// Meant for debugging and for trying out orders; the list's `WakeOrder` is unchanged, and
+    /// waiters queued afterwards go in at the usual end.
+    pub fn reverse(&mut self) {
+        self.waiters.reverse();
+    }
+
+    /// Wakes the next waiter, as `wake_one` does, and returns whether one was woken along with
+    /// the number of waiters left in the list.
+    ///
+    /// Saves a separate call to `len` for code that, having handed a resource to one waiter,
+    /// keeps a flag set only while others are still waiting.
+    pub fn wake_one_and_remaining(&mut self) -> (bool, usize) {
+        let woken = self.wake_one();
+        (woken, self.waiters.len())
+    }
+
+    /// Hands `value` to the next waiter, storing it as the waiter's payload, then wakes it and
+    /// removes it from the list.
+    ///
+    /// The woken future finds the value with `take_payload` on its next poll. If the list is
+    /// empty, the value is given back as `Err(value)`.
+    pub fn wake_one_with(&mut self, mut value: T) -> Result<(), T> {
+        while let Some(mut waiter) = self.pop_next() {
+            // Safety: the mutable reference is held for the duration of the list and element
+            // changes.
+            unsafe {
+                waiter.as_mut().payload = Some(value);
+                if wake_waiter(waiter) {
+                    return Ok(());
+                }
+                // No one to hand the value to after all; keep it for the next waiter.
+                value = waiter.as_mut().payload.take().unwrap();
+            }
+        }
+        Err(value)
+    }
+
+    /// Wakes at most `n` waiters, in wake order, removing them from the list.
+    ///
+    /// Returns the number of waiters woken, which is less than `n` when the list runs out first.
+    /// The waiters left in the list keep their order.
+    pub fn wake_n(&mut self, n: usize) -> usize {
+        let mut woken = 0;
+        while woken < n {
+            if !self.wake_one() {
+                break;
+            }
+            woken += 1;
+        }
+        woken
+    }
+
+    pub fn is_empty(&self) -> bool {
+        // Safety: the reference is held for the duration of the list traversal.
+        self.waiters.is_empty()
+    }
+
+    pub const fn len(&self) -> usize {
+        self.waiters.len()
+    }
+
+    pub fn len_backwards(&self) -> usize {
+        // Safety: the reference is held for the duration of the list traversal.
+        self.waiters.len_backwards()
+    }
+
+    /// Panics unless both `len` and `len_backwards` equal `expected`.
+    ///
+    /// A one-call check for tests of code built on the list. Only compiled with debug assertions,
+    /// the `validate` feature, or in this crate's own tests.
+    #[cfg(any(test, debug_assertions, feature = "validate"))]
+    #[track_caller]
+    pub fn assert_len(&self, expected: usize) {
+        let len = self.len();
+        let len_backwards = self.len_backwards();
+        assert!(
+            len == expected && len_backwards == expected,
+            "list length is wrong: expected {}, len {}, len_backwards {}",
+            expected,
+            len,
+            len_backwards
+        );
+    }
+
+    /// Checks the list's invariants, panicking if any is broken.
+    ///
+    /// Walks the list forwards and backwards, checking that both walks see the same number of
+    /// waiters as the list has queued, that each waiter's next and prev pointers agree with its
+    /// neighbours', and that every waiter is flagged as queued. A walk that runs longer than
+    /// expected, as it would on a cycle, panics rather than looping.
+    ///
+    /// This is O(n) and is only compiled with debug assertions or the `validate` feature. It is
+    /// meant to be sprinkled through tests of code built on the list.
+    #[cfg(any(debug_assertions, feature = "validate"))]
+    pub fn validate(&self) {
+        let expected = self.waiters.len();
+
+        let mut n = 0;
+        let mut prev: Option<NonNull<Waiter<T>>> = None;
+        let mut next = self.waiters.first().map(NonNull::from);
+        while let Some(ptr) = next {
+            n += 1;
+            assert!(n <= expected, "list walked forwards is longer than {}", expected);
+            // Safety: the shared reference is held for the duration of the list traversal.
+            unsafe {
+                assert!(ptr.as_ref().queued, "waiter in list is not flagged as queued");
+                let pointers = Waiter::addr_of_pointers(ptr);
+                assert_eq!(pointers.as_ref().get_prev(), prev, "waiter prev pointer is wrong");
+                prev = Some(ptr);
+                next = pointers.as_ref().get_next();
+            }
+        }
+        assert_eq!(n, expected, "list walked forwards has the wrong length");
+        assert_eq!(prev, self.waiters.last().map(NonNull::from), "list tail is wrong");
+
+        let mut n = 0;
+        let mut prev = self.waiters.last().map(NonNull::from);
+        while let Some(ptr) = prev {
+            n += 1;
+            assert!(n <= expected, "list walked backwards is longer than {}", expected);
+            // Safety: the shared reference is held for the duration of the list traversal.
+            prev = unsafe { Waiter::addr_of_pointers(ptr).as_ref().get_prev() };
+        }
+        assert_eq!(n, expected, "list walked backwards has the wrong length");
+        assert_eq!(self.waiters.is_empty(), expected == 0);
+    }
+
+    /// Takes the waker out of a queued elem, leaving it in the state the wake paths must
+    /// tolerate.
+    #[cfg(test)]
+    fn take_waker(&mut self, elem: &Elem<T>) -> Option<Waker> {
+        elem.waiter.with_mut(|ptr| unsafe { (*ptr).waker.take() })
+    }
+
+    /// Returns the largest number of waiters the list has held at once, since it was created or
+    /// since the last call to `reset_max_len`. Only available with the `metrics` feature.
+    #[cfg(feature = "metrics")]
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    /// Resets the high-water mark to the number of waiters currently queued. Only available with
+    /// the `metrics` feature.
+    #[cfg(feature = "metrics")]
+    pub fn reset_max_len(&mut self) {
+        self.max_len = self.waiters.len();
+    }
+
+    /// Returns the number of times `enqueue_waiter` stored a clone of the context's waker. Only
+    /// available with the `metrics` feature.
+    #[cfg(feature = "metrics")]
+    pub fn waker_clones(&self) -> u64 {
+        self.waker_clones
+    }
+
+    /// Returns the number of times `enqueue_waiter` avoided a clone because the stored waker
+    /// would already wake the polling task. Only available with the `metrics` feature.
+    #[cfg(feature = "metrics")]
+    pub fn waker_reuses(&self) -> u64 {
+        self.waker_reuses
+    }
+
+    /// Returns a snapshot of the list's counters. Only available with the `metrics` feature.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> ListMetrics {
+        ListMetrics {
+            current_len: self.waiters.len(),
+            max_len: self.max_len,
+            total_enqueues: self.total_enqueues,
+            total_wakes: self.total_wakes,
+            waker_clones: self.waker_clones,
+            waker_reuses: self.waker_reuses,
+        }
+    }
+
+    /// Returns how long, as of `now`, the waiter next to be woken has been queued, or None if the
+    /// list is empty.
+    ///
+    /// With the default FIFO order this is the longest any waiter has been waiting, so a growing
+    /// value points to starvation. Only available with the `metrics` and `std` features.
+    #[cfg(all(feature = "metrics", feature = "std"))]
+    pub fn oldest_wait(&self, now: Instant) -> Option<Duration> {
+        let enqueued_at = self.peek_next()?.enqueued_at?;
+        Some(now.saturating_duration_since(enqueued_at))
+    }
+}
+
+/// Keyed waiters: each waiter carries a `u64` key as its payload, so one can be picked out to be
+/// woken, say the task waiting on channel 7 of a set.
+impl List<u64> {
+    /// Queues `elem` with `key` as its payload, as `enqueue_waiter_with` does.
+    ///
+    /// Returns true if the elem was newly queued. Several waiters may share a key.
+    pub fn enqueue_waiter_keyed(
+        &mut self,
+        elem: &Elem<u64>,
+        cx: &mut Context<'_>,
+        key: u64,
+    ) -> bool {
+        self.enqueue_waiter_with(elem, cx, key)
+    }
+
+    /// Wakes the first waiter, in wake order, queued with `key`, removing it from the list.
+    ///
+    /// Returns false if no waiter has the key. Of several with the key, only the first is woken.
+    /// This walks the list, so is O(n). The key is left as the woken elem's payload.
+    pub fn wake_key(&mut self, key: u64) -> bool {
+        self.wake_first_waiter(|waiter| waiter.payload == Some(key))
+    }
+}
+
+/// A snapshot of a `List`'s counters, returned by `List::metrics`.
+#[cfg(feature = "metrics")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ListMetrics {
+    /// Number of waiters queued when the snapshot was taken.
+    pub current_len: usize,
+    /// See `List::max_len`.
+    pub max_len: usize,
+    /// Number of times a waiter was newly queued. Refreshing the waker of an elem already queued
+    /// doesn't count.
+    pub total_enqueues: u64,
+    /// Number of waiters taken off the list to be woken.
+    pub total_wakes: u64,
+    /// See `List::waker_clones`.
+    pub waker_clones: u64,
+    /// See `List::waker_reuses`.
+    pub waker_reuses: u64,
+}
+
+/// Stores a clone of `waker` in `slot`, unless the waker already there wakes the same task.
+///
+/// Returns true if the waker was cloned. Shared by `List` and `SingleWaiter`, so both coalesce
+/// the wakers of a task polled again in the same way.
+#[inline]
+pub(crate) fn refresh_waker(slot: &mut Option<Waker>, waker: &Waker) -> bool {
+    match slot {
+        Some(w) if w.will_wake(waker) => false,
+        _ => {
+            *slot = Some(waker.clone());
+            true
+        }
+    }
+}
+
+/// Panics if a queued waiter is not the one its list queued: its generation or address differs
+/// from the ones stamped when it was queued. That is the mark of a stale handle, an elem moved
+/// after being queued or the memory of one dropped while queued reused for another, whose
+/// removal would otherwise corrupt the list.
+///
+/// # Safety
+///
+/// `ptr` must point to a valid waiter.
+#[cfg(any(debug_assertions, feature = "validate"))]
+unsafe fn check_stamp<T>(ptr: *const Waiter<T>) {
+    let (generation, addr) = (*ptr).queued_as;
+    assert!(
+        generation == (*ptr).generation && addr == ptr as usize,
+        "stale elem: generation {} at {:p} was queued as generation {} at {:#x}",
+        (*ptr).generation,
+        ptr,
+        generation,
+        addr
+    );
+}
+
+/// Marks the waiter, just popped from its list, as no longer queued and wakes its task.
+///
+/// Returns true if the task was woken. A queued waiter always has a waker, but should that ever
+/// not hold, the waiter is skipped: debug builds panic, release builds return false so the
+/// caller can carry on with the next waiter.
+///
+/// # Safety
+///
+/// The caller must hold the list the waiter was popped from mutably.
+unsafe fn wake_waiter<T>(waiter: NonNull<Waiter<T>>) -> bool {
+    match take_waker(waiter) {
+        Some(waker) => {
+            waker.wake();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Marks the waiter, just popped from its list, as no longer queued and notified, handing back
+/// its waker for the caller to wake.
+///
+/// Returns None, panicking in debug builds, if the waiter has no waker; see `wake_waiter`.
+///
+/// # Safety
+///
+/// The caller must hold the list the waiter was popped from mutably.
+unsafe fn take_waker<T>(mut waiter: NonNull<Waiter<T>>) -> Option<Waker> {
+    let waiter = waiter.as_mut();
+
+    assert!(waiter.queued);
+    waiter.queued = false;
+    #[cfg(feature = "leak-detect")]
+    debug::dequeued(waiter as *mut Waiter<T> as usize);
+
+    match waiter.waker.take() {
+        Some(waker) => {
+            waiter.notified = true;
+            Some(waker)
+        }
+        None => {
+            debug_assert!(false, "queued waiter has no waker");
+            None
+        }
+    }
+}
+
+pub struct Elem<T = ()> {
+    waiter: UnsafeCell<Waiter<T>>,
+}
+
+impl<T> Elem<T> {
+    /// # Safety
+    ///
+    /// Constructing an Elem is only safe if the `remove_waiter` method on the list it is designed
+    /// for gets called from the drop method of the type that embeds Elem.
+    /// Failure to do so leads to UB.
+    ///
+    /// Refer to the unit test below for an example.
+    pub unsafe fn new() -> Elem<T> {
+        Elem {
+            waiter: UnsafeCell::new(Waiter {
+                queued: false,
+                notified: false,
+                #[cfg(any(debug_assertions, feature = "validate"))]
+                list_id: 0,
+                #[cfg(any(debug_assertions, feature = "validate"))]
+                generation: NEXT_GENERATION.fetch_add(1, core::sync::atomic::Ordering::Relaxed),
+                #[cfg(any(debug_assertions, feature = "validate"))]
+                queued_as: (0, 0),
+                waker: None,
+                priority: 0,
+                #[cfg(all(feature = "metrics", feature = "std"))]
+                enqueued_at: None,
+                payload: None,
+                #[cfg(feature = "leak-detect")]
+                live_id: 0,
+                pointers: linked_list::Pointers::new(),
+                _p: PhantomPinned,
+            }),
+        }
+    }
+
+    /// Creates an elem for a future that is `Unpin`, like the tests' `Bar`, whose elem can
+    /// still move until it is first queued.
+    ///
+    /// The elem is the same as one from `new`, but with the `leak-detect` feature it is also
+    /// recorded as live by the creating thread until it is dropped. An elem never dropped,
+    /// typically one leaked along with a future whose drop never ran and so never called
+    /// `remove_waiter`, is reported by `debug::assert_unpin_elems_dropped`. The elem must be
+    /// dropped on the thread that created it, or it is reported too.
+    ///
+    /// # Safety
+    ///
+    /// The same as for `new`.
+    pub unsafe fn new_for_unpin_future() -> Elem<T> {
+        let elem = Elem::new();
+        #[cfg(feature = "leak-detect")]
+        elem.waiter.with_mut(|ptr| (*ptr).live_id = debug::created());
+        elem
+    }
+}
+
+/// A payload that records which of several lists its elem is queued in, for a primitive, like a
+/// reader-writer lock, whose waiter may be parked in either of its lists depending on its state.
+///
+/// Queue the elem with `enqueue_waiter_with`, passing the tag for the list as the payload, and
+/// `Elem::remove_from_current` finds the list to remove it from, rather than the caller having
+/// to pick the right one.
+pub trait ListTag {
+    /// Returns the index of the list, among those passed to `remove_from_current`, that the elem
+    /// is queued in with this payload.
+    fn list_index(&self) -> usize;
+}
+
+impl<T: ListTag> Elem<T> {
+    /// Removes the elem from whichever of `lists` its payload's tag names, as `remove_waiter`
+    /// does.
+    ///
+    /// Returns the index of the list it was removed from, or None if it wasn't queued, having
+    /// been woken or never queued. Panics if the elem is queued without a payload, or with a tag
+    /// past the end of `lists`.
+    ///
+    /// # Safety
+    ///
+    /// The same as for `remove_waiter`, with `lists` standing in for the one list: if the elem is
+    /// queued, it must be queued in the list its tag names.
+    pub unsafe fn remove_from_current(&self, lists: &mut [&mut List<T>]) -> Option<usize> {
+        // Safety: the caller holds all the lists the elem may be queued in mutably.
+        let tag = self.waiter.with(|ptr| unsafe {
+            (*ptr).queued.then(|| (*ptr).payload.as_ref().map(T::list_index))
+        });
+        let index = tag?.expect("queued elem has no list tag");
+        lists[index].remove_waiter(self);
+        Some(index)
+    }
+}
+
+impl<T> Drop for Elem<T> {
+    fn drop(&mut self) {
+        // For those embedding this code into their source, if you understand the risks,
+        // you may want to change this assert to a debug_assert, or remove it entirely.
+        //
+        // This function cannot be used to remove an element from a list, but it can trigger a
+        // panic if it detects it was left in a list.
+        let queued = self.waiter.with(|ptr| unsafe { (*ptr).queued });
+        #[cfg(feature = "leak-detect")]
+        self.waiter.with(|ptr| unsafe {
+            debug::dropped(ptr as usize, queued);
+            if (*ptr).live_id != 0 {
+                debug::settled((*ptr).live_id);
+            }
+        });
+        assert!(!queued);
+    }
+}
+
+/// A list of waiters, each with a deadline, kept sorted so the earliest deadline is the next to
+/// be woken.
+///
+/// The deadline is stored as the elem's payload. Queuing is O(n) as the list is walked to find
+/// the elem's place; finding and waking the earliest deadlines is O(1) per waiter. Waiters with
+/// equal deadlines are woken in the order they were queued.
+///
+/// The same `remove_waiter` rules as for `List` apply. Only available with the `std` feature, for
+/// `Instant`.
+#[cfg(feature = "std")]
+pub struct DeadlineList {
+    list: List<Instant>,
+}
+
+#[cfg(feature = "std")]
+impl DeadlineList {
+    pub fn new() -> DeadlineList {
+        DeadlineList { list: List::new() }
+    }
+
+    /// Queues `elem` to be woken once `deadline` has passed, storing the waker from `cx` in it.
+    ///
+    /// An elem that is already queued with a different deadline is moved to its new place.
+    /// Returns true if the elem was newly queued.
+    pub fn enqueue_waiter(
+        &mut self,
+        elem: &Elem<Instant>,
+        cx: &mut Context<'_>,
+        deadline: Instant,
+    ) -> bool {
+        if self.list.is_queued(elem) {
+            if self.list.payload(elem) == Some(&deadline) {
+                return self.list.enqueue_waiter(elem, cx);
+            }
+            // Safety: an elem queued through this list can only be queued in `self.list`.
+            unsafe { self.list.remove_waiter(elem) };
+            self.insert(elem, cx, deadline);
+            return false;
+        }
+        self.insert(elem, cx, deadline);
+        true
+    }
+
+    fn insert(&mut self, elem: &Elem<Instant>, cx: &mut Context<'_>, deadline: Instant) {
+        // Safety: the mutable reference is held for the duration of the element change.
+        elem.waiter.with_mut(|ptr| unsafe { (*ptr).payload = Some(deadline) });
+        self.list.insert_sorted(elem, cx, 0, |waiter| waiter.payload <= Some(deadline));
+    }
+
+    /// Removes `elem` from the list. See `List::remove_waiter`.
+    ///
+    /// # Safety
+    ///
+    /// The same as for `List::remove_waiter`.
+    pub unsafe fn remove_waiter(&mut self, elem: &Elem<Instant>) {
+        self.list.remove_waiter(elem);
+    }
+
+    /// Wakes every waiter whose deadline is at or before `now`, earliest first, removing them
+    /// from the list.
+    ///
+    /// Returns the number of waiters woken.
+    pub fn wake_expired(&mut self, now: Instant) -> usize {
+        self.list.wake_while_waiter(|waiter| waiter.payload <= Some(now))
+    }
+
+    /// Takes the wakers of every waiter whose deadline is at or before `now`, earliest first,
+    /// adding them to `wakers` and removing the waiters from the list, without waking any of
+    /// them.
+    ///
+    /// Returns the number of wakers added. The form of `wake_expired` for a list behind a lock,
+    /// whose wakes are left until the lock is released.
+    pub fn drain_expired_into(&mut self, now: Instant, wakers: &mut WakeList) -> usize {
+        let mut taken = 0;
+        while self.list.peek_next().is_some_and(|waiter| waiter.payload <= Some(now)) {
+            let waiter = self.list.pop_next().unwrap();
+            // Safety: the mutable reference is held for the duration of the list and element
+            // changes.
+            if let Some(waker) = unsafe { take_waker(waiter) } {
+                wakers.push(waker);
+                taken += 1;
+            }
+        }
+        taken
+    }
+
+    /// Returns the earliest deadline of the queued waiters, or None if the list is empty.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.list.peek_next().and_then(|waiter| waiter.payload)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for DeadlineList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A `List` aligned to, and so starting, its own 64 byte cache line.
+///
+/// Lists that are changed from different threads and sit next to each other in a struct, as the
+/// reader and writer lists of a lock do, can otherwise share a cache line, so each thread's
+/// changes evict the other's. Derefs to the `List`.
+#[repr(align(64))]
+pub struct CachePadded<T = ()> {
+    list: List<T>,
+}
+
+impl<T> CachePadded<T> {
+    pub fn new(list: List<T>) -> CachePadded<T> {
+        CachePadded { list }
+    }
+
+    pub fn into_inner(self) -> List<T> {
+        self.list
+    }
+}
+
+impl<T> Default for CachePadded<T> {
+    fn default() -> Self {
+        Self::new(List::new())
+    }
+}
+
+impl<T> core::ops::Deref for CachePadded<T> {
+    type Target = List<T>;
+
+    fn deref(&self) -> &List<T> {
+        &self.list
+    }
+}
+
+impl<T> core::ops::DerefMut for CachePadded<T> {
+    fn deref_mut(&mut self) -> &mut List<T> {
+        &mut self.list
+    }
+}
+
+/// An `Elem` bundled with the list it belongs to, removing itself from that list when dropped.
+///
+/// A future can embed a `WaiterGuard` in place of a bare `Elem` and then needs no `Drop` of its
+/// own: the guard always removes its elem from the right list, so neither of the
+/// `remove_waiter` footguns can be hit.
+pub struct WaiterGuard<'a, T = ()> {
+    list: &'a RefCell<List<T>>,
+    elem: Elem<T>,
+}
+
+impl<'a, T> WaiterGuard<'a, T> {
+    /// # Safety
+    ///
+    /// Once `enqueue` has been called, the guard must not be moved until it is dropped, just as
+    /// if it were pinned. The list holds a pointer to the elem inside the guard.
+    pub unsafe fn new(list: &'a RefCell<List<T>>) -> WaiterGuard<'a, T> {
+        WaiterGuard {
+            list,
+            // Safety: the elem is removed from `list` by the guard's drop.
+            elem: Elem::new(),
+        }
+    }
+
+    /// Queues the guard's elem in its list with the waker from `cx`. See `List::enqueue_waiter`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the list is already borrowed.
+    pub fn enqueue(&self, cx: &mut Context<'_>) -> bool {
+        self.list.borrow_mut().enqueue_waiter(&self.elem, cx)
+    }
+
+    /// Returns true if the guard's elem is currently queued.
+    pub fn is_queued(&self) -> bool {
+        self.list.borrow().is_queued(&self.elem)
+    }
+}
+
+impl<'a, T> Drop for WaiterGuard<'a, T> {
+    fn drop(&mut self) {
+        // Safety: the elem can only ever have been queued in `self.list`.
+        unsafe {
+            self.list.borrow_mut().remove_waiter(&self.elem);
+        }
+    }
+}
+
+/// A safe, pinned alternative to a bare `Elem` for a future that waits on a single list.
+///
+/// The elem can only be queued through `Pin<&Self>`, so it is pinned from the moment the list
+/// holds a pointer to it, and the pin guarantee means the `PinnedWaiter` is dropped before its
+/// memory is reused. Its drop removes the elem from the list it was created with, which is
+/// borrowed for `'a` and so outlives it. `RefCell` gives the exclusive access the list
+/// operations need and keeps the whole arrangement on one thread. Together these close both
+/// `remove_waiter` footguns.
+///
+/// The `List` value inside the `RefCell` can still be replaced, with `RefCell::replace` or
+/// `mem::swap` say, while the elem is queued in it. The waiter remembers the id of the list it
+/// queued its elem in and checks it against the list it finds. Finding another list while the
+/// elem is still queued, it aborts the process: the elem is linked into a list it can no longer
+/// reach, and unwinding would free it with that list still pointing at it. A list replaced once
+/// the elem has been woken, or before it is queued, is fine.
+///
+/// A future embedding a `PinnedWaiter` is itself `!Unpin` and reaches the waiter by pin
+/// projection, which takes `unsafe` unless a crate like `pin-project` is used:
+///
+/// ```
+/// use broadcast_rs::waiter::{List, PinnedWaiter};
+/// use std::cell::RefCell;
+/// use std::future::Future;
+/// use std::pin::{pin, Pin};
+/// use std::task::{Context, Poll, Waker};
+///
+/// struct Wait<'a> {
+///     waiter: PinnedWaiter<'a>,
+/// }
+///
+/// impl<'a> Future for Wait<'a> {
+///     type Output = ();
+///
+///     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+///         // Safety: `waiter` is structurally pinned, it is never moved out of `Wait`.
+///         let waiter = unsafe { self.map_unchecked_mut(|me| &mut me.waiter) };
+///         waiter.into_ref().poll_enqueue(cx)
+///     }
+/// }
+///
+/// let list = RefCell::new(List::new());
+/// let mut wait = pin!(Wait { waiter: PinnedWaiter::new(&list) });
+/// let mut cx = Context::from_waker(Waker::noop());
+/// assert!(wait.as_mut().poll(&mut cx).is_pending());
+/// assert_eq!(list.borrow_mut().awake_waiters(), 1);
+/// assert!(wait.as_mut().poll(&mut cx).is_ready());
+/// ```
+pub struct PinnedWaiter<'a, T = ()> {
+    list: &'a RefCell<List<T>>,
+    registration: Registration<T>,
+}
+
+impl<'a, T> PinnedWaiter<'a, T> {
+    pub fn new(list: &'a RefCell<List<T>>) -> PinnedWaiter<'a, T> {
+        PinnedWaiter {
+            list,
+            // Safety: the elem is only queued while pinned and is removed from `list` by the
+            // waiter's drop, which aborts if `list` no longer holds the list it is queued in.
+            registration: unsafe { Registration::new() },
+        }
+    }
+
+    /// Returns true if the elem is queued.
+    fn is_queued(&self) -> bool {
+        // Safety: lists aren't `Send`, so the list the elem is queued in, if any, is on this
+        // thread and not in the middle of changing it.
+        self.registration.elem.waiter.with(|ptr| unsafe { (*ptr).queued })
+    }
+
+    /// Aborts if the elem is queued in a list other than `list`, the list the waiter's `RefCell`
+    /// now holds.
+    fn check_list(&self, list: &List<T>) {
+        if self.registration.list_id.get() != list.id && self.is_queued() {
+            abort("PinnedWaiter's list was replaced while its elem was queued");
+        }
+    }
+
+    /// Queues the elem with the waker from `cx` the first time it is called and returns
+    /// `Poll::Pending`. Later calls return `Poll::Ready` once the elem has been woken, otherwise
+    /// they refresh the waker and return `Poll::Pending` again.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the list is already borrowed.
+    pub fn poll_enqueue(self: Pin<&Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.is_ready() {
+            return Poll::Ready(());
+        }
+        self.registration.register(&mut self.list.borrow_mut(), cx);
+        Poll::Pending
+    }
+
+    /// Returns true if the elem has been queued and since been woken.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the list is mutably borrowed.
+    pub fn is_ready(&self) -> bool {
+        let list = self.list.borrow();
+        self.check_list(&list);
+        self.registration.is_registered() && !self.is_queued()
+    }
+}
+
+impl<'a, T> Drop for PinnedWaiter<'a, T> {
+    fn drop(&mut self) {
+        // A queued elem can't be left linked while its memory is freed, so a list that can't be
+        // borrowed, or isn't the one the elem is queued in, aborts rather than panics.
+        match self.list.try_borrow_mut() {
+            Ok(mut list) => {
+                self.check_list(&list);
+                // Safety: the elem, if queued, is queued in `list`.
+                unsafe { list.remove_waiter(self.registration.elem()) };
+            }
+            Err(_) if self.is_queued() => abort("PinnedWaiter dropped while its list is borrowed"),
+            Err(_) => {}
+        }
+    }
+}
+
+/// The elem a future waits with and the record of whether it is registered in its primitive's
+/// list, the state every future that waits on a `List` keeps.
+///
+/// A future embeds one in place of a bare `Elem` and reaches it through `&Self`, which its
+/// `Pin<&mut Self>` derefs to, so it needs no pin projection of its own. The elem is never
+/// handed out by `&mut` or moved out, so it stays pinned for as long as the future is, and the
+/// `PhantomPinned` in the elem keeps the future `!Unpin`.
+///
+/// Registered means whatever the future takes it to mean between `register` and `unregister`:
+/// typically that the elem was queued and what it was woken for has not been taken yet, the
+/// future's drop having to undo it otherwise.
+pub(crate) struct Registration<T = ()> {
+    elem: Elem<T>,
+
+    /// Id of the list the elem was last registered in, or zero while it is not registered.
+    list_id: Cell<usize>,
+}
+
+impl<T> Registration<T> {
+    /// # Safety
+    ///
+    /// The same as for `Elem::new`: the drop of the type embedding the registration must remove
+    /// its elem from the list it was registered in.
+    pub(crate) unsafe fn new() -> Registration<T> {
+        Registration {
+            elem: Elem::new(),
+            list_id: Cell::new(0),
+        }
+    }
+
+    pub(crate) fn elem(&self) -> &Elem<T> {
+        &self.elem
+    }
+
+    pub(crate) fn is_registered(&self) -> bool {
+        self.list_id.get() != 0
+    }
+
+    /// Queues the elem in `list` with the waker from `cx`, or refreshes its waker if it is
+    /// already queued there, and marks it registered. See `List::enqueue_waiter`.
+    pub(crate) fn register(&self, list: &mut List<T>, cx: &mut Context<'_>) -> bool {
+        self.list_id.set(list.id);
+        list.enqueue_waiter(&self.elem, cx)
+    }
+
+    /// As `register`, storing `payload` in the elem first. See `List::enqueue_waiter_with`.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn register_with(
+        &self,
+        list: &mut List<T>,
+        cx: &mut Context<'_>,
+        payload: T,
+    ) -> bool {
+        self.list_id.set(list.id);
+        list.enqueue_waiter_with(&self.elem, cx, payload)
+    }
+
+    /// Marks the elem no longer registered, once the future has taken what it was woken for.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn unregister(&self) {
+        self.list_id.set(0);
+    }
+}
+
+/// Aborts the process with `msg`, for a state that unwinding would leave unsound. A panic while
+/// another unwinds aborts, which needs nothing from `std`.
+#[cold]
+fn abort(msg: &str) -> ! {
+    struct PanicOnDrop<'a>(&'a str);
+
+    impl Drop for PanicOnDrop<'_> {
+        fn drop(&mut self) {
+            panic!("{}", self.0);
+        }
+    }
+
+    let _bomb = PanicOnDrop(msg);
+    panic!("{}", msg);
+}
+
+// Waiter has been copied from broadcast.rs.
+
+/// An entry in the wait queue.
+struct Waiter<T> {
+    /// True if queued.
+    queued: bool,
+
+    /// True if the waiter was woken by the list and has not been queued again since.
+    notified: bool,
+
+    /// Id of the list that last queued the waiter, zero if it has never been queued.
+    #[cfg(any(debug_assertions, feature = "validate"))]
+    list_id: usize,
+
+    /// Generation stamped by `Elem::new`, unique to the elem.
+    #[cfg(any(debug_assertions, feature = "validate"))]
+    generation: usize,
+
+    /// Generation and address the waiter had when last queued.
+    #[cfg(any(debug_assertions, feature = "validate"))]
+    queued_as: (usize, usize),
+
+    /// Future waiting to be awoken (with awake_waiters).
+    waker: Option<Waker>,
+
+    /// Priority the waiter was queued with. Higher priorities are woken first.
+    priority: u8,
+
+    /// When the waiter was last newly queued. Refreshing its waker doesn't change it.
+    #[cfg(all(feature = "metrics", feature = "std"))]
+    enqueued_at: Option<Instant>,
+
+    /// Value carried with the waiter, set when enqueued or handed over when woken.
+    payload: Option<T>,
+
+    /// Id the elem is recorded as live under by `Elem::new_for_unpin_future`, or 0 if it isn't.
+    #[cfg(feature = "leak-detect")]
+    live_id: usize,
+
+    /// Intrusive linked-list pointers.
+    pointers: linked_list::Pointers<Waiter<T>>,
+
+    /// Should not be `Unpin`.
+    _p: PhantomPinned,
+}
+
+generate_addr_of_methods! {
+    impl<T> Waiter<T> {
+        unsafe fn addr_of_pointers(self: NonNull<Self>) -> NonNull<linked_list::Pointers<Waiter<T>>> {
+            &self.pointers
+        }
+    }
+}
+
+/// # Safety
+///
+/// `Waiter` is required and forced to be !Unpin.
+unsafe impl<T> linked_list::Link for Waiter<T> {
+    type Handle = NonNull<Waiter<T>>;
+    type Target = Waiter<T>;
+
+    fn as_raw(handle: &NonNull<Waiter<T>>) -> NonNull<Waiter<T>> {
+        *handle
+    }
+
+    unsafe fn from_raw(ptr: NonNull<Waiter<T>>) -> NonNull<Waiter<T>> {
+        ptr
+    }
+
+    unsafe fn pointers(target: NonNull<Waiter<T>>) -> NonNull<linked_list::Pointers<Waiter<T>>> {
+        Waiter::addr_of_pointers(target)
+    }
+}
+
+/// Tracking of queued elems, to catch one dropped without `remove_waiter`.
+///
+/// With the `leak-detect` feature, each thread keeps a registry of the elems it has queued that
+/// have not since been removed or woken. An elem dropped while still in its thread's registry
+/// is recorded as leaked before its drop panics. The drop's panic can be lost, caught by a
+/// runtime running the future as a task, say, but the record is kept for
+/// `assert_no_leaked_waiters` to find.
+///
+/// The registries are per thread: an elem queued on one thread and removed or woken on another
+/// leaves a stale entry behind, which is cleared when the elem is dropped.
+///
+/// The elems made with `Elem::new_for_unpin_future` are also recorded from their creation until
+/// their drop, so `assert_unpin_elems_dropped` can find one never dropped at all.
+#[cfg(feature = "leak-detect")]
+pub mod debug {
+    use std::cell::RefCell;
+    use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+    use std::vec::Vec;
+
+    /// Source of the ids of the elems recorded as live, starting at 1 as 0 means untracked.
+    static NEXT_LIVE_ID: AtomicUsize = AtomicUsize::new(1);
+
+    std::thread_local! {
+        /// Addresses of the elems queued on this thread and not yet dequeued.
+        static QUEUED: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+
+        /// Addresses of the elems dropped on this thread while still queued.
+        static LEAKED: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+
+        /// Ids of the elems made by `Elem::new_for_unpin_future` on this thread and not yet
+        /// dropped.
+        static LIVE: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Records a new elem as live, returning its id.
+    pub(super) fn created() -> usize {
+        let id = NEXT_LIVE_ID.fetch_add(1, Relaxed);
+        let _ = LIVE.try_with(|live| live.borrow_mut().push(id));
+        id
+    }
+
+    pub(super) fn settled(id: usize) {
+        let _ = LIVE.try_with(|live| {
+            let mut live = live.borrow_mut();
+            if let Some(i) = live.iter().rposition(|&entry| entry == id) {
+                live.swap_remove(i);
+            }
+        });
+    }
+
+    pub(super) fn queued(addr: usize) {
+        let _ = QUEUED.try_with(|queued| queued.borrow_mut().push(addr));
+    }
+
+    /// Takes `addr` out of the registry, returning true if it was there.
+    pub(super) fn dequeued(addr: usize) -> bool {
+        QUEUED
+            .try_with(|queued| {
+                let mut queued = queued.borrow_mut();
+                match queued.iter().rposition(|&entry| entry == addr) {
+                    Some(i) => {
+                        queued.swap_remove(i);
+                        true
+                    }
+                    None => false,
+                }
+            })
+            .unwrap_or(false)
+    }
+
+    pub(super) fn dropped(addr: usize, queued: bool) {
+        if dequeued(addr) && queued {
+            let _ = LEAKED.try_with(|leaked| leaked.borrow_mut().push(addr));
+        }
+    }
+
+    /// Panics if an elem was dropped on this thread while still queued, its future never having
+    /// called `remove_waiter`. The record of the leaks is cleared by the call.
+    ///
+    /// Meant for the end of a test of code built on the waiter list.
+    #[track_caller]
+    pub fn assert_no_leaked_waiters() {
+        let leaked = LEAKED.with(|leaked| leaked.take());
+        assert!(
+            leaked.is_empty(),
+            "{} waiter elem(s) dropped while queued, without remove_waiter, at {:x?}",
+            leaked.len(),
+            leaked
+        );
+    }
+
+    /// Panics if an elem made with `Elem::new_for_unpin_future` on this thread has not been
+    /// dropped, as when its future is leaked and so never calls `remove_waiter`. The record of
+    /// the elems is cleared by the call.
+    ///
+    /// Meant for the end of a test, once all the futures it made are done with.
+    #[track_caller]
+    pub fn assert_unpin_elems_dropped() {
+        let live = LIVE.with(|live| live.take());
+        assert!(live.is_empty(), "{} unpin waiter elem(s) never dropped", live.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::pin::Pin;
+    use core::task::Poll;
+    use core::cell::RefCell;
+    use core::future::Future;
+    use std::rc::Rc;
+    use crate::test_util::CountingWaker;
+
+    #[derive(Clone, Default)]
+    /// Foo is the manager of the Futures.
+    struct Foo {
+        list: Rc<RefCell<List>>,
+    }
+    /// Bar is the Future. It may not outlive its manager.
+    struct Bar<'a> {
+        foo: &'a Foo,
+        countdown: usize,
+        elem: Elem,
+    }
+    // Define Bar to be Unpin, despite the Foo reference. The Foo reference is not a self
+    // reference. The Future in this case must be Unpin to allow its countdown field to be
+    // modified when it is polled.
+    impl<'a> Unpin for Bar<'a> {}
+
+    impl Foo {
+        fn new() -> Foo {
+            Foo {
+                list: Default::default(),
+            }
+        }
+        /// Returns a Future that will become ready once its polled countdown is reached.
+        fn bar<'a>(&'a self, countdown: usize) -> Bar<'a> {
+            Bar {
+                foo: self,
+                countdown,
+                // Safety: This Elem is removed by Bar's drop: list.remove_waiter(&self.elem).
+                elem: unsafe { Elem::new_for_unpin_future() },
+            }
+        }
+    }
+
+    impl<'a> Future for Bar<'a> {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.countdown == 0 {
+                return Poll::Ready(());
+            }
+            self.countdown -= 1;
+            let mut list = self.foo.list.borrow_mut();
+            list.enqueue_waiter(&self.elem, cx);
+            validate(&list);
+            Poll::Pending
+        }
+    }
+
+    impl<'a> Drop for Bar<'a> {
+        fn drop(&mut self) {
+            let mut list = self.foo.list.borrow_mut();
+            // Safety: the one unsafe call required by this `waiter` module model.
+            // It is unsafe because we may not forget to call it in this Future's drop
+            // and because when we do call it, the correct List manager must be used.
+            unsafe {
+                list.remove_waiter(&self.elem);
+            }
+            validate(&list);
+        }
+    }
+
+    /// Checks the list's invariants, when `List::validate` is compiled in.
+    fn validate<T>(_list: &List<T>) {
+        #[cfg(any(debug_assertions, feature = "validate"))]
+        _list.validate();
+    }
+
+    /// Drives a future by hand, as `tokio_test::task` does, so the list can be tested without
+    /// depending on any runtime.
+    mod task {
+        use crate::test_util::CountingWaker;
+        use std::future::Future;
+        use std::ops::Deref;
+        use std::pin::Pin;
+        use std::task::{Context, Poll};
+
+        pub(super) fn spawn<F: Future>(future: F) -> Spawn<F> {
+            Spawn {
+                future: Box::pin(future),
+                counter: CountingWaker::new(),
+                wakes_at_poll: 0,
+            }
+        }
+
+        /// A future with the waker it is polled with.
+        pub(super) struct Spawn<F> {
+            future: Pin<Box<F>>,
+            counter: CountingWaker,
+            /// The wake count when the future was last polled.
+            wakes_at_poll: usize,
+        }
+
+        impl<F: Future> Spawn<F> {
+            pub(super) fn poll(&mut self) -> Poll<F::Output> {
+                self.enter(|cx, future| future.poll(cx))
+            }
+
+            /// Calls `f` with the future and a context for its waker, as if from within a poll.
+            /// Clears the woken flag, as a poll would.
+            pub(super) fn enter<R>(
+                &mut self,
+                f: impl FnOnce(&mut Context<'_>, Pin<&mut F>) -> R,
+            ) -> R {
+                self.wakes_at_poll = self.counter.wake_count();
+                let waker = self.counter.waker();
+                f(&mut Context::from_waker(&waker), self.future.as_mut())
+            }
+
+            /// Returns true if the waker has been woken since the last poll.
+            pub(super) fn is_woken(&self) -> bool {
+                self.counter.wake_count() > self.wakes_at_poll
+            }
+        }
+
+        impl<F> Deref for Spawn<F> {
+            type Target = F;
+
+            fn deref(&self) -> &F {
+                &self.future
+            }
+        }
+    }
+
+    /// Baz is the same Future as Bar, but it embeds a WaiterGuard and so needs no Drop.
+    struct Baz<'a> {
+        countdown: usize,
+        guard: WaiterGuard<'a>,
+    }
+    // As with Bar, Baz is declared Unpin so its countdown can be modified when polled. Baz is
+    // only ever polled while pinned in the tests.
+    impl<'a> Unpin for Baz<'a> {}
+
+    impl<'a> Future for Baz<'a> {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.countdown == 0 {
                 return Poll::Ready(());
             }
-            self.countdown -= 1;
-            self.foo.list.borrow_mut().enqueue_waiter(&self.elem, cx);
+            self.countdown -= 1;
+            self.guard.enqueue(cx);
+            Poll::Pending
+        }
+    }
+
+    fn baz(list: &RefCell<List>, countdown: usize) -> Baz<'_> {
+        Baz {
+            countdown,
+            // Safety: Baz is not moved once polled.
+            guard: unsafe { WaiterGuard::new(list) },
+        }
+    }
+
+    #[test]
+    fn waiter_guard_removes_on_drop() {
+        let list = RefCell::new(List::new());
+        let mut a = task::spawn(baz(&list, 1));
+        let mut b = task::spawn(baz(&list, 1));
+        let mut c = task::spawn(baz(&list, 1));
+        assert!(a.poll().is_pending());
+        assert!(b.poll().is_pending());
+        assert!(c.poll().is_pending());
+        assert!(b.guard.is_queued());
+        assert_eq!(list.borrow().len(), 3);
+
+        // Dropping a future mid-wait takes it out of the list without panicking.
+        drop(b);
+        assert_eq!(list.borrow().len(), 2);
+        assert_eq!(list.borrow().len_backwards(), 2);
+
+        assert_eq!(list.borrow_mut().awake_waiters(), 2);
+        assert!(a.is_woken() && c.is_woken());
+        assert!(a.poll().is_ready());
+        drop(c);
+        assert!(list.borrow().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "expected 1, len 0, len_backwards 0")]
+    fn assert_len_panics_on_mismatch() {
+        List::<()>::new().assert_len(1);
+    }
+
+    #[test]
+    #[cfg(any(debug_assertions, feature = "validate"))]
+    #[should_panic(expected = "stale elem")]
+    fn stale_elem_panics() {
+        use core::mem::ManuallyDrop;
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut list: List = List::new();
+        // Both are leaked, queued, when the test panics.
+        // Safety: the elem is never dropped.
+        let elem = ManuallyDrop::new(Box::new(unsafe { Elem::new() }));
+        assert!(list.enqueue_waiter(&elem, &mut cx));
+
+        // Move the queued elem, as an unpinned future might be moved once polled, leaving the
+        // list pointing at its old place. The moved elem is a stale handle.
+        let moved = ManuallyDrop::new(unsafe { core::ptr::read(&**elem) });
+        unsafe { list.remove_waiter(&moved) };
+    }
+
+    #[test]
+    fn remove_unqueued_elem_from_other_list() {
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut a: List = List::new();
+        let mut b: List = List::new();
+        // Safety: the elem is woken out of, or removed from, each list before it is dropped.
+        let elem = unsafe { Elem::new() };
+
+        // Woken out of `a`, the elem is in no list, so removing it from `b` is a no-op.
+        assert!(a.enqueue_waiter(&elem, &mut cx));
+        assert!(a.wake_one());
+        unsafe { b.remove_waiter(&elem) };
+
+        // Queued again, this time in `b`, it is removed from `b`, and from `a` is a no-op.
+        assert!(b.enqueue_waiter(&elem, &mut cx));
+        unsafe { b.remove_waiter(&elem) };
+        unsafe { a.remove_waiter(&elem) };
+        assert!(a.is_empty() && b.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "leak-detect")]
+    fn leak_detect_catches_undropped_unpin_elem() {
+        use std::panic::catch_unwind;
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut list: List = List::new();
+        // Safety: the elem is removed from the list before it is dropped.
+        let elem = unsafe { Elem::new_for_unpin_future() };
+        assert!(list.enqueue_waiter(&elem, &mut cx));
+        unsafe { list.remove_waiter(&elem) };
+        drop(elem);
+        debug::assert_unpin_elems_dropped();
+
+        // Safety: the elem is never dropped, and the list, which has no drop, is never walked
+        // again.
+        let elem = Box::new(unsafe { Elem::new_for_unpin_future() });
+        assert!(list.enqueue_waiter(&elem, &mut cx));
+        // The future's drop, and so its remove_waiter, never runs.
+        core::mem::forget(elem);
+        assert!(catch_unwind(debug::assert_unpin_elems_dropped).is_err());
+        // The record was cleared by the failed assert.
+        debug::assert_unpin_elems_dropped();
+    }
+
+    #[test]
+    #[cfg(feature = "leak-detect")]
+    fn leak_detect_catches_forgotten_removal() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut list: List = List::new();
+
+        // Removed and woken elems are not leaks.
+        // Safety: the elems are removed from, or woken out of, the list before they are dropped.
+        let a = unsafe { Elem::new() };
+        let b = unsafe { Elem::new() };
+        assert!(list.enqueue_waiter(&a, &mut cx));
+        assert!(list.enqueue_waiter(&b, &mut cx));
+        unsafe { list.remove_waiter(&a) };
+        assert!(list.wake_one());
+        drop((a, b));
+        debug::assert_no_leaked_waiters();
+
+        // Safety: not upheld, on purpose. The list is not used again once the elem is dropped.
+        let c = Box::new(unsafe { Elem::new() });
+        assert!(list.enqueue_waiter(&c, &mut cx));
+        // The drop panics, but a runtime catching it would hide that.
+        assert!(catch_unwind(AssertUnwindSafe(|| drop(c))).is_err());
+        assert!(catch_unwind(debug::assert_no_leaked_waiters).is_err());
+        // The record was cleared by the failed assert.
+        debug::assert_no_leaked_waiters();
+    }
+
+    #[test]
+    fn reverse_flips_wake_order() {
+        let mut list: List<char> = List::new();
+        list.reverse();
+        assert!(list.is_empty());
+
+        let counters: Vec<CountingWaker> = (0..3).map(|_| CountingWaker::new()).collect();
+        // Safety: the elems are woken out of the list before they are dropped.
+        let elems: Vec<Elem<char>> = (0..3).map(|_| unsafe { Elem::new() }).collect();
+        for ((elem, counter), name) in elems.iter().zip(&counters).zip(['A', 'B', 'C']) {
+            let waker = counter.waker();
+            assert!(list.enqueue_waiter_with(elem, &mut Context::from_waker(&waker), name));
+        }
+        let order = |list: &List<char>| {
+            list.iter_payloads().map(|payload| *payload.unwrap()).collect::<String>()
+        };
+        assert_eq!(order(&list), "ABC");
+
+        list.reverse();
+        assert_eq!(order(&list), "CBA");
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.len_backwards(), 3);
+
+        // The waiters are woken C, B, then A.
+        let woken = |counters: &[CountingWaker]| {
+            counters.iter().map(|counter| counter.wake_count()).collect::<Vec<_>>()
+        };
+        assert!(list.wake_one());
+        assert_eq!(woken(&counters), [0, 0, 1]);
+        assert!(list.wake_one());
+        assert_eq!(woken(&counters), [0, 1, 1]);
+        assert!(list.wake_one());
+        assert_eq!(woken(&counters), [1, 1, 1]);
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn wake_key() {
+        let mut list: List<u64> = List::new();
+        let counters: Vec<CountingWaker> = (0..4).map(|_| CountingWaker::new()).collect();
+        // Safety: the elems are removed from, or woken out of, the list before they are dropped.
+        let elems: Vec<Elem<u64>> = (0..4).map(|_| unsafe { Elem::new() }).collect();
+        // Two waiters on key 7, one each on 3 and 9.
+        for ((elem, counter), key) in elems.iter().zip(&counters).zip([7, 3, 7, 9]) {
+            let waker = counter.waker();
+            assert!(list.enqueue_waiter_keyed(elem, &mut Context::from_waker(&waker), key));
+        }
+
+        assert!(!list.wake_key(5));
+        assert_eq!(list.len(), 4);
+
+        // Only the first of the two with key 7.
+        assert!(list.wake_key(7));
+        assert_eq!(counters.iter().map(|c| c.wake_count()).collect::<Vec<_>>(), [1, 0, 0, 0]);
+        assert_eq!(list.payload(&elems[0]), Some(&7));
+
+        assert!(list.wake_key(9));
+        assert!(list.wake_key(7));
+        assert!(!list.wake_key(7));
+        assert_eq!(counters.iter().map(|c| c.wake_count()).collect::<Vec<_>>(), [1, 0, 1, 1]);
+        assert_eq!(list.len(), 1);
+        assert_eq!(list.len_backwards(), 1);
+        assert!(list.is_queued(&elems[1]));
+
+        unsafe { list.remove_waiter(&elems[1]) };
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn await_waiters_synchronize() {
+        use tokio::task;
+
+        // Test that the waiters linked list grows to length three when three
+        // tasks should be awaiting.
+        // Test that calling awake_waiters then drains the list and test that
+        // the three tasks made the expected progress after getting through their
+        // await points.
+        let local = task::LocalSet::new();
+        // Run the local task set.
+        local.run_until(async move {
+            // The progress string will prove how far the various tasks have gotten
+            // between sync points.
+            let progress = Rc::new(RefCell::new(String::from("s")));
+            let foo = Foo::new();
+            for _ in 0..3 {
+                let foo = foo.clone();
+                let progress = progress.clone();
+                task::spawn_local(async move {
+                    *progress.borrow_mut() += "1";
+                    foo.bar(1).await; // Here we test, by awaiting the Future. awake_waiters
+                                      // will have to be called once below (because countdown
+                                      // is set to 1 here).
+                    *progress.borrow_mut() += "2";
+                });
+            }
+            while *progress.borrow() != "s111" {
+                task::yield_now().await;
+            }
+            foo.list.borrow().assert_len(3);
+            validate(&foo.list.borrow());
+            assert_eq!(foo.list.borrow_mut().awake_waiters(), 3);
+            validate(&foo.list.borrow());
+            foo.list.borrow().assert_len(0);
+            while *progress.borrow() != "s111222" {
+                task::yield_now().await;
+            }
+        }).await;
+    }
+
+    #[test]
+    fn drive_to_completion_by_hand() {
+        // The same as above, with no runtime: the future is polled directly with a waker that
+        // counts its wakes, and the list is awoken between polls.
+        let foo = Foo::new();
+        let mut bar = foo.bar(3);
+        let counter = CountingWaker::new();
+        let waker = counter.waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut polls = 0;
+        while Pin::new(&mut bar).poll(&mut cx).is_pending() {
+            polls += 1;
+            foo.list.borrow().assert_len(1);
+            assert_eq!(foo.list.borrow_mut().awake_waiters(), 1);
+            assert_eq!(counter.wake_count(), polls);
+        }
+        assert_eq!(polls, 3);
+        foo.list.borrow().assert_len(0);
+    }
+
+    #[test]
+    fn wake_one_in_fifo_order() {
+        // Enqueue three waiters and check that each wake_one call wakes the oldest remaining one.
+        let foo = Foo::new();
+        let mut a = task::spawn(foo.bar(1));
+        let mut b = task::spawn(foo.bar(1));
+        let mut c = task::spawn(foo.bar(1));
+        assert!(a.poll().is_pending());
+        assert!(b.poll().is_pending());
+        assert!(c.poll().is_pending());
+        assert_eq!(foo.list.borrow().len(), 3);
+
+        assert!(foo.list.borrow_mut().wake_one());
+        assert_eq!(foo.list.borrow().len(), 2);
+        validate(&foo.list.borrow());
+        assert!(a.is_woken() && !b.is_woken() && !c.is_woken());
+
+        assert!(foo.list.borrow_mut().wake_one());
+        assert_eq!(foo.list.borrow().len(), 1);
+        assert!(b.is_woken() && !c.is_woken());
+
+        assert!(foo.list.borrow_mut().wake_one());
+        assert_eq!(foo.list.borrow().len(), 0);
+        assert!(c.is_woken());
+
+        assert!(!foo.list.borrow_mut().wake_one());
+    }
+
+    fn wake_order(order: WakeOrder) -> Vec<usize> {
+        // Enqueue A, B, C in that order and return the order in which wake_one wakes them.
+        let foo = Foo {
+            list: Rc::new(RefCell::new(List::with_order(order))),
+        };
+        let mut futs: Vec<_> = (0..3).map(|_| task::spawn(foo.bar(1))).collect();
+        for fut in futs.iter_mut() {
+            assert!(fut.poll().is_pending());
+        }
+        let mut woken = vec![];
+        while foo.list.borrow_mut().wake_one() {
+            let i = (0..3)
+                .find(|i| !woken.contains(i) && futs[*i].is_woken())
+                .unwrap();
+            woken.push(i);
+        }
+        woken
+    }
+
+    #[test]
+    fn wake_order_modes() {
+        assert_eq!(List::<()>::new().order(), WakeOrder::Fifo);
+        assert_eq!(List::<()>::default().order(), WakeOrder::Fifo);
+        assert_eq!(wake_order(WakeOrder::Fifo), [0, 1, 2]);
+        assert_eq!(wake_order(WakeOrder::Lifo), [2, 1, 0]);
+    }
+
+    #[test]
+    fn peek_back_waker_then_wake_one() {
+        let foo = Foo::new();
+        assert!(foo.list.borrow().peek_back_waker().is_none());
+
+        let mut a = task::spawn(foo.bar(1));
+        let mut b = task::spawn(foo.bar(1));
+        assert!(a.poll().is_pending());
+        assert!(b.poll().is_pending());
+
+        // Peeking leaves the list alone.
+        let peeked = foo.list.borrow().peek_back_waker().unwrap();
+        assert_eq!(foo.list.borrow().len(), 2);
+        assert!(!a.is_woken() && !b.is_woken());
+
+        // wake_one wakes the task that was peeked, and the next peek sees the other task.
+        assert!(foo.list.borrow_mut().wake_one());
+        assert!(a.is_woken() && !b.is_woken());
+        let next = foo.list.borrow().peek_back_waker().unwrap();
+        assert!(!peeked.will_wake(&next));
+
+        next.wake();
+        assert!(b.is_woken());
+        assert_eq!(foo.list.borrow().len(), 1);
+        assert_eq!(foo.list.borrow_mut().awake_waiters(), 1);
+    }
+
+    #[test]
+    fn iter_wakers_in_wake_order() {
+        let foo = Foo::new();
+        assert_eq!(foo.list.borrow().iter_wakers().count(), 0);
+
+        let mut futs: Vec<_> = (0..3).map(|_| task::spawn(foo.bar(1))).collect();
+        for fut in futs.iter_mut() {
+            assert!(fut.poll().is_pending());
+        }
+
+        let wakers: Vec<Waker> = foo.list.borrow().iter_wakers().collect();
+        assert_eq!(wakers.len(), foo.list.borrow().len());
+        assert!(futs.iter().all(|fut| !fut.is_woken()));
+
+        // The first waker yielded is the one wake_one would wake.
+        assert!(wakers[0].will_wake(&foo.list.borrow().peek_back_waker().unwrap()));
+        for (i, waker) in wakers.into_iter().enumerate() {
+            waker.wake();
+            assert!(futs[i].is_woken());
+        }
+        assert_eq!(foo.list.borrow_mut().awake_waiters(), 3);
+    }
+
+    #[test]
+    #[cfg(feature = "metrics")]
+    fn max_len_high_water_mark() {
+        let foo = Foo::new();
+        assert_eq!(foo.list.borrow().max_len(), 0);
+
+        let mut futs: Vec<_> = (0..5).map(|_| task::spawn(foo.bar(2))).collect();
+        for (i, fut) in futs.iter_mut().enumerate() {
+            assert!(fut.poll().is_pending());
+            assert_eq!(foo.list.borrow().max_len(), i + 1);
+        }
+
+        // Polling an already queued future does not change the mark.
+        assert!(futs[0].poll().is_pending());
+        assert_eq!(foo.list.borrow().max_len(), 5);
+
+        assert_eq!(foo.list.borrow_mut().wake_n(2), 2);
+        drop(futs.remove(4));
+        assert_eq!(foo.list.borrow().len(), 2);
+        assert_eq!(foo.list.borrow().max_len(), 5);
+        assert_eq!(foo.list.borrow_mut().awake_waiters(), 2);
+        assert_eq!(foo.list.borrow().max_len(), 5);
+
+        foo.list.borrow_mut().reset_max_len();
+        assert_eq!(foo.list.borrow().max_len(), 0);
+        assert!(futs[0].poll().is_ready());
+        assert!(futs[2].poll().is_pending());
+        assert_eq!(foo.list.borrow().max_len(), 1);
+        assert_eq!(foo.list.borrow_mut().awake_waiters(), 1);
+    }
+
+    #[test]
+    fn requeue_to_back_loses_place() {
+        let foo = Foo::new();
+        let mut futs: Vec<_> = (0..3).map(|_| task::spawn(foo.bar(1))).collect();
+        for fut in futs.iter_mut() {
+            assert!(fut.poll().is_pending());
+        }
+
+        // Move the oldest behind the other two, from within its own task.
+        futs[0].enter(|cx, bar| unsafe {
+            bar.foo.list.borrow_mut().requeue_to_back(&bar.elem, cx);
+        });
+        assert_eq!(foo.list.borrow().len(), 3);
+
+        let woken = |futs: &[task::Spawn<Bar>]| -> Vec<bool> {
+            futs.iter().map(|fut| fut.is_woken()).collect()
+        };
+        assert!(foo.list.borrow_mut().wake_one());
+        assert_eq!(woken(&futs), [false, true, false]);
+        assert!(foo.list.borrow_mut().wake_one());
+        assert_eq!(woken(&futs), [false, true, true]);
+        assert!(foo.list.borrow_mut().wake_one());
+        assert_eq!(woken(&futs), [true, true, true]);
+        assert!(foo.list.borrow().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "metrics")]
+    fn waker_clone_and_reuse_counts() {
+        let foo = Foo::new();
+        let mut a = task::spawn(foo.bar(3));
+        let mut b = task::spawn(foo.bar(1));
+
+        assert!(a.poll().is_pending());
+        assert!(b.poll().is_pending());
+        assert_eq!(foo.list.borrow().waker_clones(), 2);
+        assert_eq!(foo.list.borrow().waker_reuses(), 0);
+
+        // Polling the same future again, from the same task, reuses its stored waker.
+        assert!(a.poll().is_pending());
+        assert_eq!(foo.list.borrow().waker_clones(), 2);
+        assert_eq!(foo.list.borrow().waker_reuses(), 1);
+
+        // Once woken the waker has been taken, so the next poll has to clone again.
+        assert_eq!(foo.list.borrow_mut().awake_waiters(), 2);
+        assert!(a.poll().is_pending());
+        assert_eq!(foo.list.borrow().waker_clones(), 3);
+        assert_eq!(foo.list.borrow().waker_reuses(), 1);
+        assert_eq!(foo.list.borrow_mut().awake_waiters(), 1);
+    }
+
+    #[test]
+    fn cache_padded_alignment() {
+        struct Lists {
+            read: CachePadded,
+            write: CachePadded,
+        }
+
+        assert_eq!(core::mem::align_of::<CachePadded>(), 64);
+        let lists = Lists {
+            read: CachePadded::default(),
+            write: CachePadded::new(List::with_order(WakeOrder::Lifo)),
+        };
+        let read = &lists.read as *const CachePadded as usize;
+        let write = &lists.write as *const CachePadded as usize;
+        assert_eq!(read % 64, 0);
+        assert_eq!(write % 64, 0);
+        assert!(read.abs_diff(write) >= 64);
+
+        // Derefs to the list.
+        assert_eq!(lists.write.order(), WakeOrder::Lifo);
+        assert!(lists.read.is_empty());
+    }
+
+    #[test]
+    fn enqueue_clones_waker_at_most_once() {
+        // Counted with the waker's own reference count, not the list's counters.
+        let counter = CountingWaker::new();
+        let waker = counter.waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut list: List = List::new();
+
+        // Safety: the elem is woken out of the list before it is dropped.
+        let elem = unsafe { Elem::new() };
+        assert!(list.enqueue_waiter(&elem, &mut cx));
+        assert_eq!(counter.waker_count(), 2);
+        for _ in 0..3 {
+            assert!(!list.enqueue_waiter(&elem, &mut cx));
+        }
+        assert_eq!(counter.waker_count(), 2);
+
+        // The stored waker is woken by value, not cloned.
+        assert_eq!(list.awake_waiters(), 1);
+        assert_eq!(counter.waker_count(), 1);
+        #[cfg(feature = "metrics")]
+        assert_eq!(list.waker_clones(), 1);
+    }
+
+    #[test]
+    fn redundant_enqueue_changes_nothing() {
+        let counter = CountingWaker::new();
+        let waker = counter.waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut list: List = List::new();
+
+        // Safety: the elems are woken out of the list before they are dropped.
+        let a = unsafe { Elem::new() };
+        let b = unsafe { Elem::new() };
+        assert!(list.enqueue_waiter(&a, &mut cx));
+        assert!(list.enqueue_waiter(&b, &mut cx));
+        #[cfg(feature = "metrics")]
+        let (clones, reuses) = (list.waker_clones(), list.waker_reuses());
+
+        for _ in 0..3 {
+            assert!(!list.enqueue_waiter(&a, &mut cx));
+        }
+        #[cfg(feature = "metrics")]
+        {
+            assert_eq!(list.waker_clones(), clones);
+            assert_eq!(list.waker_reuses(), reuses + 3);
+        }
+        assert_eq!(list.len(), 2);
+        assert_eq!(counter.waker_count(), 3);
+        // a keeps its place at the head.
+        assert!(list.wake_one());
+        assert!(!list.is_queued(&a));
+
+        // Another task's waker still replaces the stored one.
+        let other = CountingWaker::new();
+        let other_waker = other.waker();
+        assert!(!list.enqueue_waiter(&b, &mut Context::from_waker(&other_waker)));
+        #[cfg(feature = "metrics")]
+        assert_eq!(list.waker_clones(), clones + 1);
+        assert_eq!(list.awake_waiters(), 1);
+        assert_eq!(other.wake_count(), 1);
+    }
+
+    #[test]
+    fn remove_after_wake_is_a_no_op() {
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut list: List = List::new();
+
+        // Safety: the elems are removed from, or woken out of, the list before they are dropped.
+        let a = unsafe { Elem::new() };
+        let b = unsafe { Elem::new() };
+        let c = unsafe { Elem::new() };
+        assert!(list.enqueue_waiter(&a, &mut cx));
+        assert!(list.enqueue_waiter(&b, &mut cx));
+
+        // a is woken, then its future is cancelled before it is polled again.
+        assert!(list.wake_one());
+        assert!(list.enqueue_waiter(&c, &mut cx));
+        unsafe { list.remove_waiter(&a) };
+        unsafe { list.remove_waiter(&a) };
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.len_backwards(), 2);
+
+        // The same after awake_waiters, once the list is empty.
+        assert_eq!(list.awake_waiters(), 2);
+        unsafe {
+            list.remove_waiter(&b);
+            list.remove_waiter(&c);
+        }
+        assert!(list.is_empty());
+        assert_eq!(list.len_backwards(), 0);
+        assert!(list.enqueue_waiter(&b, &mut cx));
+        assert_eq!(list.len_backwards(), 1);
+        unsafe { list.remove_waiter(&b) };
+    }
+
+    #[test]
+    fn awake_waiters_drains_before_waking() {
+        use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+        use std::sync::Arc;
+        use std::task::Wake;
+
+        /// Counts, on each wake, the elems still queued.
+        struct Check {
+            elems: *const [Elem],
+            wakes: AtomicUsize,
+            still_queued: AtomicUsize,
+        }
+        // Safety: the test is single threaded.
+        unsafe impl Send for Check {}
+        unsafe impl Sync for Check {}
+
+        impl Wake for Check {
+            fn wake(self: Arc<Self>) {
+                self.wake_by_ref()
+            }
+            fn wake_by_ref(self: &Arc<Self>) {
+                self.wakes.fetch_add(1, SeqCst);
+                // Safety: the elems outlive the wakes. Only their waiters' cells are read, which
+                // the list leaves alone while it wakes.
+                let elems = unsafe { &*self.elems };
+                for elem in elems {
+                    if elem.waiter.with(|ptr| unsafe { (*ptr).queued }) {
+                        self.still_queued.fetch_add(1, SeqCst);
+                    }
+                }
+            }
+        }
+
+        let mut list: List = List::new();
+        // Safety: the elems are woken out of the list before they are dropped.
+        let elems: Vec<Elem> = (0..3).map(|_| unsafe { Elem::new() }).collect();
+        let check = Arc::new(Check {
+            elems: &elems[..] as *const [Elem],
+            wakes: AtomicUsize::new(0),
+            still_queued: AtomicUsize::new(0),
+        });
+        let waker = Waker::from(check.clone());
+        let mut cx = Context::from_waker(&waker);
+        for elem in elems.iter() {
+            assert!(list.enqueue_waiter(elem, &mut cx));
+        }
+
+        assert_eq!(list.awake_waiters(), 3);
+        assert_eq!(check.wakes.load(SeqCst), 3);
+        assert_eq!(check.still_queued.load(SeqCst), 0);
+
+        // The first task woken, polling again, goes into the now empty list on its own.
+        assert!(list.enqueue_waiter(&elems[0], &mut cx));
+        assert_eq!(list.len(), 1);
+        assert_eq!(list.awake_waiters(), 1);
+    }
+
+    #[test]
+    fn wake_one_and_remaining() {
+        let counter = CountingWaker::new();
+        let waker = counter.waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut list: List = List::new();
+
+        // Safety: the elems are woken out of the list before they are dropped.
+        let elems: Vec<Elem> = (0..3).map(|_| unsafe { Elem::new() }).collect();
+        for elem in elems.iter() {
+            assert!(list.enqueue_waiter(elem, &mut cx));
+        }
+
+        assert_eq!(list.wake_one_and_remaining(), (true, 2));
+        assert_eq!(list.wake_one_and_remaining(), (true, 1));
+        assert_eq!(list.wake_one_and_remaining(), (true, 0));
+        assert_eq!(list.wake_one_and_remaining(), (false, 0));
+        assert_eq!(counter.wake_count(), 3);
+    }
+
+    #[test]
+    fn enqueue_waiter_reports_newly_queued() {
+        let foo = Foo::new();
+        let mut a = task::spawn(foo.bar(2));
+
+        let queued = a.enter(|cx, bar| bar.foo.list.borrow_mut().enqueue_waiter(&bar.elem, cx));
+        assert!(queued);
+        let queued = a.enter(|cx, bar| bar.foo.list.borrow_mut().enqueue_waiter(&bar.elem, cx));
+        assert!(!queued);
+        assert_eq!(foo.list.borrow().len(), 1);
+
+        assert!(foo.list.borrow_mut().wake_one());
+        let queued = a.enter(|cx, bar| bar.foo.list.borrow_mut().enqueue_waiter(&bar.elem, cx));
+        assert!(queued);
+        assert_eq!(foo.list.borrow_mut().awake_waiters(), 1);
+    }
+
+    #[test]
+    fn unit_payload() {
+        let foo = Foo::new();
+        let mut a = task::spawn(foo.bar(2));
+        assert!(a.poll().is_pending());
+        assert_eq!(foo.list.borrow().payload(&a.elem), None);
+        assert_eq!(foo.list.borrow().iter_payloads().collect::<Vec<_>>(), [None]);
+
+        a.enter(|cx, bar| bar.foo.list.borrow_mut().enqueue_waiter_with(&bar.elem, cx, ()));
+        assert_eq!(foo.list.borrow().payload(&a.elem), Some(&()));
+        assert_eq!(foo.list.borrow_mut().take_payload(&a.elem), Some(()));
+        assert_eq!(foo.list.borrow_mut().awake_waiters(), 1);
+    }
+
+    #[test]
+    fn usize_payload() {
+        let list: RefCell<List<usize>> = RefCell::new(List::new());
+        // Safety: each elem is removed from the list below, before it is dropped.
+        let elems: Vec<Elem<usize>> = (0..3).map(|_| unsafe { Elem::new() }).collect();
+        let waker = Waker::noop();
+        let mut cx = Context::from_waker(waker);
+        for (i, elem) in elems.iter().enumerate() {
+            assert!(list.borrow_mut().enqueue_waiter_with(elem, &mut cx, i * 10));
+        }
+
+        // Payloads are seen in wake order, oldest first.
+        let payloads: Vec<Option<usize>> =
+            list.borrow().iter_payloads().map(|p| p.copied()).collect();
+        assert_eq!(payloads, [Some(0), Some(10), Some(20)]);
+
+        // A later enqueue replaces the payload without moving the elem.
+        assert!(!list.borrow_mut().enqueue_waiter_with(&elems[0], &mut cx, 5));
+        assert_eq!(list.borrow().payload(&elems[0]), Some(&5));
+        assert_eq!(list.borrow_mut().take_payload(&elems[0]), Some(5));
+        assert_eq!(list.borrow().payload(&elems[0]), None);
+
+        assert!(list.borrow_mut().wake_one());
+        assert_eq!(list.borrow_mut().take_payload(&elems[1]), Some(10));
+        unsafe {
+            list.borrow_mut().remove_waiter(&elems[1]);
+            list.borrow_mut().remove_waiter(&elems[2]);
+        }
+        assert!(list.borrow().is_empty());
+    }
+
+    fn priority_wake_order(order: WakeOrder, priorities: &[u8]) -> Vec<usize> {
+        let list: RefCell<List> = RefCell::new(List::with_order(order));
+        // Safety: every elem is woken, and so removed from the list, before it is dropped.
+        let elems: Vec<Elem> = priorities.iter().map(|_| unsafe { Elem::new() }).collect();
+        let waker = Waker::noop();
+        let mut cx = Context::from_waker(waker);
+        for (elem, priority) in elems.iter().zip(priorities) {
+            let mut list = list.borrow_mut();
+            assert!(list.enqueue_waiter_with_priority(elem, &mut cx, *priority));
+            validate(&list);
+        }
+        let mut woken = vec![];
+        while list.borrow_mut().wake_one() {
+            let i = (0..elems.len())
+                .find(|i| !woken.contains(i) && !list.borrow().is_queued(&elems[*i]))
+                .unwrap();
+            woken.push(i);
+        }
+        woken
+    }
+
+    #[test]
+    fn priority_insertion() {
+        // Priorities 1, 3, 2 are woken 3, 2, 1.
+        assert_eq!(priority_wake_order(WakeOrder::Fifo, &[1, 3, 2]), [1, 2, 0]);
+        assert_eq!(priority_wake_order(WakeOrder::Lifo, &[1, 3, 2]), [1, 2, 0]);
+
+        // Equal priorities keep the list's order.
+        assert_eq!(priority_wake_order(WakeOrder::Fifo, &[2, 1, 2, 1]), [0, 2, 1, 3]);
+        assert_eq!(priority_wake_order(WakeOrder::Lifo, &[2, 1, 2, 1]), [2, 0, 3, 1]);
+    }
+
+    #[test]
+    fn priority_mixed_with_plain_enqueue() {
+        let foo = Foo::new();
+        let mut plain = task::spawn(foo.bar(1));
+        let mut high = task::spawn(foo.bar(1));
+        assert!(plain.poll().is_pending());
+        high.enter(|cx, bar| {
+            assert!(bar.foo.list.borrow_mut().enqueue_waiter_with_priority(&bar.elem, cx, 1));
+            // Already queued: the waiter keeps its place.
+            assert!(!bar.foo.list.borrow_mut().enqueue_waiter_with_priority(&bar.elem, cx, 0));
+        });
+
+        assert!(foo.list.borrow_mut().wake_one());
+        assert!(high.is_woken() && !plain.is_woken());
+        assert!(foo.list.borrow_mut().wake_one());
+        assert!(plain.is_woken());
+    }
+
+    /// Qux is a Future that waits for a value to be handed to it by `wake_one_with`.
+    struct Qux<'a> {
+        list: &'a RefCell<List<String>>,
+        elem: Elem<String>,
+    }
+
+    impl<'a> Future for Qux<'a> {
+        type Output = String;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<String> {
+            let mut list = self.list.borrow_mut();
+            if let Some(value) = list.take_payload(&self.elem) {
+                return Poll::Ready(value);
+            }
+            list.enqueue_waiter(&self.elem, cx);
             Poll::Pending
         }
     }
 
-    impl<'a> Drop for Bar<'a> {
+    impl<'a> Drop for Qux<'a> {
         fn drop(&mut self) {
-            let mut list = self.foo.list.borrow_mut();
-            // Safety: the one unsafe call required by this `waiter` module model.
-            // It is unsafe because we may not forget to call it in this Future's drop
-            // and because when we do call it, the correct List manager must be used.
+            // Safety: the elem is only ever queued in this list.
             unsafe {
-                list.remove_waiter(&self.elem);
+                self.list.borrow_mut().remove_waiter(&self.elem);
             }
         }
     }
 
-    #[tokio::test]
-    async fn await_waiters_synchronize() {
-        // Test that the waiters linked list grows to length three when three
-        // tasks should be awaiting.
-        // Test that calling awake_waiters then drains the list and test that
-        // the three tasks made the expected progress after getting through their
-        // await points.
-        let local = task::LocalSet::new();
-        // Run the local task set.
-        local.run_until(async move {
-            // The progress string will prove how far the various tasks have gotten
-            // between sync points.
-            let progress = Rc::new(RefCell::new(String::from("s")));
+    #[test]
+    fn wake_one_with_hands_off_value() {
+        let list = RefCell::new(List::new());
+        assert_eq!(list.borrow_mut().wake_one_with(String::from("lost")), Err("lost".into()));
+
+        // Safety: Qux removes its elem from the list in its drop.
+        let qux = || Qux {
+            list: &list,
+            elem: unsafe { Elem::new() },
+        };
+        let mut a = task::spawn(qux());
+        let mut b = task::spawn(qux());
+        assert!(a.poll().is_pending());
+        assert!(b.poll().is_pending());
+
+        assert_eq!(list.borrow_mut().wake_one_with(String::from("permit")), Ok(()));
+        assert!(a.is_woken() && !b.is_woken());
+        assert_eq!(list.borrow().len(), 1);
+
+        assert_eq!(a.poll(), Poll::Ready(String::from("permit")));
+        assert!(b.poll().is_pending());
+        assert_eq!(list.borrow().iter_payloads().collect::<Vec<_>>(), [None]);
+    }
+
+    #[test]
+    #[cfg(any(debug_assertions, feature = "validate"))]
+    fn contains_goes_false_after_removal() {
+        let foo = Foo::new();
+        let mut a = task::spawn(foo.bar(1));
+        let mut b = task::spawn(foo.bar(1));
+        assert!(!foo.list.borrow().contains(&a.elem));
+        assert!(a.poll().is_pending());
+        assert!(b.poll().is_pending());
+        assert!(foo.list.borrow().contains(&a.elem));
+        assert!(foo.list.borrow().contains(&b.elem));
+
+        unsafe { foo.list.borrow_mut().remove_waiter(&a.elem) };
+        assert!(!foo.list.borrow().contains(&a.elem));
+        assert!(foo.list.borrow().contains(&b.elem));
+
+        assert!(foo.list.borrow_mut().wake_one());
+        assert!(!foo.list.borrow().contains(&b.elem));
+    }
+
+    #[test]
+    fn cancel_after_wake_passes_wake_on() {
+        let foo = Foo::new();
+        let mut a = task::spawn(foo.bar(1));
+        let mut b = task::spawn(foo.bar(1));
+        let mut c = task::spawn(foo.bar(1));
+        assert!(a.poll().is_pending());
+        assert!(b.poll().is_pending());
+        assert!(c.poll().is_pending());
+
+        // A cancelled waiter that was never woken passes nothing on.
+        let woke_next = unsafe { foo.list.borrow_mut().remove_waiter_and_wake_next(&b.elem) };
+        assert!(!woke_next);
+        assert!(!c.is_woken());
+
+        // a is woken for the single permit, then cancelled before consuming it: c gets it.
+        assert!(foo.list.borrow_mut().wake_one());
+        assert!(a.is_woken());
+        let woke_next = unsafe { foo.list.borrow_mut().remove_waiter_and_wake_next(&a.elem) };
+        assert!(woke_next);
+        assert!(c.is_woken());
+
+        // The notification is only passed on once.
+        let woke_next = unsafe { foo.list.borrow_mut().remove_waiter_and_wake_next(&a.elem) };
+        assert!(!woke_next);
+
+        // c's wake is passed on to no one, the list being empty.
+        let woke_next = unsafe { foo.list.borrow_mut().remove_waiter_and_wake_next(&c.elem) };
+        assert!(!woke_next);
+        assert!(foo.list.borrow().is_empty());
+    }
+
+    #[test]
+    fn consumed_wake_is_not_passed_on() {
+        let foo = Foo::new();
+        let mut a = task::spawn(foo.bar(1));
+        let mut b = task::spawn(foo.bar(1));
+        assert!(a.poll().is_pending());
+        assert!(b.poll().is_pending());
+
+        // a is woken and takes the permit, so its drop has nothing to pass on.
+        assert!(foo.list.borrow_mut().wake_one());
+        assert!(foo.list.borrow_mut().consume_wake(&a.elem));
+        assert!(!foo.list.borrow_mut().consume_wake(&a.elem));
+        let woke_next = unsafe { foo.list.borrow_mut().remove_waiter_and_wake_next(&a.elem) };
+        assert!(!woke_next);
+        assert!(!b.is_woken());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn deadline_list_wakes_only_expired() {
+        use std::time::Duration;
+
+        let mut list = DeadlineList::new();
+        assert_eq!(list.next_deadline(), None);
+        assert_eq!(list.wake_expired(Instant::now()), 0);
+
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        // Safety: every elem is removed from the list before it is dropped.
+        let elems: Vec<Elem<Instant>> = (0..4).map(|_| unsafe { Elem::new() }).collect();
+        let waker = Waker::noop();
+        let mut cx = Context::from_waker(waker);
+        for (elem, secs) in elems.iter().zip([30, 10, 20, 10]) {
+            assert!(list.enqueue_waiter(elem, &mut cx, at(secs)));
+        }
+        assert_eq!(list.len(), 4);
+        assert_eq!(list.next_deadline(), Some(at(10)));
+
+        assert_eq!(list.wake_expired(at(5)), 0);
+        assert_eq!(list.wake_expired(at(10)), 2);
+        assert!(!list.list.is_queued(&elems[1]) && !list.list.is_queued(&elems[3]));
+        assert_eq!(list.next_deadline(), Some(at(20)));
+
+        // Moving a deadline earlier moves the waiter to the front.
+        assert!(!list.enqueue_waiter(&elems[0], &mut cx, at(15)));
+        assert_eq!(list.next_deadline(), Some(at(15)));
+        assert_eq!(list.wake_expired(at(19)), 1);
+        assert!(!list.list.is_queued(&elems[0]));
+        assert!(list.list.is_queued(&elems[2]));
+
+        unsafe { list.remove_waiter(&elems[2]) };
+        assert!(list.is_empty());
+        assert_eq!(list.next_deadline(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn deadline_list_drains_expired_without_waking() {
+        use std::time::Duration;
+
+        let counter = CountingWaker::new();
+        let waker = counter.waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut list = DeadlineList::new();
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        // Safety: every elem is drained from, or removed from, the list before it is dropped.
+        let elems: Vec<Elem<Instant>> = (0..3).map(|_| unsafe { Elem::new() }).collect();
+        for (elem, secs) in elems.iter().zip([10, 30, 20]) {
+            assert!(list.enqueue_waiter(elem, &mut cx, at(secs)));
+        }
+
+        let mut wakers = WakeList::new();
+        assert_eq!(list.drain_expired_into(at(20), &mut wakers), 2);
+        assert_eq!(wakers.len(), 2);
+        assert_eq!(counter.wake_count(), 0);
+        assert_eq!(list.next_deadline(), Some(at(30)));
+
+        wakers.wake_all();
+        assert_eq!(counter.wake_count(), 2);
+        unsafe { list.remove_waiter(&elems[1]) };
+    }
+
+    #[test]
+    fn keep_first_waker_policy() {
+        let foo = Foo::new();
+        assert_eq!(foo.list.borrow().waker_policy(), WakerPolicy::ReplaceLatest);
+        foo.list.borrow_mut().set_waker_policy(WakerPolicy::KeepFirst);
+
+        let mut first = task::spawn(foo.bar(1));
+        let mut second = task::spawn(async {});
+        assert!(first.poll().is_pending());
+
+        // Enqueue the same elem again, but from another task.
+        let bar = &*first;
+        second.enter(|cx, _| {
+            assert!(!foo.list.borrow_mut().enqueue_waiter(&bar.elem, cx));
+        });
+        #[cfg(feature = "metrics")]
+        assert_eq!(foo.list.borrow().waker_reuses(), 1);
+
+        assert!(foo.list.borrow_mut().wake_one());
+        assert!(first.is_woken());
+        assert!(!second.is_woken());
+
+        // Under the default policy, the second registration would have won.
+        foo.list.borrow_mut().set_waker_policy(WakerPolicy::ReplaceLatest);
+        let mut third = task::spawn(foo.bar(1));
+        assert!(third.poll().is_pending());
+        let bar = &*third;
+        second.enter(|cx, _| {
+            assert!(!foo.list.borrow_mut().enqueue_waiter(&bar.elem, cx));
+        });
+        assert!(foo.list.borrow_mut().wake_one());
+        assert!(!third.is_woken());
+        assert!(second.is_woken());
+    }
+
+    #[test]
+    #[cfg(feature = "tracing")]
+    fn tracing_events() {
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        /// Records the message and `len` field of each event.
+        #[derive(Clone, Default)]
+        struct Recorder(Arc<Mutex<Vec<(String, u64)>>>);
+
+        #[derive(Default)]
+        struct Fields {
+            message: String,
+            len: u64,
+        }
+
+        impl Visit for Fields {
+            fn record_u64(&mut self, field: &Field, value: u64) {
+                if field.name() == "len" {
+                    self.len = value;
+                }
+            }
+
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                if field.name() == "message" {
+                    self.message = format!("{:?}", value);
+                }
+            }
+        }
+
+        impl Subscriber for Recorder {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, _: &Attributes<'_>) -> Id {
+                Id::from_u64(1)
+            }
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, event: &Event<'_>) {
+                let mut fields = Fields::default();
+                event.record(&mut fields);
+                self.0.lock().unwrap().push((fields.message, fields.len));
+            }
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
             let foo = Foo::new();
-            for _ in 0..3 {
-                let foo = foo.clone();
-                let progress = progress.clone();
-                task::spawn_local(async move {
-                    *progress.borrow_mut() += "1";
-                    foo.bar(1).await; // Here we test, by awaiting the Future. awake_waiters
-                                      // will have to be called once below (because countdown
-                                      // is set to 1 here).
-                    *progress.borrow_mut() += "2";
-                });
+            let mut a = task::spawn(foo.bar(2));
+            let mut b = task::spawn(foo.bar(1));
+            assert!(a.poll().is_pending());
+            assert!(b.poll().is_pending());
+            // Already queued: no event.
+            assert!(a.poll().is_pending());
+            assert!(foo.list.borrow_mut().wake_one());
+            drop(b);
+        });
+
+        let events = recorder.0.lock().unwrap();
+        let events: Vec<(&str, u64)> = events.iter().map(|(m, len)| (m.as_str(), *len)).collect();
+        assert_eq!(
+            events,
+            [
+                ("waiter enqueued", 1),
+                ("waiter enqueued", 2),
+                ("waiter woken", 1),
+                ("waiter removed", 0),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "metrics")]
+    fn metrics_snapshot() {
+        let foo = Foo::new();
+        assert_eq!(foo.list.borrow().metrics(), ListMetrics::default());
+
+        let mut futs: Vec<_> = (0..3).map(|_| task::spawn(foo.bar(2))).collect();
+        for fut in futs.iter_mut() {
+            assert!(fut.poll().is_pending());
+        }
+        // Polled again by the same task while still queued.
+        assert!(futs[0].poll().is_pending());
+        assert!(foo.list.borrow_mut().wake_one());
+        // The woken one completes; the next, still queued, keeps its waker.
+        assert!(futs[0].poll().is_ready());
+        assert!(futs[1].poll().is_pending());
+        drop(futs.remove(2));
+
+        assert_eq!(
+            foo.list.borrow().metrics(),
+            ListMetrics {
+                current_len: 1,
+                max_len: 3,
+                total_enqueues: 3,
+                total_wakes: 1,
+                waker_clones: 3,
+                waker_reuses: 2,
             }
-            while *progress.borrow() != "s111" {
-                task::yield_now().await;
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "metrics", feature = "std"))]
+    fn oldest_wait() {
+        use std::time::Duration;
+
+        let foo = Foo::new();
+        assert_eq!(foo.list.borrow().oldest_wait(Instant::now()), None);
+
+        let mut a = task::spawn(foo.bar(2));
+        assert!(a.poll().is_pending());
+        std::thread::sleep(Duration::from_millis(5));
+        let mut b = task::spawn(foo.bar(2));
+        assert!(b.poll().is_pending());
+
+        // a is next to be woken, and has waited at least the sleep.
+        let now = Instant::now();
+        let a_wait = foo.list.borrow().oldest_wait(now).unwrap();
+        assert!(a_wait >= Duration::from_millis(5));
+
+        // Then b, queued after the sleep.
+        assert!(foo.list.borrow_mut().wake_one());
+        let b_wait = foo.list.borrow().oldest_wait(now).unwrap();
+        assert!(b_wait + Duration::from_millis(5) <= a_wait);
+    }
+
+    #[test]
+    fn clear_does_not_wake() {
+        let list: RefCell<List> = RefCell::new(List::new());
+        assert_eq!(list.borrow_mut().clear(), 0);
+
+        // Safety: the elems are removed from the list, by clear, before they are dropped.
+        let elems: Vec<Elem> = (0..3).map(|_| unsafe { Elem::new() }).collect();
+        let counter = CountingWaker::new();
+        let waker = counter.waker();
+        let mut cx = Context::from_waker(&waker);
+        for elem in elems.iter() {
+            assert!(list.borrow_mut().enqueue_waiter(elem, &mut cx));
+        }
+
+        assert_eq!(list.borrow_mut().clear(), 3);
+        assert!(list.borrow().is_empty());
+        assert!(elems.iter().all(|elem| !list.borrow().is_queued(elem)));
+        assert_eq!(counter.wake_count(), 0);
+        // The stored clones were dropped, leaving only the test's own waker.
+        assert_eq!(counter.waker_count(), 1);
+    }
+
+    #[test]
+    fn drain_wakers_into_defers_wakes() {
+        let list: RefCell<List> = RefCell::new(List::new());
+        let mut wakers = Vec::new();
+        assert_eq!(list.borrow_mut().drain_wakers_into(&mut wakers), 0);
+
+        // Safety: the elems are taken out of the list, by the drain, before they are dropped.
+        let elems: Vec<Elem> = (0..3).map(|_| unsafe { Elem::new() }).collect();
+        let counter = CountingWaker::new();
+        let waker = counter.waker();
+        let mut cx = Context::from_waker(&waker);
+        for elem in elems.iter() {
+            assert!(list.borrow_mut().enqueue_waiter(elem, &mut cx));
+        }
+
+        assert_eq!(list.borrow_mut().drain_wakers_into(&mut wakers), 3);
+        assert_eq!(wakers.len(), 3);
+        // The list is empty, and free to borrow, before any of the wakers fire.
+        assert!(list.borrow().is_empty());
+        assert!(elems.iter().all(|elem| !list.borrow().is_queued(elem)));
+        assert_eq!(counter.wake_count(), 0);
+
+        for waker in wakers.drain(..) {
+            waker.wake();
+        }
+        assert_eq!(counter.wake_count(), 3);
+        assert_eq!(counter.waker_count(), 1);
+    }
+
+    #[test]
+    fn drain_n_into_takes_at_most_n() {
+        let mut list: List = List::new();
+        let mut wakers = Vec::new();
+
+        // Safety: the elems are taken out of the list, by the drains, before they are dropped.
+        let elems: Vec<Elem> = (0..5).map(|_| unsafe { Elem::new() }).collect();
+        let counter = CountingWaker::new();
+        let waker = counter.waker();
+        let mut cx = Context::from_waker(&waker);
+        for elem in elems.iter() {
+            assert!(list.enqueue_waiter(elem, &mut cx));
+        }
+
+        assert_eq!(list.drain_n_into(0, &mut wakers), 0);
+        assert_eq!(list.drain_n_into(2, &mut wakers), 2);
+        assert_eq!(wakers.len(), 2);
+        assert_eq!(list.len(), 3);
+        // Taken in wake order, the oldest first.
+        assert!(!list.is_queued(&elems[0]) && !list.is_queued(&elems[1]));
+        assert!(list.is_queued(&elems[2]));
+
+        // Fewer left than asked for.
+        assert_eq!(list.drain_n_into(4, &mut wakers), 3);
+        assert_eq!(wakers.len(), 5);
+        assert!(list.is_empty());
+        assert_eq!(counter.wake_count(), 0);
+
+        for waker in wakers.drain(..) {
+            waker.wake();
+        }
+        assert_eq!(counter.wake_count(), 5);
+    }
+
+    #[test]
+    fn reset_elem_between_waits() {
+        let mut list: List<u32> = List::new();
+        // Safety: the elem is out of the list, woken or removed, before it is dropped.
+        let elem = unsafe { Elem::new() };
+        let first = CountingWaker::new();
+        let second = CountingWaker::new();
+
+        assert!(list.enqueue_waiter_with(&elem, &mut Context::from_waker(&first.waker()), 7));
+        assert!(list.wake_one());
+        assert_eq!(first.wake_count(), 1);
+
+        list.reset_elem(&elem);
+        assert_eq!(list.payload(&elem), None);
+
+        // Parked again, by another task, the elem carries nothing from its first wait.
+        assert!(list.enqueue_waiter(&elem, &mut Context::from_waker(&second.waker())));
+        assert!(list.wake_one());
+        assert_eq!((first.wake_count(), second.wake_count()), (1, 1));
+        assert_eq!(first.waker_count(), 0);
+
+        // Its wake is consumed; a reset elem queued and cancelled has none to pass on.
+        list.reset_elem(&elem);
+        assert!(list.enqueue_waiter(&elem, &mut Context::from_waker(&second.waker())));
+        // Safety: the elem is removed from the list below.
+        let other = unsafe { Elem::new() };
+        assert!(list.enqueue_waiter(&other, &mut Context::from_waker(&first.waker())));
+        // Safety: both elems are only queued in this list.
+        unsafe {
+            assert!(!list.remove_waiter_and_wake_next(&elem));
+            list.remove_waiter(&other);
+        }
+        assert_eq!(first.wake_count(), 1);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn reset_queued_elem_panics() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let mut list: List = List::new();
+        // Safety: the elem is removed from the list before it is dropped.
+        let elem = unsafe { Elem::new() };
+        let counter = CountingWaker::new();
+        list.enqueue_waiter(&elem, &mut Context::from_waker(&counter.waker()));
+
+        let result = catch_unwind(AssertUnwindSafe(|| list.reset_elem(&elem)));
+        assert!(result.is_err());
+        // The panic came before the elem was touched.
+        assert_eq!(counter.waker_count(), 1);
+        // Safety: the elem is only queued in this list.
+        unsafe { list.remove_waiter(&elem) };
+    }
+
+    #[test]
+    fn wakers_from_two_lists_woken_together() {
+        let first: RefCell<List> = RefCell::new(List::new());
+        let second: RefCell<List> = RefCell::new(List::new());
+        assert!(first.borrow_mut().wake_all_drain_returning_wakers().is_empty());
+
+        // Safety: the elems are taken out of their lists, by the drains, before they are dropped.
+        let elems: Vec<Elem> = (0..4).map(|_| unsafe { Elem::new() }).collect();
+        let counter = CountingWaker::new();
+        let waker = counter.waker();
+        let mut cx = Context::from_waker(&waker);
+        for (i, elem) in elems.iter().enumerate() {
+            let list = if i % 2 == 0 { &first } else { &second };
+            assert!(list.borrow_mut().enqueue_waiter(elem, &mut cx));
+        }
+
+        let mut wakers = first.borrow_mut().wake_all_drain_returning_wakers();
+        wakers.extend(second.borrow_mut().wake_all_drain_returning_wakers());
+        assert_eq!(wakers.len(), 4);
+        assert!(first.borrow().is_empty() && second.borrow().is_empty());
+        assert_eq!(counter.wake_count(), 0);
+
+        for waker in wakers {
+            waker.wake();
+        }
+        assert_eq!(counter.wake_count(), 4);
+        assert_eq!(counter.waker_count(), 1);
+    }
+
+    #[test]
+    fn remove_from_current_follows_tag() {
+        #[derive(Debug, PartialEq)]
+        enum Side {
+            Read,
+            Write,
+        }
+
+        impl ListTag for Side {
+            fn list_index(&self) -> usize {
+                match self {
+                    Side::Read => 0,
+                    Side::Write => 1,
+                }
             }
-            assert_eq!(foo.list.borrow_mut().len(), 3);
-            foo.list.borrow_mut().awake_waiters();
-            assert_eq!(foo.list.borrow_mut().len(), 0);
-            while *progress.borrow() != "s111222" {
-                task::yield_now().await;
+        }
+
+        let mut read: List<Side> = List::new();
+        let mut write: List<Side> = List::new();
+        let counter = CountingWaker::new();
+        let waker = counter.waker();
+        let mut cx = Context::from_waker(&waker);
+        // Safety: the elem is removed from its current list before it is dropped.
+        let elem = Box::pin(unsafe { Elem::new() });
+
+        // Toggle the waiter back and forth, letting its tag pick the list to leave.
+        for (side, index) in [(Side::Read, 0), (Side::Write, 1), (Side::Read, 0)] {
+            let list = if index == 0 { &mut read } else { &mut write };
+            assert!(list.enqueue_waiter_with(&elem, &mut cx, side));
+            // Safety: the elem is queued in the list its tag names.
+            let removed = unsafe { elem.remove_from_current(&mut [&mut read, &mut write]) };
+            assert_eq!(removed, Some(index));
+            assert!(read.is_empty() && write.is_empty());
+        }
+
+        // A woken waiter is no longer in either list.
+        write.enqueue_waiter_with(&elem, &mut cx, Side::Write);
+        assert!(write.wake_one());
+        // Safety: the elem isn't queued, so no list is touched.
+        assert_eq!(unsafe { elem.remove_from_current(&mut [&mut read, &mut write]) }, None);
+        assert_eq!(write.payload(&elem), Some(&Side::Write));
+    }
+
+    #[test]
+    fn try_enqueue_rejects_past_capacity() {
+        let mut list: List = List::with_capacity(2);
+        assert_eq!(list.capacity(), Some(2));
+        let counter = CountingWaker::new();
+        let waker = counter.waker();
+        let mut cx = Context::from_waker(&waker);
+        // Safety: the elems are removed from, or woken out of, the list before they are dropped.
+        let elems: Vec<Elem> = (0..3).map(|_| unsafe { Elem::new() }).collect();
+
+        assert_eq!(list.try_enqueue_waiter(&elems[0], &mut cx), Ok(true));
+        assert!(!list.is_full());
+        assert_eq!(list.try_enqueue_waiter(&elems[1], &mut cx), Ok(true));
+        assert!(list.is_full());
+
+        // A new waiter is turned away, but one already queued keeps its place.
+        assert_eq!(list.try_enqueue_waiter(&elems[2], &mut cx), Err(ListFull(())));
+        assert!(!list.is_queued(&elems[2]));
+        assert_eq!(list.try_enqueue_waiter(&elems[1], &mut cx), Ok(false));
+        assert_eq!(list.len(), 2);
+        assert_eq!(counter.waker_count(), 3);
+
+        // Waking one makes room.
+        assert!(list.wake_one());
+        assert_eq!(list.try_enqueue_waiter(&elems[2], &mut cx), Ok(true));
+        assert_eq!(list.awake_waiters(), 2);
+
+        // An unbounded list is never full.
+        assert_eq!(List::<()>::new().capacity(), None);
+        assert!(!List::<()>::new().is_full());
+    }
+
+    #[test]
+    fn drain_into_wake_list_spills() {
+        use crate::util::wake_list::NUM_WAKERS;
+
+        let list: RefCell<List> = RefCell::new(List::new());
+        let count = NUM_WAKERS + 3;
+        // Safety: the elems are taken out of the list, by the drain, before they are dropped.
+        let elems: Vec<Elem> = (0..count).map(|_| unsafe { Elem::new() }).collect();
+        let counter = CountingWaker::new();
+        let waker = counter.waker();
+        let mut cx = Context::from_waker(&waker);
+        for elem in elems.iter() {
+            assert!(list.borrow_mut().enqueue_waiter(elem, &mut cx));
+        }
+
+        let mut wakers = WakeList::new();
+        assert_eq!(list.borrow_mut().drain_into_wake_list(&mut wakers), count);
+        assert!(wakers.spilled());
+        assert!(list.borrow().is_empty());
+        assert_eq!(counter.wake_count(), 0);
+
+        wakers.wake_all();
+        assert_eq!(counter.wake_count(), count);
+        assert_eq!(counter.waker_count(), 1);
+    }
+
+    #[test]
+    #[cfg(any(debug_assertions, feature = "validate"))]
+    fn validate_catches_unflagged_waiter() {
+        let foo = Foo::new();
+        let mut a = task::spawn(foo.bar(1));
+        assert!(a.poll().is_pending());
+        foo.list.borrow().validate();
+
+        let set_queued = |queued| a.elem.waiter.with_mut(|ptr| unsafe { (*ptr).queued = queued });
+        set_queued(false);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            foo.list.borrow().validate();
+        }));
+        set_queued(true);
+        assert!(result.is_err());
+        foo.list.borrow().validate();
+    }
+
+    #[test]
+    #[cfg(any(debug_assertions, feature = "validate"))]
+    fn cross_list_use_panics() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let foo = Foo::new();
+        let other: RefCell<List> = RefCell::new(List::new());
+        let mut a = task::spawn(foo.bar(2));
+        assert!(a.poll().is_pending());
+
+        // Removing from, or enqueuing on, the wrong list panics before touching either list.
+        let result = catch_unwind(AssertUnwindSafe(|| unsafe {
+            other.borrow_mut().remove_waiter(&a.elem);
+        }));
+        assert!(result.is_err());
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            a.enter(|cx, bar| other.borrow_mut().enqueue_waiter(&bar.elem, cx));
+        }));
+        assert!(result.is_err());
+        assert!(other.borrow().is_empty());
+        assert_eq!(foo.list.borrow().len(), 1);
+        validate(&foo.list.borrow());
+
+        // Once woken, the elem is in no list, and removing it from any list is a no-op.
+        assert!(foo.list.borrow_mut().wake_one());
+        unsafe { other.borrow_mut().remove_waiter(&a.elem) };
+        assert!(other.borrow().is_empty());
+    }
+
+    #[test]
+    fn pinned_waiter_survives_list_replaced_while_not_queued() {
+        let list: RefCell<List> = RefCell::new(List::new());
+        let mut cx = Context::from_waker(Waker::noop());
+
+        // Replaced after the elem is woken: the drop finds the elem in no list.
+        let waiter = Box::pin(PinnedWaiter::new(&list));
+        assert!(waiter.as_ref().poll_enqueue(&mut cx).is_pending());
+        assert_eq!(list.borrow_mut().awake_waiters(), 1);
+        list.replace(List::new());
+        assert!(waiter.is_ready());
+        drop(waiter);
+
+        // Replaced before the elem is queued: it is queued in, and removed from, the new list.
+        let waiter = Box::pin(PinnedWaiter::new(&list));
+        list.replace(List::new());
+        assert!(waiter.as_ref().poll_enqueue(&mut cx).is_pending());
+        assert_eq!(list.borrow().len(), 1);
+        drop(waiter);
+        assert!(list.borrow().is_empty());
+    }
+
+    #[test]
+    fn waiter_without_waker_is_skipped() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let foo = Foo::new();
+        let mut a = task::spawn(foo.bar(1));
+        let mut b = task::spawn(foo.bar(1));
+        assert!(a.poll().is_pending());
+        assert!(b.poll().is_pending());
+        assert!(foo.list.borrow_mut().take_waker(&a.elem).is_some());
+
+        assert_eq!(foo.list.borrow().iter_wakers().count(), 1);
+        let result = catch_unwind(AssertUnwindSafe(|| foo.list.borrow_mut().wake_one()));
+        if cfg!(debug_assertions) {
+            // The debug_assert caught the broken waiter, which is out of the list.
+            assert!(result.is_err());
+            assert!(!b.is_woken());
+            assert!(foo.list.borrow_mut().wake_one());
+        } else {
+            // The broken waiter was dropped from the list and the next one woken instead.
+            assert_eq!(result.ok(), Some(true));
+        }
+        assert!(b.is_woken());
+        assert!(!foo.list.borrow().is_queued(&a.elem));
+        assert!(foo.list.borrow().is_empty());
+    }
+
+    #[test]
+    fn is_queued_states() {
+        let foo = Foo::new();
+        let mut a = task::spawn(foo.bar(2));
+        assert!(!foo.list.borrow().is_queued(&a.elem));
+
+        assert!(a.poll().is_pending());
+        assert!(foo.list.borrow().is_queued(&a.elem));
+
+        assert!(foo.list.borrow_mut().wake_one());
+        assert!(!foo.list.borrow().is_queued(&a.elem));
+
+        assert!(a.poll().is_pending());
+        assert!(foo.list.borrow().is_queued(&a.elem));
+        assert_eq!(foo.list.borrow_mut().awake_waiters(), 1);
+    }
+
+    #[test]
+    fn wake_while_stops_at_first_false() {
+        let foo = Foo::new();
+        let mut futs: Vec<_> = (0..4).map(|_| task::spawn(foo.bar(1))).collect();
+        for fut in futs.iter_mut() {
+            assert!(fut.poll().is_pending());
+        }
+
+        // Wake the two oldest, then refuse the third. The fourth is never considered.
+        let mut calls = 0;
+        let woken = foo.list.borrow_mut().wake_while(|_| {
+            calls += 1;
+            calls <= 2
+        });
+        assert_eq!(woken, 2);
+        assert_eq!(calls, 3);
+        assert_eq!(foo.list.borrow().len(), 2);
+        let woken: Vec<bool> = futs.iter().map(|fut| fut.is_woken()).collect();
+        assert_eq!(woken, [true, true, false, false]);
+
+        assert_eq!(foo.list.borrow_mut().wake_while(|_| false), 0);
+        assert_eq!(foo.list.borrow().len(), 2);
+
+        assert_eq!(foo.list.borrow_mut().wake_while(|_| true), 2);
+        assert!(futs.iter().all(|fut| fut.is_woken()));
+        assert!(foo.list.borrow().is_empty());
+    }
+
+    #[test]
+    fn wake_while_panic_leaves_list_consistent() {
+        let foo = Foo::new();
+        let mut futs: Vec<_> = (0..3).map(|_| task::spawn(foo.bar(1))).collect();
+        for fut in futs.iter_mut() {
+            assert!(fut.poll().is_pending());
+        }
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let mut calls = 0;
+            foo.list.borrow_mut().wake_while(|_| {
+                calls += 1;
+                if calls == 2 {
+                    panic!("predicate panic");
+                }
+                true
+            })
+        }));
+        assert!(result.is_err());
+
+        // The first was woken; the other two are still queued and can be drained normally.
+        assert!(futs[0].is_woken() && !futs[1].is_woken());
+        assert_eq!(foo.list.borrow().len(), 2);
+        assert_eq!(foo.list.borrow().len_backwards(), 2);
+        assert_eq!(foo.list.borrow_mut().awake_waiters(), 2);
+    }
+
+    #[test]
+    fn wake_n_bounded() {
+        let foo = Foo::new();
+        let mut futs: Vec<_> = (0..4).map(|_| task::spawn(foo.bar(1))).collect();
+        for fut in futs.iter_mut() {
+            assert!(fut.poll().is_pending());
+        }
+
+        assert_eq!(foo.list.borrow_mut().wake_n(0), 0);
+        assert_eq!(foo.list.borrow().len(), 4);
+        assert!(futs.iter().all(|fut| !fut.is_woken()));
+
+        // The two oldest are woken, the other two keep their place.
+        assert_eq!(foo.list.borrow_mut().wake_n(2), 2);
+        assert_eq!(foo.list.borrow().len(), 2);
+        let woken: Vec<bool> = futs.iter().map(|fut| fut.is_woken()).collect();
+        assert_eq!(woken, [true, true, false, false]);
+
+        assert!(foo.list.borrow_mut().wake_one());
+        assert!(futs[2].is_woken() && !futs[3].is_woken());
+
+        // Asking for more than are queued wakes what is there.
+        assert_eq!(foo.list.borrow_mut().wake_n(10), 1);
+        assert!(futs[3].is_woken());
+        assert!(foo.list.borrow().is_empty());
+    }
+}
+
+// Run with `cargo test --release --features loom --lib loom_tests`. With the feature, the elems'
+// cells are loom's, which panic when accessed outside `loom::model`, so the other tests are left
+// out by the filter.
+#[cfg(all(test, feature = "loom"))]
+mod loom_tests {
+    use super::*;
+
+    #[test]
+    fn enqueue_remove_awake() {
+        loom::model(|| {
+            let mut cx = Context::from_waker(Waker::noop());
+            let mut list = List::<()>::new();
+            // Safety: both elems are out of the list before they are dropped.
+            let (a, b) = unsafe { (Elem::new(), Elem::new()) };
+
+            assert!(list.enqueue_waiter(&a, &mut cx));
+            assert!(list.enqueue_waiter(&b, &mut cx));
+            unsafe {
+                list.remove_waiter(&a);
             }
-        }).await;
+            assert!(list.enqueue_waiter(&a, &mut cx));
+            assert_eq!(list.awake_waiters(), 2);
+
+            assert!(list.is_empty());
+            assert!(!list.is_queued(&a) && !list.is_queued(&b));
+        });
+    }
+
+    #[test]
+    fn concurrent_enqueue_remove_and_awake() {
+        use loom::sync::{Arc, Mutex};
+        use loom::thread;
+
+        loom::model(|| {
+            let list = Arc::new(Mutex::new(List::<()>::new()));
+
+            let waiter = {
+                let list = list.clone();
+                thread::spawn(move || {
+                    let mut cx = Context::from_waker(Waker::noop());
+                    // Safety: the elem is removed from the list before it is dropped.
+                    let elem = unsafe { Elem::new() };
+
+                    list.lock().unwrap().enqueue_waiter(&elem, &mut cx);
+                    let mut list = list.lock().unwrap();
+                    unsafe {
+                        list.remove_waiter(&elem);
+                    }
+                    assert!(!list.is_queued(&elem));
+                })
+            };
+            let waker = {
+                let list = list.clone();
+                thread::spawn(move || {
+                    let woken = list.lock().unwrap().awake_waiters();
+                    assert!(woken <= 1);
+                })
+            };
+            waiter.join().unwrap();
+            waker.join().unwrap();
+
+            let list = list.lock().unwrap();
+            assert!(list.is_empty());
+            assert_eq!(list.len(), list.len_backwards());
+        });
     }
 }
@@ -0,0 +1,164 @@
+//! Cancels each of the crate's waiting futures from a `tokio::select!`, as a `select!` losing to
+//! a ready branch does, and checks nothing is lost.
+//!
+//! Each future is cancelled twice: while still queued, and after being handed its resource but
+//! before being polled to take it. Either way the resource must end up with the next waiter or
+//! back with its owner, and the waiter behind must still be reachable. With the `leak-detect`
+//! feature, each test also checks no elem was dropped while still queued.
+
+#![cfg(feature = "std")]
+
+use broadcast_rs::mutex::Mutex;
+use broadcast_rs::notify::Notify;
+use broadcast_rs::rwlock::RwLock;
+use broadcast_rs::semaphore::Semaphore;
+use broadcast_rs::{broadcast, oneshot};
+
+use std::future::{ready, Future};
+use std::pin::Pin;
+
+/// Polls `fut` once from a `select!` against a ready branch, returning its output if it was
+/// ready. A pending `fut` loses to the ready branch, as a cancelled future would.
+async fn poll_once<F: Future + Unpin>(fut: &mut F) -> Option<F::Output> {
+    tokio::select! {
+        biased;
+        out = fut => Some(out),
+        _ = ready(()) => None,
+    }
+}
+
+/// Cancels `fut` from a `select!` after its first poll.
+async fn cancel_queued<F: Future>(fut: F) {
+    let mut fut = Box::pin(fut);
+    assert!(poll_once(&mut fut).await.is_none());
+}
+
+fn assert_no_leaked_waiters() {
+    #[cfg(feature = "leak-detect")]
+    broadcast_rs::waiter::debug::assert_no_leaked_waiters();
+}
+
+fn queued<F: Future>(fut: F) -> Pin<Box<F>> {
+    Box::pin(fut)
+}
+
+#[tokio::test]
+async fn mutex_lock() {
+    let mutex = Mutex::new(0);
+    let guard = mutex.lock().await;
+
+    cancel_queued(mutex.lock()).await;
+
+    let mut a = queued(mutex.lock());
+    let mut b = queued(mutex.lock());
+    assert!(poll_once(&mut a).await.is_none());
+    assert!(poll_once(&mut b).await.is_none());
+    // Hands the lock to a, which is cancelled before taking it.
+    drop(guard);
+    drop(a);
+    let guard = poll_once(&mut b).await.expect("lock passed on to b");
+    drop(guard);
+    drop(b);
+
+    assert!(mutex.try_lock().is_ok());
+    assert_no_leaked_waiters();
+}
+
+#[tokio::test]
+async fn semaphore_acquire() {
+    let semaphore = Semaphore::new(2);
+    let held = semaphore.acquire_many(2).await;
+
+    cancel_queued(semaphore.acquire()).await;
+
+    let mut a = queued(semaphore.acquire_many(2));
+    let mut b = queued(semaphore.acquire());
+    assert!(poll_once(&mut a).await.is_none());
+    assert!(poll_once(&mut b).await.is_none());
+    // Grants a its two permits; a is cancelled before taking them.
+    drop(held);
+    drop(a);
+    let permit = poll_once(&mut b).await.expect("permits passed on to b");
+    assert_eq!(semaphore.available_permits(), 1);
+    drop(permit);
+    drop(b);
+
+    assert_eq!(semaphore.available_permits(), 2);
+    assert_no_leaked_waiters();
+}
+
+#[tokio::test]
+async fn notify_notified() {
+    let notify = Notify::new();
+
+    cancel_queued(notify.notified()).await;
+
+    let mut a = queued(notify.notified());
+    let mut b = queued(notify.notified());
+    assert!(poll_once(&mut a).await.is_none());
+    assert!(poll_once(&mut b).await.is_none());
+    // Notifies a, which is cancelled before consuming the notification.
+    notify.notify_one();
+    drop(a);
+    assert!(poll_once(&mut b).await.is_some(), "notification passed on to b");
+    drop(b);
+
+    // Nothing left over: no permit was stored along the way.
+    cancel_queued(notify.notified()).await;
+    assert_no_leaked_waiters();
+}
+
+#[tokio::test]
+async fn rwlock_write_and_read() {
+    let lock = RwLock::new(0);
+    let reader = lock.read().await;
+
+    cancel_queued(lock.write()).await;
+
+    let mut a = queued(lock.write());
+    let mut b = queued(lock.write());
+    assert!(poll_once(&mut a).await.is_none());
+    assert!(poll_once(&mut b).await.is_none());
+    // Hands the write lock to a, which is cancelled before taking it.
+    drop(reader);
+    drop(a);
+    let writer = poll_once(&mut b).await.expect("write lock passed on to b");
+
+    cancel_queued(lock.read()).await;
+    let mut c = queued(lock.read());
+    assert!(poll_once(&mut c).await.is_none());
+    // Hands a read lock to c, which is cancelled before taking it.
+    drop(writer);
+    drop(c);
+    drop(b);
+
+    assert!(lock.try_write().is_ok());
+    assert_no_leaked_waiters();
+}
+
+#[tokio::test]
+async fn broadcast_recv() {
+    let (tx, mut rx) = broadcast::channel(4);
+
+    // A cancelled recv takes its waiter out of the list and loses nothing: the value sent after
+    // it is received by the next recv.
+    cancel_queued(rx.recv()).await;
+    tx.send(1).unwrap();
+    assert_eq!(poll_once(&mut Box::pin(rx.recv())).await, Some(Ok(1)));
+
+    cancel_queued(rx.recv()).await;
+    drop(tx);
+    assert_eq!(rx.recv().await, Err(broadcast::error::RecvError::Closed));
+    assert_no_leaked_waiters();
+}
+
+#[tokio::test]
+async fn oneshot_receiver() {
+    let (tx, mut rx) = oneshot::channel();
+
+    // Polling the receiver by reference and abandoning the poll keeps it usable.
+    assert!(poll_once(&mut rx).await.is_none());
+    tx.send(5).unwrap();
+    assert_eq!(rx.await, Ok(5));
+    assert_no_leaked_waiters();
+}
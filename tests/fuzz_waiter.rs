@@ -0,0 +1,102 @@
+//! Applies random sequences of enqueue, remove and wake operations to a `List`, checking it
+//! against a `VecDeque` model after every step.
+
+use broadcast_rs::waiter::{Elem, List};
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::task::{Context, Waker};
+
+/// Number of elems the operations pick from.
+const ELEMS: usize = 8;
+
+#[derive(Debug)]
+enum Op {
+    Enqueue(usize),
+    Remove(usize),
+    WakeOne,
+    AwakeAll,
+}
+
+fn run_fuzz(ops: Vec<usize>) {
+    let ops = ops
+        .iter()
+        .map(|i| match i % 8 {
+            0..=3 => Op::Enqueue(i / 8 % ELEMS),
+            4 | 5 => Op::Remove(i / 8 % ELEMS),
+            6 => Op::WakeOne,
+            7 => Op::AwakeAll,
+            _ => unreachable!(),
+        })
+        .collect::<Vec<_>>();
+
+    let mut cx = Context::from_waker(Waker::noop());
+    let mut list: List = List::new();
+    // Oldest at the front, the order the list wakes them in.
+    let mut reference = VecDeque::new();
+
+    // Safety: the elems stay pinned in their boxes, and all are removed at the end.
+    let elems: Vec<Pin<Box<Elem>>> =
+        (0..ELEMS).map(|_| Box::pin(unsafe { Elem::new() })).collect();
+
+    for op in ops.iter() {
+        match *op {
+            Op::Enqueue(i) => {
+                let newly = list.enqueue_waiter(&elems[i], &mut cx);
+                assert_eq!(newly, !reference.contains(&i));
+                if newly {
+                    reference.push_back(i);
+                }
+            }
+            Op::Remove(i) => {
+                // Safety: the elems are only ever queued in this list.
+                unsafe { list.remove_waiter(&elems[i]) };
+                reference.retain(|&queued| queued != i);
+            }
+            Op::WakeOne => {
+                assert_eq!(list.wake_one(), reference.pop_front().is_some());
+            }
+            Op::AwakeAll => {
+                assert_eq!(list.awake_waiters(), reference.len());
+                reference.clear();
+            }
+        }
+
+        assert_eq!(list.len(), reference.len());
+        assert_eq!(list.len_backwards(), reference.len());
+        for (i, elem) in elems.iter().enumerate() {
+            assert_eq!(list.is_queued(elem), reference.contains(&i), "elem {} after {:?}", i, op);
+        }
+    }
+
+    for elem in elems.iter() {
+        // Safety: the elems are only ever queued in this list.
+        unsafe { list.remove_waiter(elem) };
+    }
+    assert!(list.is_empty());
+}
+
+proptest::proptest! {
+    #[test]
+    fn fuzz_waiter_list(ops: Vec<usize>) {
+        run_fuzz(ops);
+    }
+}
+
+#[test]
+fn known_edge_cases() {
+    // Each value is `elem * 8 + kind`, kinds 0 to 3 enqueue, 4 and 5 remove, 6 wakes one and 7
+    // awakes all.
+    run_fuzz(vec![
+        // Wakes on an empty list.
+        6, 7,
+        // Enqueue elem 1 twice, remove it, then remove it again once it is gone.
+        8, 8, 12, 12,
+        // Queue 0, 1 and 2, remove the middle one, wake the head, then queue the woken one again.
+        0, 8, 16, 12, 6, 0,
+        // Remove the newest, 0, and then 2, emptying the list; queue 0 again and awake all.
+        4, 20, 0, 7,
+        // Remove an elem that was woken.
+        4,
+    ]);
+}
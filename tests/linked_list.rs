@@ -0,0 +1,79 @@
+//! Defines a node type outside the crate and runs it through a `LinkedList`, using the exported
+//! `Link`, `Pointers` and `generate_addr_of_methods!`.
+
+use broadcast_rs::generate_addr_of_methods;
+use broadcast_rs::util::linked_list::{Link, LinkedList, Pointers};
+
+use std::marker::PhantomPinned;
+use std::pin::Pin;
+use std::ptr::NonNull;
+
+struct Job {
+    pointers: Pointers<Job>,
+    name: &'static str,
+    cost: u32,
+    _pin: PhantomPinned,
+}
+
+generate_addr_of_methods! {
+    impl<> Job {
+        unsafe fn addr_of_pointers(self: NonNull<Self>) -> NonNull<Pointers<Job>> {
+            &self.pointers
+        }
+    }
+}
+
+unsafe impl Link for Job {
+    type Handle = NonNull<Job>;
+    type Target = Job;
+
+    fn as_raw(handle: &NonNull<Job>) -> NonNull<Job> {
+        *handle
+    }
+
+    unsafe fn from_raw(ptr: NonNull<Job>) -> NonNull<Job> {
+        ptr
+    }
+
+    unsafe fn pointers(target: NonNull<Job>) -> NonNull<Pointers<Job>> {
+        Job::addr_of_pointers(target)
+    }
+}
+
+fn job(name: &'static str, cost: u32) -> Pin<Box<Job>> {
+    Box::pin(Job {
+        pointers: Pointers::new(),
+        name,
+        cost,
+        _pin: PhantomPinned,
+    })
+}
+
+fn handle(job: &Pin<Box<Job>>) -> NonNull<Job> {
+    NonNull::from(&**job)
+}
+
+#[test]
+fn bespoke_node() {
+    let jobs = [job("a", 3), job("b", 1), job("c", 2)];
+    let mut list = LinkedList::<Job, Job>::new();
+    for job in &jobs {
+        list.push_back(handle(job));
+    }
+    assert_eq!(list.len(), 3);
+    assert_eq!(list.first().unwrap().name, "a");
+    assert_eq!(list.last().unwrap().name, "c");
+
+    // Safety: b is in the list.
+    let b = unsafe { list.remove(handle(&jobs[1])) }.unwrap();
+    assert_eq!(unsafe { b.as_ref() }.cost, 1);
+    assert_eq!(list.len_backwards(), 2);
+
+    let mut names = vec![];
+    while let Some(job) = list.pop_front() {
+        // Safety: the jobs outlive the list.
+        names.push(unsafe { job.as_ref() }.name);
+    }
+    assert_eq!(names, ["a", "c"]);
+    assert!(list.is_empty());
+}
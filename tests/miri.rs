@@ -0,0 +1,167 @@
+//! Scenarios for checking the waiter list's pointer handling under Miri.
+//!
+//! Run with:
+//!
+//!     cargo +nightly miri test --test miri
+//!
+//! The tests stick to what Miri runs quickly: no runtime, no threads and no timers. Futures are
+//! polled by hand with a waker that counts its wakes, and every elem lives in a pinned box or on
+//! the stack behind `pin!`, as it would inside a future. They also pass as ordinary tests.
+
+use broadcast_rs::waiter::{Elem, List, WaiterGuard};
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::{pin, Pin};
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+struct Counter(AtomicUsize);
+
+impl Wake for Counter {
+    fn wake(self: Arc<Self>) {
+        self.0.fetch_add(1, SeqCst);
+    }
+}
+
+fn counting_waker() -> (Waker, Arc<Counter>) {
+    let counter = Arc::new(Counter(AtomicUsize::new(0)));
+    (Waker::from(counter.clone()), counter)
+}
+
+/// A future that waits to be woken once, removing its elem from the list when dropped.
+struct Wait<'a> {
+    list: &'a RefCell<List<String>>,
+    registered: bool,
+    elem: Elem<String>,
+}
+
+impl<'a> Wait<'a> {
+    fn new(list: &'a RefCell<List<String>>) -> Wait<'a> {
+        Wait {
+            list,
+            registered: false,
+            // Safety: the elem is removed from the list by Wait's drop.
+            elem: unsafe { Elem::new() },
+        }
+    }
+}
+
+impl<'a> Future for Wait<'a> {
+    type Output = Option<String>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<String>> {
+        // Safety: the elem is not moved out of the pinned future.
+        let me = unsafe { self.get_unchecked_mut() };
+        let mut list = me.list.borrow_mut();
+        if me.registered && !list.is_queued(&me.elem) {
+            return Poll::Ready(list.take_payload(&me.elem));
+        }
+        list.enqueue_waiter(&me.elem, cx);
+        me.registered = true;
+        Poll::Pending
+    }
+}
+
+impl<'a> Drop for Wait<'a> {
+    fn drop(&mut self) {
+        // Safety: the elem is only ever queued in this list.
+        unsafe { self.list.borrow_mut().remove_waiter(&self.elem) };
+    }
+}
+
+#[test]
+fn enqueue_remove_awake() {
+    let mut list: List = List::new();
+    let (waker, counter) = counting_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    // Safety: each elem is removed or woken out of the list before it is dropped.
+    let elems: Vec<Pin<Box<Elem>>> = (0..4).map(|_| Box::pin(unsafe { Elem::new() })).collect();
+    for elem in &elems {
+        assert!(list.enqueue_waiter(elem, &mut cx));
+    }
+    // Enqueuing again leaves the elem in place.
+    assert!(!list.enqueue_waiter(&elems[0], &mut cx));
+
+    // Remove from the middle and both ends, then wake what is left.
+    unsafe {
+        list.remove_waiter(&elems[2]);
+        list.remove_waiter(&elems[0]);
+    }
+    assert_eq!(list.len(), 2);
+    assert_eq!(list.len_backwards(), 2);
+    assert_eq!(list.awake_waiters(), 2);
+    assert_eq!(counter.0.load(SeqCst), 2);
+
+    // Removing an elem that was woken, or never queued, is allowed.
+    unsafe {
+        list.remove_waiter(&elems[1]);
+        list.remove_waiter(&elems[2]);
+    }
+    assert!(list.is_empty());
+}
+
+#[test]
+fn cancel_mid_wait() {
+    let list = RefCell::new(List::new());
+    let (waker, counter) = counting_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    let mut a = Box::pin(Wait::new(&list));
+    let mut b = Box::pin(Wait::new(&list));
+    let mut c = Box::pin(Wait::new(&list));
+    assert!(a.as_mut().poll(&mut cx).is_pending());
+    assert!(b.as_mut().poll(&mut cx).is_pending());
+    assert!(c.as_mut().poll(&mut cx).is_pending());
+
+    // b is cancelled while queued, between its neighbours.
+    drop(b);
+    assert_eq!(list.borrow().len(), 2);
+
+    // a is handed a value, then cancelled before taking it: its drop frees the value.
+    assert_eq!(list.borrow_mut().wake_one_with(String::from("first")), Ok(()));
+    drop(a);
+
+    assert_eq!(list.borrow_mut().wake_one_with(String::from("second")), Ok(()));
+    assert_eq!(c.as_mut().poll(&mut cx), Poll::Ready(Some(String::from("second"))));
+    assert_eq!(counter.0.load(SeqCst), 2);
+    assert!(list.borrow().is_empty());
+}
+
+#[test]
+fn drop_ordering() {
+    let (waker, _counter) = counting_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    // Elems on the stack, dropped in reverse order of declaration after the list is cleared.
+    {
+        let mut list: List<String> = List::new();
+        // Safety: both elems are taken out of the list by clear.
+        let a = pin!(unsafe { Elem::new() });
+        let b = pin!(unsafe { Elem::new() });
+        list.enqueue_waiter_with(&a, &mut cx, String::from("a"));
+        list.enqueue_waiter_with(&b, &mut cx, String::from("b"));
+        // The payloads are left in the elems, and freed with them.
+        assert_eq!(list.clear(), 2);
+    }
+
+    // Guards removing themselves, in either order, before the list goes.
+    {
+        let list = RefCell::new(List::<()>::new());
+        // Safety: the guards are boxed, so they don't move once queued, and dropped before the
+        // list.
+        let first = Box::pin(unsafe { WaiterGuard::new(&list) });
+        let second = Box::pin(unsafe { WaiterGuard::new(&list) });
+        let third = Box::pin(unsafe { WaiterGuard::new(&list) });
+        first.enqueue(&mut cx);
+        second.enqueue(&mut cx);
+        third.enqueue(&mut cx);
+        drop(second);
+        drop(first);
+        assert_eq!(list.borrow().len(), 1);
+        drop(third);
+        assert!(list.borrow().is_empty());
+    }
+}
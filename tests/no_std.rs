@@ -0,0 +1,35 @@
+//! Uses the waiter list from a `#![no_std]` crate, with only `core` paths.
+//!
+//! To check the library itself builds without `std`, run this with the default features off:
+//!
+//!     cargo test --no-default-features --test no_std
+
+#![no_std]
+
+// Only for the test harness.
+extern crate std;
+
+use broadcast_rs::waiter::{Elem, List};
+use core::cell::RefCell;
+use core::pin::pin;
+use core::task::{Context, Waker};
+
+#[test]
+fn waiter_list_with_core_only() {
+    let list = RefCell::new(List::<u32>::new());
+    let mut cx = Context::from_waker(Waker::noop());
+
+    // Safety: both elems are removed from, or woken out of, the list before they are dropped.
+    let a = pin!(unsafe { Elem::new() });
+    let b = pin!(unsafe { Elem::new() });
+    assert!(list.borrow_mut().enqueue_waiter(&a, &mut cx));
+    assert!(list.borrow_mut().enqueue_waiter(&b, &mut cx));
+    assert_eq!(list.borrow().len(), 2);
+
+    assert_eq!(list.borrow_mut().wake_one_with(7), Ok(()));
+    assert!(!list.borrow().is_queued(&a));
+    assert_eq!(list.borrow_mut().take_payload(&a), Some(7));
+
+    unsafe { list.borrow_mut().remove_waiter(&b) };
+    assert!(list.borrow().is_empty());
+}